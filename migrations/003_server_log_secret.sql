@@ -0,0 +1,3 @@
+/* value of `sv_logsecret` on the server, log lines received without it are rejected */
+ALTER TABLE servers
+    ADD COLUMN log_secret varchar;
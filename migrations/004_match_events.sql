@@ -0,0 +1,14 @@
+/* log entries of the game server, recorded while a match is played on it */
+CREATE TABLE match_events
+(
+    id         bigserial                                                        NOT NULL, /* order the entries were logged in, timestamps only have a precision of a second */
+    match_id   uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    map_number integer                                                          NOT NULL,
+    round      integer                                                          NOT NULL,
+    timestamp  timestamp                                                        NOT NULL, /* local time of the game server */
+    event_type varchar                                                          NOT NULL,
+    payload    jsonb                                                            NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX match_events_match_id_idx ON match_events (match_id);
@@ -0,0 +1,21 @@
+CREATE TYPE game_side AS ENUM (
+    'ct',
+    't'
+    );
+
+/* rounds played on the maps of a match, put together from the log of the game server */
+CREATE TABLE match_rounds
+(
+    match_id    uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    map_number  integer                                                          NOT NULL,
+    number      integer                                                          NOT NULL CHECK ( number > 0 ),
+    half        integer                                                          NOT NULL, /* increased at every side swap, overtime included */
+    overtime    integer                                                          NOT NULL, /* 0 for regulation time */
+    winner_side game_side                                                        NOT NULL,
+    reason      varchar                                                          NOT NULL,
+    ct_score    integer                                                          NOT NULL,
+    t_score     integer                                                          NOT NULL,
+    team1_score integer, /* unknown until the side of team1 is known */
+    team2_score integer,
+    PRIMARY KEY (match_id, map_number, number)
+);
@@ -0,0 +1,28 @@
+/* statistics of the players on a map of a match, see `crate::csgo::stats::PlayerStats` */
+CREATE TABLE player_map_stats
+(
+    match_id          uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    map_number        integer                                                          NOT NULL,
+    steamid           varchar                                                          NOT NULL,
+    nick              varchar                                                          NOT NULL,
+    rounds            integer                                                          NOT NULL,
+    kills             integer                                                          NOT NULL,
+    deaths            integer                                                          NOT NULL,
+    assists           integer                                                          NOT NULL,
+    flash_assists     integer                                                          NOT NULL,
+    headshot_kills    integer                                                          NOT NULL,
+    damage            integer                                                          NOT NULL,
+    kast_rounds       integer                                                          NOT NULL,
+    entry_kills       integer                                                          NOT NULL,
+    entry_deaths      integer                                                          NOT NULL,
+    one_kill_rounds   integer                                                          NOT NULL,
+    two_kill_rounds   integer                                                          NOT NULL,
+    three_kill_rounds integer                                                          NOT NULL,
+    four_kill_rounds  integer                                                          NOT NULL,
+    five_kill_rounds  integer                                                          NOT NULL,
+    clutches_played   integer                                                          NOT NULL,
+    clutches_won      integer                                                          NOT NULL,
+    PRIMARY KEY (match_id, map_number, steamid)
+);
+
+CREATE INDEX player_map_stats_steamid_idx ON player_map_stats (steamid);
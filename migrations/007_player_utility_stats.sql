@@ -0,0 +1,12 @@
+/* use of grenades by the players on a map, see `crate::csgo::utility::UtilityStats` */
+ALTER TABLE player_map_stats
+    ADD COLUMN flashbangs_thrown  integer          NOT NULL DEFAULT 0,
+    ADD COLUMN smokes_thrown      integer          NOT NULL DEFAULT 0,
+    ADD COLUMN he_grenades_thrown integer          NOT NULL DEFAULT 0,
+    ADD COLUMN molotovs_thrown    integer          NOT NULL DEFAULT 0,
+    ADD COLUMN decoys_thrown      integer          NOT NULL DEFAULT 0,
+    ADD COLUMN enemies_flashed    integer          NOT NULL DEFAULT 0,
+    ADD COLUMN teammates_flashed  integer          NOT NULL DEFAULT 0,
+    ADD COLUMN enemy_blind_time   double precision NOT NULL DEFAULT 0,
+    ADD COLUMN he_damage          integer          NOT NULL DEFAULT 0,
+    ADD COLUMN molotov_damage     integer          NOT NULL DEFAULT 0;
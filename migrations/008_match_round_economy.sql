@@ -0,0 +1,20 @@
+CREATE TYPE buy_type AS ENUM (
+    'eco',
+    'force',
+    'half',
+    'full'
+    );
+
+/* economy of the sides in the rounds of a match, see `crate::csgo::economy::EconomyTracker` */
+CREATE TABLE match_round_economy
+(
+    match_id        uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    map_number      integer                                                          NOT NULL,
+    round           integer                                                          NOT NULL CHECK ( round > 0 ),
+    side            game_side                                                        NOT NULL,
+    starting_money  integer                                                          NOT NULL,
+    money_spent     integer                                                          NOT NULL, /* refunds deducted */
+    equipment_value integer                                                          NOT NULL, /* when leaving the buyzone */
+    buy_type        buy_type                                                         NOT NULL,
+    PRIMARY KEY (match_id, map_number, round, side)
+);
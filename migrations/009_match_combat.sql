@@ -0,0 +1,31 @@
+CREATE TYPE combat_kind AS ENUM (
+    'kill',
+    'damage'
+    );
+
+/* kills and damage dealt in the rounds of a match, with the position of both players */
+CREATE TABLE match_combat
+(
+    id               bigserial PRIMARY KEY,
+    match_id         uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    map_number       integer                                                          NOT NULL,
+    map_name         varchar                                                          NOT NULL,
+    round            integer                                                          NOT NULL CHECK ( round > 0 ),
+    kind             combat_kind                                                      NOT NULL,
+    attacker         varchar                                                          NOT NULL, /* Steam ID, or BOT followed by the nickname */
+    attacker_side    game_side,
+    attacker_x       integer                                                          NOT NULL,
+    attacker_y       integer                                                          NOT NULL,
+    attacker_z       integer                                                          NOT NULL,
+    victim           varchar                                                          NOT NULL,
+    victim_side      game_side,
+    victim_x         integer                                                          NOT NULL,
+    victim_y         integer                                                          NOT NULL,
+    victim_z         integer                                                          NOT NULL,
+    weapon           varchar                                                          NOT NULL,
+    damage           integer                                                          NOT NULL, /* 0 for kills */
+    headshot         boolean                                                          NOT NULL
+);
+
+CREATE INDEX match_combat_match_id_idx ON match_combat (match_id, map_number, round);
+CREATE INDEX match_combat_map_name_idx ON match_combat (map_name);
@@ -0,0 +1,30 @@
+CREATE TYPE get5_state AS ENUM (
+    'idle',
+    'pre_veto',
+    'veto',
+    'warmup',
+    'knife_round',
+    'waiting_for_knife_round_decision',
+    'going_live',
+    'live',
+    'post_game'
+    );
+
+/* last known status of the game servers, updated by polling `get5_status` */
+CREATE TABLE server_status
+(
+    server_id       uuid PRIMARY KEY REFERENCES servers (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    reachable       boolean   NOT NULL,
+    error           varchar, /* why the last poll failed */
+    polled_at       timestamp NOT NULL,
+    /* as of the last successful poll, null if Get5 did not reply */
+    state           get5_state,
+    matchid         varchar,
+    map_number      integer,
+    paused          boolean,
+    plugin_version  varchar,
+    team1_connected integer,
+    team1_ready     boolean,
+    team2_connected integer,
+    team2_ready     boolean
+);
@@ -0,0 +1,23 @@
+CREATE TYPE match_action AS ENUM (
+    'pause',
+    'unpause',
+    'force_ready',
+    'end_match',
+    'restore_backup',
+    'skip_veto'
+    );
+
+/* audit log of the commands sent to the game servers to control a match */
+CREATE TABLE match_actions
+(
+    id           bigserial PRIMARY KEY,
+    match_id     uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    server_id    uuid REFERENCES servers (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    action       match_action                                                     NOT NULL,
+    argument     varchar, /* e.g. the round a backup is restored to */
+    triggered_by varchar                                                          NOT NULL,
+    triggered_at timestamp                                                        NOT NULL,
+    error        varchar /* null if the command succeeded */
+);
+
+CREATE INDEX match_actions_match_id_idx ON match_actions (match_id);
@@ -0,0 +1,13 @@
+/* optional settings passed on to Get5 in the match configuration */
+ALTER TABLE matches
+    ADD COLUMN match_title              varchar,
+    ADD COLUMN favored_percentage_team1 integer CHECK ( favored_percentage_team1 BETWEEN 0 AND 100 ),
+    ADD COLUMN favored_percentage_text  varchar;
+
+/* maps won by each team in the series, the score columns hold the rounds won on the current map */
+ALTER TABLE matches
+    ADD COLUMN team1_series_score integer NOT NULL DEFAULT 0,
+    ADD COLUMN team2_series_score integer NOT NULL DEFAULT 0;
+
+ALTER TABLE teams
+    ADD COLUMN tag varchar;
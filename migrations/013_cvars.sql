@@ -0,0 +1,24 @@
+/* cvars passed on to Get5 in the match configuration, overriding the defaults of the server */
+CREATE TABLE match_cvars
+(
+    match_id uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    name     varchar                                                          NOT NULL,
+    value    varchar                                                          NOT NULL,
+    PRIMARY KEY (match_id, name)
+);
+
+/* cvars passed on to Get5 for every match played on the server */
+CREATE TABLE server_cvars
+(
+    server_id uuid REFERENCES servers (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    name      varchar                                                          NOT NULL,
+    value     varchar                                                          NOT NULL,
+    PRIMARY KEY (server_id, name)
+);
+
+/* changes of the cvars are recorded in the audit log, without a match for the defaults of a server */
+ALTER TYPE match_action ADD VALUE 'set_cvars';
+ALTER TYPE match_action ADD VALUE 'remove_cvars';
+
+ALTER TABLE match_actions
+    ALTER COLUMN match_id DROP NOT NULL;
@@ -0,0 +1,5 @@
+/* when the match was created, to list the matches by date */
+ALTER TABLE matches
+    ADD COLUMN created_at timestamp NOT NULL DEFAULT (now() AT TIME ZONE 'utc');
+
+CREATE INDEX matches_created_at ON matches (created_at);
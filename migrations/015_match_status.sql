@@ -0,0 +1,37 @@
+CREATE TYPE match_status AS ENUM (
+    'pending',
+    'warmup',
+    'veto',
+    'knife_round',
+    'live',
+    'paused',
+    'finished',
+    'cancelled'
+    );
+
+CREATE TYPE status_source AS ENUM (
+    'operator',
+    'log',
+    'poll'
+    );
+
+/* changed only along the transitions allowed by `crate::status` */
+ALTER TABLE matches
+    ADD COLUMN status match_status NOT NULL DEFAULT 'pending';
+
+CREATE INDEX matches_status ON matches (status);
+
+/* every change of the status of a match, and what caused it */
+CREATE TABLE match_status_history
+(
+    id           bigserial PRIMARY KEY,
+    match_id     uuid REFERENCES matches (id) ON UPDATE CASCADE ON DELETE CASCADE NOT NULL,
+    from_status  match_status                                                     NOT NULL,
+    to_status    match_status                                                     NOT NULL,
+    source       status_source                                                    NOT NULL,
+    event        varchar                                                          NOT NULL, /* e.g. the mutation or the Get5 event */
+    triggered_by varchar, /* the operator, null for changes observed on the server */
+    changed_at   timestamp                                                        NOT NULL
+);
+
+CREATE INDEX match_status_history_match_id_idx ON match_status_history (match_id);
@@ -1,11 +1,21 @@
 extern crate minictrl;
 
 use std::env;
+use std::time::Duration;
 
+use async_std::channel;
+use async_std::task;
 use sqlx::postgres::PgPoolOptions;
 
+use minictrl::csgo::rcon::poller::poll_server_status;
+use minictrl::csgo::rcon::{
+    CommandFilter, RconManager, DEFAULT_ALLOWED_COMMANDS, DEFAULT_DENIED_COMMANDS,
+};
+use minictrl::csgo::receiver::udp::udp_log_receiver;
+use minictrl::csgo::receiver::{process_log_entries, LogRouter};
 use minictrl::database::run_migrations;
-use minictrl::web::webserver_start;
+use minictrl::events::EventBus;
+use minictrl::web::{webserver_start, ConsoleConfig, OperatorToken, PublicUrl};
 
 #[async_std::main]
 async fn main() -> anyhow::Result<()> {
@@ -26,5 +36,74 @@ async fn main() -> anyhow::Result<()> {
 
     run_migrations(&pool).await?;
 
-    webserver_start(pool).await
+    // Changes to the matches and servers, see the GraphQL subscriptions
+    let events = EventBus::new();
+
+    // Process the log entries received from the game servers
+    let (log_sender, log_receiver) = channel::bounded(1024);
+    task::spawn(process_log_entries(
+        log_receiver,
+        pool.clone(),
+        events.clone(),
+    ));
+    let log_router = LogRouter::new(log_sender);
+
+    // Receive logs over UDP, see `logaddress_add`
+    let udp_address = env::var("LOG_UDP_ADDRESS").unwrap_or_else(|_| "0.0.0.0:27500".to_string());
+    let udp_receiver = udp_log_receiver(udp_address, pool.clone(), log_router.clone());
+    task::spawn(async move {
+        if let Err(err) = udp_receiver.await {
+            tracing::error!("UDP log receiver stopped: {}", err);
+        }
+    });
+
+    // RCON connections to the game servers
+    let rcon = RconManager::new(pool.clone());
+
+    // Keep the status of the game servers up to date, see `server_status`
+    let poll_interval = env::var("STATUS_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(30);
+    task::spawn(poll_server_status(
+        pool.clone(),
+        rcon.clone(),
+        events.clone(),
+        Duration::from_secs(poll_interval),
+    ));
+
+    // Address given to the game servers to download the match configurations
+    let public_url =
+        PublicUrl(env::var("PUBLIC_URL").unwrap_or_else(|_| "http://127.0.0.1:8080".to_string()));
+
+    // RCON console for the admins, disabled unless a token is set. The allow and deny lists are
+    // comma separated commands, every command not denied is allowed if the allow list is set empty
+    let console = env::var("CONSOLE_TOKEN").ok().map(|token| {
+        let allow =
+            env::var("CONSOLE_ALLOW").unwrap_or_else(|_| DEFAULT_ALLOWED_COMMANDS.join(","));
+        let deny = env::var("CONSOLE_DENY").unwrap_or_else(|_| DEFAULT_DENIED_COMMANDS.join(","));
+
+        ConsoleConfig {
+            token,
+            filter: CommandFilter::new(allow.split(','), deny.split(',')),
+        }
+    });
+
+    // Operators allowed to control the matches, as comma separated `name:token` pairs. The name
+    // is recorded along the commands the operator sends to the servers
+    let operators = env::var("OPERATOR_TOKENS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|operator| operator.split_once(':'))
+        .map(|(name, token)| OperatorToken {
+            name: name.trim().to_string(),
+            token: token.trim().to_string(),
+        })
+        .filter(|operator| !operator.name.is_empty() && !operator.token.is_empty())
+        .collect();
+
+    webserver_start(
+        pool, log_router, rcon, events, public_url, console, operators,
+    )
+    .await
 }
@@ -2,7 +2,7 @@ use async_graphql::*;
 use rand::Fill;
 
 #[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type)]
-#[sqlx(rename_all = "snake_case")]
+#[sqlx(type_name = "side", rename_all = "snake_case")]
 pub enum SideType {
     #[graphql(name = "standard")]
     Standard,
@@ -12,6 +12,165 @@ pub enum SideType {
     AlwaysKnife,
 }
 
+/// Side of the map a team plays on
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type, Serialize)]
+#[sqlx(type_name = "game_side")]
+pub enum GameSide {
+    #[graphql(name = "ct")]
+    #[sqlx(rename = "ct")]
+    CT,
+    #[graphql(name = "t")]
+    #[sqlx(rename = "t")]
+    T,
+}
+
+/// How much a team invested in its equipment for a round
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type, Serialize)]
+#[sqlx(type_name = "buy_type", rename_all = "snake_case")]
+pub enum BuyType {
+    #[graphql(name = "eco")]
+    Eco,
+    #[graphql(name = "force")]
+    Force,
+    #[graphql(name = "half")]
+    Half,
+    #[graphql(name = "full")]
+    Full,
+}
+
+/// State of Get5 on a game server
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type, Serialize)]
+#[sqlx(type_name = "get5_state", rename_all = "snake_case")]
+pub enum Get5State {
+    #[graphql(name = "idle")]
+    Idle,
+    #[graphql(name = "pre_veto")]
+    PreVeto,
+    #[graphql(name = "veto")]
+    Veto,
+    #[graphql(name = "warmup")]
+    Warmup,
+    #[graphql(name = "knife_round")]
+    KnifeRound,
+    #[graphql(name = "waiting_for_knife_round_decision")]
+    WaitingForKnifeRoundDecision,
+    #[graphql(name = "going_live")]
+    GoingLive,
+    #[graphql(name = "live")]
+    Live,
+    #[graphql(name = "post_game")]
+    PostGame,
+}
+
+/// Command sent to a game server to control a match, or change of the cvars Get5 sets, see
+/// `crate::database::models::MatchAction`
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type, Serialize)]
+#[sqlx(type_name = "match_action", rename_all = "snake_case")]
+pub enum MatchActionKind {
+    #[graphql(name = "pause")]
+    Pause,
+    #[graphql(name = "unpause")]
+    Unpause,
+    #[graphql(name = "force_ready")]
+    ForceReady,
+    #[graphql(name = "end_match")]
+    EndMatch,
+    #[graphql(name = "restore_backup")]
+    RestoreBackup,
+    #[graphql(name = "skip_veto")]
+    SkipVeto,
+    #[graphql(name = "set_cvars")]
+    SetCvars,
+    #[graphql(name = "remove_cvars")]
+    RemoveCvars,
+}
+
+/// Kind of a combat event, see `crate::database::models::MatchCombat`
+#[derive(Clone, Debug, Copy, Eq, PartialEq, sqlx::Type, Serialize)]
+#[sqlx(type_name = "combat_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CombatKind {
+    Kill,
+    Damage,
+}
+
+/// Stage of the lifecycle of a match, see `crate::status` for the allowed transitions
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type, Serialize)]
+#[sqlx(type_name = "match_status", rename_all = "snake_case")]
+pub enum MatchStatus {
+    /// Created, but not loaded on a server yet
+    #[graphql(name = "pending")]
+    Pending,
+    /// Loaded on its server, waiting for the players to ready up
+    #[graphql(name = "warmup")]
+    Warmup,
+    #[graphql(name = "veto")]
+    Veto,
+    #[graphql(name = "knife_round")]
+    KnifeRound,
+    #[graphql(name = "live")]
+    Live,
+    #[graphql(name = "paused")]
+    Paused,
+    /// The series was played to the end
+    #[graphql(name = "finished")]
+    Finished,
+    /// The series was ended without a winner
+    #[graphql(name = "cancelled")]
+    Cancelled,
+}
+
+/// What observed or requested a change of the status of a match
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum, sqlx::Type, Serialize)]
+#[sqlx(type_name = "status_source", rename_all = "snake_case")]
+pub enum StatusSource {
+    /// Mutation sent by an operator
+    #[graphql(name = "operator")]
+    Operator,
+    /// Event in the log of the server
+    #[graphql(name = "log")]
+    Log,
+    /// State of Get5 polled from the server
+    #[graphql(name = "poll")]
+    Poll,
+}
+
+/// Order of a list of teams, see `crate::database::list_teams`
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum)]
+pub enum TeamOrder {
+    #[graphql(name = "name")]
+    Name,
+    #[graphql(name = "country")]
+    Country,
+}
+
+/// Order of a list of players, see `crate::database::list_players`
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum)]
+pub enum PlayerOrder {
+    #[graphql(name = "name")]
+    Name,
+    #[graphql(name = "steamid")]
+    Steamid,
+}
+
+/// Order of a list of servers, see `crate::database::list_servers`
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum)]
+pub enum ServerOrder {
+    #[graphql(name = "host")]
+    Host,
+    #[graphql(name = "type")]
+    Type,
+}
+
+/// Order of a list of matches, see `crate::database::list_matches`
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Enum)]
+pub enum MatchOrder {
+    #[graphql(name = "created_at")]
+    CreatedAt,
+    #[graphql(name = "title")]
+    Title,
+}
+
 pub(crate) fn generate_password() -> anyhow::Result<String> {
     const SIZE: usize = 16;
 
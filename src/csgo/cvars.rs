@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::csgo::rcon::DEFAULT_DENIED_COMMANDS;
+
+/// Longest cvar name accepted by the Source engine
+const MAX_CVAR_NAME_LENGTH: usize = 64;
+
+/// Prefixes of the cvars that can be set from a match configuration: the game rules, GOTV and the
+/// server settings
+///
+/// Get5 runs a name that is not a cvar as a command, so any other name could run a command on the
+/// server when the match is loaded.
+const ALLOWED_CVAR_PREFIXES: &[&str] = &["mp_", "tv_", "sv_"];
+
+/// Cvars without an allowed prefix that can be set from a match configuration
+const ALLOWED_CVARS: &[&str] = &["hostname"];
+
+/// Cvars and commands with an allowed prefix that can't be set from a match configuration, next
+/// to the commands denied in the console (see `DEFAULT_DENIED_COMMANDS`)
+///
+/// Get5 sets the cvars when the match is loaded, which would undo the player password set while
+/// loading the match (see `crate::csgo::rcon::load_match`), lock us out of the RCON, or stop the
+/// server, the recording of the demo or the match itself.
+const RESERVED_CVARS: &[&str] = &[
+    "rcon_password",
+    "sv_password",
+    "sv_shutdown",
+    "mp_backup_restore_load_file",
+    "tv_record",
+    "tv_stop",
+    "tv_stoprecord",
+];
+
+lazy_static! {
+    static ref CVAR_NAME: Regex = Regex::new(r"^[A-Za-z0-9_]+$").unwrap();
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CvarError {
+    #[error(
+        "The cvar name {0:?} is not valid, it may only contain letters, digits and underscores"
+    )]
+    InvalidName(String),
+    #[error("The cvar name {0:?} is too long")]
+    NameTooLong(String),
+    #[error("The cvar {0} can't be set for a match")]
+    Reserved(String),
+    #[error("The cvar {0} is not a game rule, GOTV or server setting")]
+    NotAllowed(String),
+    #[error("The value of the cvar {0} contains a line break or a semicolon")]
+    InvalidValue(String),
+}
+
+/// Checks that a cvar can be passed on to Get5, and returns its name in lower case
+pub fn validate_cvar(name: &str, value: &str) -> Result<String, CvarError> {
+    if !CVAR_NAME.is_match(name) {
+        return Err(CvarError::InvalidName(name.to_string()));
+    }
+    if name.len() > MAX_CVAR_NAME_LENGTH {
+        return Err(CvarError::NameTooLong(name.to_string()));
+    }
+
+    // Cvar names are case-insensitive
+    let name = name.to_lowercase();
+    if RESERVED_CVARS.contains(&name.as_str()) || DEFAULT_DENIED_COMMANDS.contains(&name.as_str()) {
+        return Err(CvarError::Reserved(name));
+    }
+    let allowed = ALLOWED_CVARS.contains(&name.as_str())
+        || ALLOWED_CVAR_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix));
+    if !allowed {
+        return Err(CvarError::NotAllowed(name));
+    }
+    // The server runs every part of a line separated by a semicolon or a new line as a command
+    if value.contains(['\n', '\r', ';']) {
+        return Err(CvarError::InvalidValue(name));
+    }
+
+    Ok(name)
+}
+
+/// Overlays the cvars of a match over the default cvars of its server
+pub fn merge_cvars<D, M>(server_defaults: D, match_overrides: M) -> BTreeMap<String, String>
+where
+    D: IntoIterator<Item = (String, String)>,
+    M: IntoIterator<Item = (String, String)>,
+{
+    let mut cvars = server_defaults.into_iter().collect::<BTreeMap<_, _>>();
+    cvars.extend(match_overrides);
+
+    cvars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_cvars, validate_cvar, CvarError};
+
+    #[test]
+    fn cvar_validation() {
+        assert_eq!(validate_cvar("tv_delay", "90"), Ok("tv_delay".to_string()));
+        assert_eq!(
+            validate_cvar("Mp_Overtime_Enable", "1"),
+            Ok("mp_overtime_enable".to_string())
+        );
+        assert_eq!(
+            validate_cvar("hostname", "Finals \"A\""),
+            Ok("hostname".to_string())
+        );
+
+        assert_eq!(
+            validate_cvar("tv_delay; quit", "1"),
+            Err(CvarError::InvalidName("tv_delay; quit".to_string()))
+        );
+        assert_eq!(
+            validate_cvar("", "1"),
+            Err(CvarError::InvalidName("".to_string()))
+        );
+        assert!(matches!(
+            validate_cvar(&"a".repeat(65), "1"),
+            Err(CvarError::NameTooLong(_))
+        ));
+        assert_eq!(
+            validate_cvar("SV_PASSWORD", "secret"),
+            Err(CvarError::Reserved("sv_password".to_string()))
+        );
+        assert_eq!(
+            validate_cvar("hostname", "a\nquit"),
+            Err(CvarError::InvalidValue("hostname".to_string()))
+        );
+        assert_eq!(
+            validate_cvar("hostname", "a; quit"),
+            Err(CvarError::InvalidValue("hostname".to_string()))
+        );
+    }
+
+    #[test]
+    fn commands_are_not_cvars() {
+        // Denied in the console
+        for command in [
+            "quit",
+            "sm_rcon",
+            "logaddress_delall",
+            "sv_logsecret",
+            "exec",
+        ] {
+            assert_eq!(
+                validate_cvar(command, "1"),
+                Err(CvarError::Reserved(command.to_string()))
+            );
+        }
+        assert_eq!(
+            validate_cvar("Sv_Shutdown", "1"),
+            Err(CvarError::Reserved("sv_shutdown".to_string()))
+        );
+
+        // Neither game rules, GOTV nor server settings
+        for command in ["logaddress_add", "changelevel", "get5_endmatch", "kick"] {
+            assert_eq!(
+                validate_cvar(command, "1"),
+                Err(CvarError::NotAllowed(command.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn match_cvars_override_server_defaults() {
+        let server = vec![
+            ("hostname".to_string(), "Server 1".to_string()),
+            ("tv_delay".to_string(), "90".to_string()),
+        ];
+        let r#match = vec![
+            ("hostname".to_string(), "Grand final".to_string()),
+            ("mp_overtime_enable".to_string(), "1".to_string()),
+        ];
+
+        let cvars = merge_cvars(server, r#match);
+        assert_eq!(
+            cvars.into_iter().collect::<Vec<(String, String)>>(),
+            vec![
+                ("hostname".to_string(), "Grand final".to_string()),
+                ("mp_overtime_enable".to_string(), "1".to_string()),
+                ("tv_delay".to_string(), "90".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use crate::common::BuyType;
+use crate::csgo::logs::{LogEntry, Player, Team, TeamAll};
+use crate::csgo::stats::{player_key, player_side};
+
+/// Average equipment value per player from which a round is a full buy
+const FULL_BUY_EQUIPMENT_VALUE: i32 = 4000;
+/// Average equipment value per player below which a round is an eco
+const ECO_EQUIPMENT_VALUE: i32 = 1500;
+/// Share of the starting money a team must spend for a round between eco and full buy to be a
+/// force buy rather than a half buy
+const FORCE_BUY_SPENT_RATIO: f64 = 0.8;
+
+/// Price of an instrument, as named in `LogEntry::PlayerLeftBuyzone`
+///
+/// Instruments are named with or without their `weapon_` or `item_` prefix, and may be followed
+/// by a quantity, e.g. `kevlar(100)`. The knife, the bomb and unknown instruments are free.
+pub fn instrument_price(instrument: &str) -> i32 {
+    let name = instrument.split('(').next().unwrap_or_default();
+    let name = name
+        .trim_start_matches("weapon_")
+        .trim_start_matches("item_");
+
+    match name {
+        // Pistols
+        "glock" | "hkp2000" | "usp_silencer" => 200,
+        "p250" | "elite" => 300,
+        "fiveseven" | "tec9" | "cz75a" => 500,
+        "revolver" => 600,
+        "deagle" => 700,
+        // SMGs
+        "mac10" => 1050,
+        "ump45" => 1200,
+        "mp9" => 1250,
+        "bizon" => 1400,
+        "mp7" | "mp5sd" => 1500,
+        "p90" => 2350,
+        // Heavy
+        "nova" => 1050,
+        "sawedoff" => 1100,
+        "mag7" => 1300,
+        "negev" => 1700,
+        "xm1014" => 2000,
+        "m249" => 5200,
+        // Rifles
+        "ssg08" => 1700,
+        "galilar" => 1800,
+        "famas" => 2050,
+        "ak47" => 2700,
+        "m4a1_silencer" => 2900,
+        "sg556" => 3000,
+        "m4a1" => 3100,
+        "aug" => 3300,
+        "awp" => 4750,
+        "g3sg1" | "scar20" => 5000,
+        // Grenades
+        "decoy" => 50,
+        "flashbang" => 200,
+        "hegrenade" | "smokegrenade" => 300,
+        "molotov" => 400,
+        "incgrenade" => 600,
+        // Gear
+        "taser" => 200,
+        "helmet" => 350,
+        "defuser" => 400,
+        "kevlar" | "vest" => 650,
+        "assaultsuit" | "vesthelm" => 1000,
+        _ => 0,
+    }
+}
+
+/// Economy of a side in a round
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundEconomy {
+    pub side: Team,
+    /// Money of the players on the side before buying
+    pub starting_money: i32,
+    /// Money spent on equipment, less the refunds
+    pub money_spent: i32,
+    /// Value of the equipment the players carried when leaving the buyzone
+    pub equipment_value: i32,
+    pub buy_type: BuyType,
+}
+
+/// Economy of a player in the round being played
+#[derive(Debug, Default)]
+struct PlayerEconomy {
+    starting_money: Option<i32>,
+    money_spent: i32,
+    equipment_value: i32,
+}
+
+/// Rebuilds the economy of both sides from the log of the game server
+///
+/// Purchases are logged during the freeze time, before the `Round_Start` of the round, such that
+/// every entry logged after a round ended belongs to the next round. The money of a player before
+/// their first purchase is their starting money, players that did not buy anything before the
+/// round started keep the money they had at that time.
+#[derive(Default)]
+pub struct EconomyTracker {
+    /// Side of the players in the game, by player key
+    sides: HashMap<String, Team>,
+    /// Latest money of the players, by player key
+    money: HashMap<String, i32>,
+    /// Economy of the players in the round being played, by player key
+    round: HashMap<String, PlayerEconomy>,
+}
+
+impl EconomyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the round being played, but keeps the players in the game
+    pub fn restart(&mut self) {
+        self.round.clear();
+    }
+
+    fn see_player(&mut self, player: &Player) -> Option<String> {
+        let key = player_key(player)?;
+        if let Some(side) = player_side(player) {
+            self.sides.insert(key.clone(), side);
+        }
+
+        Some(key)
+    }
+
+    pub fn process_entry(&mut self, entry: &LogEntry) {
+        match entry {
+            LogEntry::WorldTriggeredEvent { event, .. } if event == "Round_Start" => {
+                for (key, money) in self.money.iter() {
+                    let player = self.round.entry(key.clone()).or_default();
+                    player.starting_money.get_or_insert(*money);
+                }
+            }
+            LogEntry::MoneyChanged {
+                player,
+                previously,
+                operation,
+                change,
+                new_amount,
+                instrument,
+                ..
+            } => {
+                let key = match self.see_player(player) {
+                    Some(key) => key,
+                    None => return,
+                };
+                self.money.insert(key.clone(), *new_amount);
+
+                if instrument.is_some() {
+                    let player = self.round.entry(key).or_default();
+                    player.starting_money.get_or_insert(*previously);
+                    match operation.as_str() {
+                        "-" => player.money_spent += change,
+                        // Equipment sold back during the freeze time
+                        _ => player.money_spent -= change,
+                    }
+                }
+            }
+            LogEntry::PlayerPurchased { player, .. } => {
+                self.see_player(player);
+            }
+            LogEntry::PlayerLeftBuyzone {
+                player,
+                instruments,
+                ..
+            } => {
+                if let Some(key) = self.see_player(player) {
+                    self.round.entry(key).or_default().equipment_value = instruments
+                        .iter()
+                        .map(|instrument| instrument_price(instrument))
+                        .sum();
+                }
+            }
+            LogEntry::SwitchedTeam { player, to, .. } => {
+                if let Some(key) = player_key(player) {
+                    match to {
+                        TeamAll::CT => self.sides.insert(key, Team::CT),
+                        TeamAll::TERRORIST => self.sides.insert(key, Team::TERRORIST),
+                        _ => self.sides.remove(&key),
+                    };
+                }
+            }
+            LogEntry::PlayerDisconnected { player, .. } => {
+                if let Some(key) = player_key(player) {
+                    self.sides.remove(&key);
+                    self.money.remove(&key);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Economy of both sides in the round that just ended
+    pub fn finish_round(&mut self) -> Vec<RoundEconomy> {
+        let round = std::mem::take(&mut self.round);
+
+        [Team::CT, Team::TERRORIST]
+            .iter()
+            .filter_map(|side| {
+                let players = round
+                    .iter()
+                    .filter(|(key, _)| self.sides.get(*key) == Some(side))
+                    .map(|(_, player)| player)
+                    .collect::<Vec<_>>();
+                if players.is_empty() {
+                    return None;
+                }
+
+                let starting_money = players
+                    .iter()
+                    .map(|player| player.starting_money.unwrap_or_default())
+                    .sum();
+                let money_spent = players.iter().map(|player| player.money_spent).sum();
+                let equipment_value = players.iter().map(|player| player.equipment_value).sum();
+
+                Some(RoundEconomy {
+                    side: *side,
+                    starting_money,
+                    money_spent,
+                    equipment_value,
+                    buy_type: buy_type(
+                        players.len() as i32,
+                        starting_money,
+                        money_spent,
+                        equipment_value,
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Classifies a buy from the average equipment value of the players and the money they spent
+fn buy_type(players: i32, starting_money: i32, money_spent: i32, equipment_value: i32) -> BuyType {
+    let average_equipment_value = equipment_value / players.max(1);
+
+    if average_equipment_value >= FULL_BUY_EQUIPMENT_VALUE {
+        BuyType::Full
+    } else if average_equipment_value < ECO_EQUIPMENT_VALUE {
+        BuyType::Eco
+    } else if starting_money > 0
+        && money_spent as f64 >= starting_money as f64 * FORCE_BUY_SPENT_RATIO
+    {
+        BuyType::Force
+    } else {
+        BuyType::Half
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::BuyType;
+    use crate::csgo::logs::{LogEntry, LogPrefix, Player, PlayerID, Team, TeamAll};
+
+    use super::{instrument_price, EconomyTracker};
+
+    fn prefix() -> LogPrefix {
+        LogPrefix {
+            month: 1,
+            day: 2,
+            year: 2020,
+            hour: 3,
+            minute: 4,
+            second: 5,
+        }
+    }
+
+    fn player(id: u8, team: TeamAll) -> Player {
+        Player {
+            nick: format!("player{}", id),
+            entity_index: id as i32,
+            id: PlayerID::STAMID(format!("STEAM_1:0:{}", id)),
+            team,
+        }
+    }
+
+    fn purchase(player: Player, previously: i32, price: i32, instrument: &str) -> LogEntry {
+        LogEntry::MoneyChanged {
+            prefix: prefix(),
+            player,
+            previously,
+            operation: "-".to_string(),
+            change: price,
+            new_amount: previously - price,
+            instrument: Some(instrument.to_string()),
+            tracked: true,
+        }
+    }
+
+    fn left_buyzone(player: Player, instruments: &[&str]) -> LogEntry {
+        LogEntry::PlayerLeftBuyzone {
+            prefix: prefix(),
+            player,
+            instruments: instruments.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn price_of_instruments() {
+        assert_eq!(instrument_price("weapon_ak47"), 2700);
+        assert_eq!(instrument_price("kevlar(100)"), 650);
+        assert_eq!(instrument_price("item_assaultsuit"), 1000);
+        assert_eq!(instrument_price("weapon_knife"), 0);
+    }
+
+    #[test]
+    fn economy_of_a_round() {
+        let mut tracker = EconomyTracker::new();
+        let entries = [
+            // Money from the previous round
+            LogEntry::MoneyChanged {
+                prefix: prefix(),
+                player: player(2, TeamAll::TERRORIST),
+                previously: 1000,
+                operation: "+".to_string(),
+                change: 1400,
+                new_amount: 2400,
+                instrument: None,
+                tracked: false,
+            },
+            purchase(player(1, TeamAll::CT), 5000, 3100, "weapon_m4a1"),
+            purchase(player(1, TeamAll::CT), 1900, 1000, "item_assaultsuit"),
+            LogEntry::WorldTriggeredEvent {
+                prefix: prefix(),
+                event: "Round_Start".to_string(),
+            },
+            left_buyzone(
+                player(1, TeamAll::CT),
+                &["weapon_knife", "weapon_m4a1", "kevlar(100)", "helmet"],
+            ),
+            left_buyzone(
+                player(2, TeamAll::TERRORIST),
+                &["weapon_knife", "weapon_glock"],
+            ),
+        ];
+        for entry in entries.iter() {
+            tracker.process_entry(entry);
+        }
+
+        let economy = tracker.finish_round();
+        assert_eq!(economy.len(), 2);
+
+        let ct = economy.iter().find(|side| side.side == Team::CT).unwrap();
+        assert_eq!(ct.starting_money, 5000);
+        assert_eq!(ct.money_spent, 4100);
+        assert_eq!(ct.equipment_value, 4100);
+        assert_eq!(ct.buy_type, BuyType::Full);
+
+        let t = economy
+            .iter()
+            .find(|side| side.side == Team::TERRORIST)
+            .unwrap();
+        assert_eq!(t.starting_money, 2400);
+        assert_eq!(t.money_spent, 0);
+        assert_eq!(t.equipment_value, 200);
+        assert_eq!(t.buy_type, BuyType::Eco);
+    }
+
+    #[test]
+    fn force_and_half_buys() {
+        let mut tracker = EconomyTracker::new();
+        let entries = [
+            purchase(player(1, TeamAll::CT), 2500, 2050, "weapon_famas"),
+            left_buyzone(player(1, TeamAll::CT), &["weapon_hkp2000", "weapon_famas"]),
+            purchase(player(2, TeamAll::TERRORIST), 4500, 1800, "weapon_galilar"),
+            left_buyzone(
+                player(2, TeamAll::TERRORIST),
+                &["weapon_glock", "weapon_galilar"],
+            ),
+        ];
+        for entry in entries.iter() {
+            tracker.process_entry(entry);
+        }
+
+        let economy = tracker.finish_round();
+        let ct = economy.iter().find(|side| side.side == Team::CT).unwrap();
+        assert_eq!(ct.buy_type, BuyType::Force);
+        let t = economy
+            .iter()
+            .find(|side| side.side == Team::TERRORIST)
+            .unwrap();
+        assert_eq!(t.buy_type, BuyType::Half);
+    }
+}
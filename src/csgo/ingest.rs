@@ -0,0 +1,739 @@
+use std::collections::HashMap;
+
+use serde_json::Value as JsonValue;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+
+use crate::common::{CombatKind, GameSide, MatchStatus};
+use crate::csgo::economy::{EconomyTracker, RoundEconomy};
+use crate::csgo::logs::{HitGroup, LogEntry, Player, Vector3};
+use crate::csgo::rounds::{Round, RoundTracker, RoundUpdate};
+use crate::csgo::stats::{player_key, player_side, StatsTracker};
+use crate::csgo::utility::UtilityTracker;
+use crate::database::models::{MatchCombat, MatchRound, MatchRoundEconomy, PlayerMapStats};
+use crate::database::{
+    add_match_event, delete_match_rounds_after, get_match, save_match_round, save_round_combat,
+    save_round_economy, set_match_score, set_match_series_score, set_player_map_stats,
+};
+use crate::events::{Event, EventBus, Kill, MatchUpdate};
+use crate::get5::events::{Get5Event, Get5EventKind};
+use crate::status::{observe_match_status, status_of_get5_event, StatusTrigger};
+
+/// Position in the match a server is playing, as observed from its log
+struct ActiveMatch {
+    match_id: Uuid,
+    /// Maps in the series, the series is over once the last one is played
+    num_maps: i32,
+    /// Zero-based index of the map in the series
+    map_number: i32,
+    /// Name of the current map, unknown until a Get5 event of the map is logged
+    map_name: Option<String>,
+    /// Rounds played on the current map
+    rounds: RoundTracker,
+    /// Statistics of the players on the current map
+    stats: StatsTracker,
+    /// Use of grenades by the players on the current map
+    utility: UtilityTracker,
+    /// Economy of the sides in the round being played
+    economy: EconomyTracker,
+    /// Kills and damage in the round being played
+    combat: Vec<MatchCombat>,
+}
+
+/// What a log entry changed in the match being played, to be stored and published
+#[derive(Default)]
+struct TrackedEntry {
+    round_update: Option<RoundUpdate>,
+    stats_changed: bool,
+    /// Economy of the round that was won, with its number
+    round_economy: Option<(i32, Vec<RoundEconomy>)>,
+    /// Kills and damage of the round that was won, with its number
+    round_combat: Option<(i32, Vec<MatchCombat>)>,
+    kill: Option<Kill>,
+    status: Option<(MatchStatus, &'static str)>,
+}
+
+impl ActiveMatch {
+    /// Follows a log entry of the map being played, other than a Get5 event
+    fn track_entry(&mut self, entry: &LogEntry) -> TrackedEntry {
+        let mut tracked = TrackedEntry::default();
+
+        self.stats.process_entry(entry);
+        self.utility.process_entry(entry);
+        self.economy.process_entry(entry);
+        match entry {
+            LogEntry::WorldTriggeredEvent { event, .. } if event == "Round_Start" => {
+                self.combat.clear();
+            }
+            // The series may end without a Get5 event, e.g. with an older version of Get5
+            LogEntry::GameOver { .. } if self.map_number + 1 >= self.num_maps => {
+                tracked.status = Some((MatchStatus::Finished, "GameOver"));
+            }
+            _ => {
+                if let Some(event) = combat_event(self, entry) {
+                    if let LogEntry::PlayerKilledPlayer {
+                        offender, victim, ..
+                    } = entry
+                    {
+                        tracked.kill = Some(Kill {
+                            combat: MatchCombat {
+                                round: self.rounds.current_round(),
+                                ..event.clone()
+                            },
+                            attacker_nick: offender.nick.clone(),
+                            victim_nick: victim.nick.clone(),
+                        });
+                    }
+                    self.combat.push(event);
+                }
+            }
+        }
+        tracked.round_update = self.rounds.process_entry(entry);
+        // A corrected round keeps the statistics, economy and combat it was finished with
+        if let Some(RoundUpdate::Finished(round)) = &tracked.round_update {
+            self.stats.finish_round(round);
+            self.utility.finish_round(round);
+            tracked.stats_changed = true;
+            tracked.round_economy = Some((round.number, self.economy.finish_round()));
+            tracked.round_combat = Some((round.number, std::mem::take(&mut self.combat)));
+        }
+
+        tracked
+    }
+}
+
+/// Records the log entries of every server that is playing a match in `match_events`
+///
+/// The match loaded on a server is known from the `matchid` of the Get5 events, which is the id
+/// of the match in the database (see `crate::web::get5::endpoint_get5_config`). Entries logged
+/// before the first Get5 event of a match, or by servers without a match, are not recorded.
+///
+/// The progress of the matches, the kills and the rounds are published to the event bus once they
+/// are stored. The status of the matches follows the Get5 events, and the end of the last map.
+pub struct MatchEventIngest {
+    db_pool: Pool<Postgres>,
+    events: EventBus,
+    matches: HashMap<Uuid, ActiveMatch>,
+}
+
+impl MatchEventIngest {
+    pub fn new(db_pool: Pool<Postgres>, events: EventBus) -> Self {
+        MatchEventIngest {
+            db_pool,
+            events,
+            matches: HashMap::new(),
+        }
+    }
+
+    /// Id of the match being played on the server, if any
+    pub fn active_match(&self, server_id: Uuid) -> Option<Uuid> {
+        self.matches
+            .get(&server_id)
+            .map(|active_match| active_match.match_id)
+    }
+
+    pub async fn ingest(&mut self, server_id: Uuid, entry: &LogEntry) -> anyhow::Result<()> {
+        let mut series_over = false;
+        let mut map_changed = false;
+        let mut round_update = None;
+        let mut stats_changed = false;
+        let mut round_economy = None;
+        let mut round_combat = None;
+        let mut status = None;
+        let mut series_score = None;
+
+        if let LogEntry::Get5Event { json, .. } = entry {
+            match json.parse::<Get5Event>() {
+                Ok(event) => {
+                    series_over = matches!(
+                        event.kind,
+                        Get5EventKind::SeriesEnd { .. } | Get5EventKind::SeriesCancel { .. }
+                    );
+                    map_changed = self.track_get5_event(server_id, &event).await?;
+                    status = status_of_get5_event(&event.kind);
+                    series_score = match event.kind {
+                        Get5EventKind::SeriesEnd {
+                            team1_series_score,
+                            team2_series_score,
+                            ..
+                        }
+                        | Get5EventKind::SeriesCancel {
+                            team1_series_score,
+                            team2_series_score,
+                        } => Some((team1_series_score, team2_series_score)),
+                        _ => None,
+                    };
+
+                    if let Some(active_match) = self.matches.get_mut(&server_id) {
+                        if let Get5EventKind::GoingLive { .. } = event.kind {
+                            active_match.stats.restart();
+                            active_match.utility.restart();
+                            active_match.economy.restart();
+                        }
+                        round_update = active_match.rounds.process_get5_event(&event.kind);
+                    }
+                }
+                Err(err) => warn!(
+                    "failed to decode Get5 event from server (id={}): {}",
+                    server_id, err
+                ),
+            }
+        } else if let Some(active_match) = self.matches.get_mut(&server_id) {
+            let tracked = active_match.track_entry(entry);
+            if let Some(kill) = tracked.kill {
+                self.events.publish(Event::Kill(kill));
+            }
+            status = tracked.status;
+            round_update = tracked.round_update;
+            stats_changed = tracked.stats_changed;
+            round_economy = tracked.round_economy;
+            round_combat = tracked.round_combat;
+        }
+
+        if let Some(active_match) = self.matches.get(&server_id) {
+            let timestamp = match entry.prefix().timestamp() {
+                Some(timestamp) => timestamp,
+                None => {
+                    warn!(
+                        "log entry from server (id={}) has an invalid timestamp: {:?}",
+                        server_id,
+                        entry.prefix()
+                    );
+                    return Ok(());
+                }
+            };
+            let (event_type, payload) = event_record(entry)?;
+
+            let mut db_conn = self.db_pool.acquire().await?;
+            add_match_event(
+                &mut db_conn,
+                active_match.match_id,
+                active_match.map_number,
+                active_match.rounds.current_round(),
+                timestamp,
+                event_type.as_str(),
+                payload,
+            )
+            .await?;
+
+            if let Some((round, economy)) = round_economy {
+                self.save_economy(active_match, round, economy).await?;
+            }
+            if let Some((round, combat)) = round_combat {
+                self.save_combat(active_match, round, combat).await?;
+            }
+            let round_changed = round_update.is_some();
+            if let Some(update) = round_update {
+                let finished = matches!(update, RoundUpdate::Finished(_));
+                let round = self
+                    .save_round(
+                        active_match.match_id,
+                        active_match.map_number,
+                        update.into_round(),
+                    )
+                    .await?;
+                if finished {
+                    self.events.publish(Event::RoundEnded(round));
+                }
+            }
+            if stats_changed {
+                self.save_stats(active_match).await?;
+            }
+            // The series score is polled while the match is played, Get5 is idle once it is over
+            if let Some((team1, team2)) = series_score {
+                set_match_series_score(&mut db_conn, active_match.match_id, team1, team2).await?;
+            }
+            if let Some((status, event)) = status {
+                observe_match_status(
+                    &mut db_conn,
+                    active_match.match_id,
+                    status,
+                    StatusTrigger::log(event),
+                )
+                .await?;
+            }
+
+            if round_changed || map_changed || series_over {
+                self.events
+                    .publish(Event::MatchUpdated(match_update(active_match, series_over)));
+            }
+        }
+
+        if series_over {
+            self.matches.remove(&server_id);
+        }
+
+        Ok(())
+    }
+
+    /// Follows the match and map loaded on the server, returns whether a match or a map started
+    async fn track_get5_event(
+        &mut self,
+        server_id: Uuid,
+        event: &Get5Event,
+    ) -> anyhow::Result<bool> {
+        let match_id = match Uuid::parse_str(event.matchid.as_str()) {
+            Ok(match_id) => match_id,
+            // No match is loaded, or the match was not loaded by us
+            Err(_) => {
+                self.matches.remove(&server_id);
+                return Ok(false);
+            }
+        };
+
+        let known = self
+            .matches
+            .get(&server_id)
+            .is_some_and(|active_match| active_match.match_id == match_id);
+        if !known {
+            let mut db_conn = self.db_pool.acquire().await?;
+            match get_match(&mut db_conn, match_id).await? {
+                Some(r#match) if r#match.server_id == server_id => {
+                    debug!(
+                        "server (id={}) is playing match (id={})",
+                        server_id, match_id
+                    );
+                    self.matches.insert(
+                        server_id,
+                        ActiveMatch {
+                            match_id,
+                            num_maps: r#match.num_maps,
+                            map_number: 0,
+                            map_name: None,
+                            rounds: RoundTracker::new(),
+                            stats: StatsTracker::new(),
+                            utility: UtilityTracker::new(),
+                            economy: EconomyTracker::new(),
+                            combat: Vec::new(),
+                        },
+                    );
+                }
+                _ => {
+                    warn!(
+                        "server (id={}) is playing match (id={}), which is not assigned to it",
+                        server_id, match_id
+                    );
+                    self.matches.remove(&server_id);
+                    return Ok(false);
+                }
+            }
+        }
+
+        let mut started = !known;
+        if let Some(active_match) = self.matches.get_mut(&server_id) {
+            if let Some((map_number, map_name)) = event.kind.map() {
+                started |= active_match.map_name.is_none();
+                active_match.map_name = Some(map_name.to_string());
+                if map_number != active_match.map_number {
+                    started = true;
+                    active_match.map_number = map_number;
+                    active_match.rounds.restart();
+                    active_match.stats.restart();
+                    active_match.utility.restart();
+                    active_match.economy.restart();
+                    active_match.combat.clear();
+                }
+            }
+        }
+
+        Ok(started)
+    }
+
+    /// Stores the round, and updates the score of the match
+    async fn save_round(
+        &self,
+        match_id: Uuid,
+        map_number: i32,
+        round: Round,
+    ) -> anyhow::Result<MatchRound> {
+        let round = MatchRound {
+            match_id,
+            map_number,
+            number: round.number,
+            half: round.half,
+            overtime: round.overtime,
+            winner_side: round.winner.into(),
+            reason: round.reason,
+            ct_score: round.ct_score,
+            t_score: round.t_score,
+            team1_score: round.team1_score,
+            team2_score: round.team2_score,
+        };
+
+        let mut tx = self.db_pool.begin().await?;
+
+        delete_match_rounds_after(&mut tx, match_id, map_number, round.number).await?;
+        save_match_round(&mut tx, &round).await?;
+        if round.team1_score.is_some() && round.team2_score.is_some() {
+            set_match_score(&mut tx, match_id, round.team1_score, round.team2_score).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(round)
+    }
+
+    /// Stores the statistics of the players on the current map, bots are left out
+    async fn save_stats(&self, active_match: &ActiveMatch) -> anyhow::Result<()> {
+        let mut utility = active_match.utility.totals();
+        let player_stats = active_match
+            .stats
+            .totals()
+            .into_iter()
+            .filter(|(key, _)| key.starts_with("STEAM_"))
+            .map(|(steamid, stats)| {
+                let nick = active_match
+                    .stats
+                    .nick(&steamid)
+                    .unwrap_or_default()
+                    .to_string();
+                let utility = utility.remove(&steamid).unwrap_or_default();
+                PlayerMapStats::new(
+                    active_match.match_id,
+                    active_match.map_number,
+                    steamid,
+                    nick,
+                    &stats,
+                    &utility,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut db_conn = self.db_pool.acquire().await?;
+        set_player_map_stats(
+            &mut db_conn,
+            active_match.match_id,
+            active_match.map_number,
+            &player_stats,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn save_economy(
+        &self,
+        active_match: &ActiveMatch,
+        round: i32,
+        economy: Vec<RoundEconomy>,
+    ) -> anyhow::Result<()> {
+        let economy = economy
+            .into_iter()
+            .map(|side| MatchRoundEconomy {
+                match_id: active_match.match_id,
+                map_number: active_match.map_number,
+                round,
+                side: side.side.into(),
+                starting_money: side.starting_money,
+                money_spent: side.money_spent,
+                equipment_value: side.equipment_value,
+                buy_type: side.buy_type,
+            })
+            .collect::<Vec<_>>();
+
+        let mut db_conn = self.db_pool.acquire().await?;
+        save_round_economy(
+            &mut db_conn,
+            active_match.match_id,
+            active_match.map_number,
+            round,
+            &economy,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn save_combat(
+        &self,
+        active_match: &ActiveMatch,
+        round: i32,
+        mut combat: Vec<MatchCombat>,
+    ) -> anyhow::Result<()> {
+        for event in combat.iter_mut() {
+            event.round = round;
+        }
+
+        let mut db_conn = self.db_pool.acquire().await?;
+        save_round_combat(
+            &mut db_conn,
+            active_match.match_id,
+            active_match.map_number,
+            round,
+            &combat,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Progress of the match, the score is the one of the last round played on the map
+fn match_update(active_match: &ActiveMatch, finished: bool) -> MatchUpdate {
+    let last_round = active_match.rounds.rounds().last();
+
+    MatchUpdate {
+        match_id: active_match.match_id,
+        map_number: active_match.map_number,
+        map_name: active_match.map_name.clone(),
+        round: active_match.rounds.current_round(),
+        team1_score: last_round.and_then(|round| round.team1_score),
+        team2_score: last_round.and_then(|round| round.team2_score),
+        finished,
+    }
+}
+
+/// Kill or damage logged by the entry, the round is set once the round is over
+fn combat_event(active_match: &ActiveMatch, entry: &LogEntry) -> Option<MatchCombat> {
+    let (kind, attacker, attacker_location, victim, victim_location, weapon, damage, headshot) =
+        match entry {
+            LogEntry::PlayerKilledPlayer {
+                offender,
+                offender_location,
+                victim,
+                victim_location,
+                instrument,
+                kill_attributes,
+                ..
+            } => (
+                CombatKind::Kill,
+                offender,
+                offender_location,
+                victim,
+                victim_location,
+                instrument,
+                0,
+                kill_attributes.headshot,
+            ),
+            LogEntry::PlayerAttackedPlayer {
+                offender,
+                offender_location,
+                victim,
+                victim_location,
+                instrument,
+                damage,
+                hitgroup,
+                ..
+            } => (
+                CombatKind::Damage,
+                offender,
+                offender_location,
+                victim,
+                victim_location,
+                instrument,
+                *damage,
+                *hitgroup == HitGroup::Head,
+            ),
+            _ => return None,
+        };
+
+    let side = |player: &Player| player_side(player).map(GameSide::from);
+    let Vector3 {
+        x: attacker_x,
+        y: attacker_y,
+        z: attacker_z,
+    } = *attacker_location;
+    let Vector3 {
+        x: victim_x,
+        y: victim_y,
+        z: victim_z,
+    } = *victim_location;
+
+    Some(MatchCombat {
+        id: 0,
+        match_id: active_match.match_id,
+        map_number: active_match.map_number,
+        map_name: active_match.map_name.clone()?,
+        round: 0,
+        kind,
+        attacker: player_key(attacker)?,
+        attacker_side: side(attacker),
+        attacker_x,
+        attacker_y,
+        attacker_z,
+        victim: player_key(victim)?,
+        victim_side: side(victim),
+        victim_x,
+        victim_y,
+        victim_z,
+        weapon: weapon.clone(),
+        damage,
+        headshot,
+    })
+}
+
+/// Splits a log entry into the name of its variant and its fields
+fn event_record(entry: &LogEntry) -> serde_json::Result<(String, JsonValue)> {
+    match serde_json::to_value(entry)? {
+        JsonValue::Object(map) if map.len() == 1 => {
+            let (event_type, payload) = map.into_iter().next().unwrap();
+            Ok((event_type, payload))
+        }
+        value => Ok((String::from("Unknown"), value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use sqlx::types::Uuid;
+
+    use crate::common::{CombatKind, GameSide};
+    use crate::csgo::economy::EconomyTracker;
+    use crate::csgo::logs::{
+        KillAttributes, LogEntry, LogPrefix, Player, PlayerID, Team, TeamAll, Vector3,
+    };
+    use crate::csgo::rounds::{RoundTracker, RoundUpdate};
+    use crate::csgo::stats::StatsTracker;
+    use crate::csgo::utility::UtilityTracker;
+
+    use super::{combat_event, event_record, ActiveMatch};
+
+    fn prefix() -> LogPrefix {
+        LogPrefix {
+            month: 1,
+            day: 2,
+            year: 2020,
+            hour: 3,
+            minute: 4,
+            second: 5,
+        }
+    }
+
+    #[test]
+    fn event_record_of_entry() {
+        let entry = LogEntry::WorldTriggeredEvent {
+            prefix: prefix(),
+            event: "Round_Start".to_string(),
+        };
+
+        let (event_type, payload) = event_record(&entry).unwrap();
+        assert_eq!(event_type, "WorldTriggeredEvent");
+        assert_eq!(
+            payload,
+            json!({
+                "prefix": {
+                    "month": 1,
+                    "day": 2,
+                    "year": 2020,
+                    "hour": 3,
+                    "minute": 4,
+                    "second": 5,
+                },
+                "event": "Round_Start",
+            })
+        );
+    }
+
+    fn active_match() -> ActiveMatch {
+        ActiveMatch {
+            match_id: Uuid::nil(),
+            num_maps: 3,
+            map_number: 1,
+            map_name: Some("de_nuke".to_string()),
+            rounds: RoundTracker::new(),
+            stats: StatsTracker::new(),
+            utility: UtilityTracker::new(),
+            economy: EconomyTracker::new(),
+            combat: Vec::new(),
+        }
+    }
+
+    fn kill() -> LogEntry {
+        LogEntry::PlayerKilledPlayer {
+            prefix: prefix(),
+            offender: Player {
+                nick: "foo".to_string(),
+                entity_index: 1,
+                id: PlayerID::STAMID("STEAM_1:0:1".to_string()),
+                team: TeamAll::CT,
+            },
+            offender_location: Vector3 { x: 1, y: 2, z: 3 },
+            victim: Player {
+                nick: "bar".to_string(),
+                entity_index: 2,
+                id: PlayerID::BOT,
+                team: TeamAll::TERRORIST,
+            },
+            victim_location: Vector3 { x: 4, y: 5, z: 6 },
+            instrument: "awp".to_string(),
+            kill_attributes: KillAttributes {
+                headshot: false,
+                penetrated: true,
+            },
+        }
+    }
+
+    #[test]
+    fn combat_event_of_kill() {
+        let mut active_match = ActiveMatch {
+            map_name: None,
+            ..active_match()
+        };
+        let entry = kill();
+
+        // The map is not known yet
+        assert!(combat_event(&active_match, &entry).is_none());
+
+        active_match.map_name = Some("de_nuke".to_string());
+        let event = combat_event(&active_match, &entry).unwrap();
+        assert_eq!(event.kind, CombatKind::Kill);
+        assert_eq!(event.map_name, "de_nuke");
+        assert_eq!(event.attacker, "STEAM_1:0:1");
+        assert_eq!(event.attacker_side, Some(GameSide::CT));
+        assert_eq!(
+            (event.attacker_x, event.attacker_y, event.attacker_z),
+            (1, 2, 3)
+        );
+        assert_eq!(event.victim, "BOT bar");
+        assert_eq!(event.victim_side, Some(GameSide::T));
+        assert_eq!((event.victim_x, event.victim_y, event.victim_z), (4, 5, 6));
+        assert_eq!(event.weapon, "awp");
+    }
+
+    #[test]
+    fn score_corrected_after_round_end() {
+        let mut active_match = active_match();
+        active_match.track_entry(&LogEntry::WorldTriggeredEvent {
+            prefix: prefix(),
+            event: "Round_Start".to_string(),
+        });
+        assert!(active_match.track_entry(&kill()).kill.is_some());
+
+        let finished = active_match.track_entry(&LogEntry::TeamTriggeredEventScore {
+            prefix: prefix(),
+            team: Team::CT,
+            event: "SFUI_Notice_CTs_Win".to_string(),
+            ct_score: 1,
+            t_score: 0,
+        });
+        assert!(matches!(
+            finished.round_update,
+            Some(RoundUpdate::Finished(_))
+        ));
+        assert!(finished.stats_changed);
+        assert!(finished.round_economy.is_some());
+        assert_eq!(
+            finished.round_combat.map(|(_, combat)| combat.len()),
+            Some(1)
+        );
+
+        let corrected = active_match.track_entry(&LogEntry::TeamScored {
+            prefix: prefix(),
+            team: Team::CT,
+            score: 2,
+            player_count: 5,
+        });
+        let round = match corrected.round_update {
+            Some(RoundUpdate::Corrected(round)) => round,
+            update => panic!("expected a corrected round, got {:?}", update),
+        };
+        assert_eq!((round.number, round.ct_score), (1, 2));
+        // The round is not finished a second time
+        assert!(!corrected.stats_changed);
+        assert!(corrected.round_economy.is_none());
+        assert!(corrected.round_combat.is_none());
+        assert_eq!(active_match.stats.totals()["STEAM_1:0:1"].kills, 1);
+    }
+}
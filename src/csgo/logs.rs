@@ -1,8 +1,10 @@
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::str::FromStr;
 use std::time::Duration;
 
+use chrono::{NaiveDate, NaiveDateTime};
 use regex::{Captures, Regex, RegexSet};
 
 // NOTES ON LOG PROCESSING
@@ -15,14 +17,14 @@ use regex::{Captures, Regex, RegexSet};
 //
 // Two groups of log lines needs to be parsed statefully. The CVARs dump and ACCOLADE lines needs
 // the context of previous lines to provide full information. If processing starts in the middle
-// of one of the two, these lines can be discarded without breaking anything. The stateful
-// interpretation of these can be moved outside the parser.
+// of one of the two, these lines can be discarded without breaking anything. The `LogProcessor`
+// keeps the state of these groups between lines, and emits a combined entry for each group.
 //
 // A couple of regexes are known to fail if a player's Steam nickname contains a less than
 // character, `<`. This is unfixable since some log lines contains user input in two places
 // (nickname and chat message for instance), and Valve doesn't provide any escape characters.
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct LogPrefix {
     pub month: i32,
     pub day: i32,
@@ -32,7 +34,20 @@ pub struct LogPrefix {
     pub second: i32,
 }
 
-#[derive(Debug, PartialEq)]
+impl LogPrefix {
+    /// Time the line was logged, in the local time of the game server
+    ///
+    /// `None` if the prefix doesn't describe a valid date and time.
+    pub fn timestamp(&self) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(self.year, self.month as u32, self.day as u32)?.and_hms_opt(
+            self.hour as u32,
+            self.minute as u32,
+            self.second as u32,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum TeamAll {
     TERRORIST,
     CT,
@@ -41,13 +56,13 @@ pub enum TeamAll {
     CONSOLE,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub enum Team {
     TERRORIST,
     CT,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum PlayerID {
     STAMID(String),
     BOT,
@@ -66,7 +81,7 @@ pub enum PlayerID {
 /// alice<10><STEAM_1:0:536763442><TERRORIST>
 /// bob<4><STEAM_1:0:145932671><CT>
 /// ```
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Player {
     pub nick: String,
     pub entity_index: i32,
@@ -74,20 +89,38 @@ pub struct Player {
     pub team: TeamAll,
 }
 
-#[derive(Debug, PartialEq)]
+/// Position on the map, in world units as shown by `getpos`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Vector3 {
-    x: i32,
-    y: i32,
-    z: i32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct KillAttributes {
-    headshot: bool,
-    penetrated: bool,
+    pub headshot: bool,
+    pub penetrated: bool,
 }
 
-#[derive(Debug, PartialEq)]
+/// Accolade awarded to a player at the end of a game, see `LogEntry::Accolade`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Award {
+    pub value: f32,
+    pub pos: i32,
+    pub score: f32,
+}
+
+/// All the accolades awarded to a single player at the end of a game
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PlayerAccolades {
+    pub nick: String,
+    pub entity_index: i32,
+    /// Awards by accolade category
+    pub awards: HashMap<String, Award>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub enum HitGroup {
     Chest,
     Generic,
@@ -100,7 +133,7 @@ pub enum HitGroup {
     Stomach,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum LogEntry {
     /// Start of log file.
     LogFileStart {
@@ -153,10 +186,11 @@ pub enum LogEntry {
         map: String,
     },
     /// Server dumped all it's cvars during startup.
+    /// Put together from the lines between `server cvars start` and `server cvars end`.
     CvarDump {
         start: LogPrefix,
         end: LogPrefix,
-        cvars: Vec<(String, String)>,
+        cvars: HashMap<String, String>,
     },
     /// Started map.
     StartedMap {
@@ -176,7 +210,7 @@ pub enum LogEntry {
         prefix: LogPrefix,
         player: Player,
     },
-    /// Get5 event encoded as JSON.
+    /// Get5 event encoded as JSON, decoded by `crate::get5::events::Get5Event`.
     Get5Event {
         prefix: LogPrefix,
         json: String,
@@ -410,6 +444,13 @@ pub enum LogEntry {
         pos: i32,
         score: f32,
     },
+    /// Summary of all accolades awarded at the end of a game, per player.
+    /// Emitted after the last `Accolade` entry of the group, before the entry that ended the group.
+    Accolades {
+        /// Prefix of the first accolade in the group
+        prefix: LogPrefix,
+        players: Vec<PlayerAccolades>,
+    },
     /// Game ended.
     /// CT and T score might be swapped, this needs validation.
     GameOver {
@@ -458,6 +499,68 @@ pub enum LogEntry {
     },
 }
 
+impl LogEntry {
+    /// Prefix of the line the entry was parsed from
+    ///
+    /// For a cvar dump this is the prefix of the line that started the dump.
+    pub fn prefix(&self) -> &LogPrefix {
+        match self {
+            LogEntry::CvarDump { start, .. } => start,
+            LogEntry::LogFileStart { prefix, .. }
+            | LogEntry::LogFileClosed { prefix, .. }
+            | LogEntry::WorldTriggeredEvent { prefix, .. }
+            | LogEntry::WorldTriggeredEventMap { prefix, .. }
+            | LogEntry::WorldTriggeredEventScore { prefix, .. }
+            | LogEntry::PlayerTriggeredEvent { prefix, .. }
+            | LogEntry::TeamTriggeredEventScore { prefix, .. }
+            | LogEntry::LoadingMap { prefix, .. }
+            | LogEntry::StartedMap { prefix, .. }
+            | LogEntry::Cvar { prefix, .. }
+            | LogEntry::PlayerEnteredGame { prefix, .. }
+            | LogEntry::Get5Event { prefix, .. }
+            | LogEntry::RconCommand { prefix, .. }
+            | LogEntry::RconBadPassword { prefix, .. }
+            | LogEntry::SwitchedTeam { prefix, .. }
+            | LogEntry::PlayerPickedUp { prefix, .. }
+            | LogEntry::PlayerDropped { prefix, .. }
+            | LogEntry::TeamPlaying { prefix, .. }
+            | LogEntry::StartingFreezePeriod { prefix, .. }
+            | LogEntry::PlayerLeftBuyzone { prefix, .. }
+            | LogEntry::TeamChat { prefix, .. }
+            | LogEntry::MoneyChanged { prefix, .. }
+            | LogEntry::PlayerPurchased { prefix, .. }
+            | LogEntry::ThrewFlashbang { prefix, .. }
+            | LogEntry::BlindedPlayer { prefix, .. }
+            | LogEntry::GlobalChat { prefix, .. }
+            | LogEntry::PlayerKilledEntity { prefix, .. }
+            | LogEntry::PlayerKilledPlayer { prefix, .. }
+            | LogEntry::PlayerThrewSmokegrenade { prefix, .. }
+            | LogEntry::PlayerThrewHEGrenade { prefix, .. }
+            | LogEntry::PlayerAttackedPlayer { prefix, .. }
+            | LogEntry::PlayerDisconnected { prefix, .. }
+            | LogEntry::PlayerAssistedKillingPlayer { prefix, .. }
+            | LogEntry::PlayerAssistedBlindingPlayer { prefix, .. }
+            | LogEntry::SpawnedMolotov { prefix, .. }
+            | LogEntry::ThrewMolotov { prefix, .. }
+            | LogEntry::PlayerConnected { prefix, .. }
+            | LogEntry::ValidatedSteamID { prefix, .. }
+            | LogEntry::TeamScored { prefix, .. }
+            | LogEntry::ThrewDecoy { prefix, .. }
+            | LogEntry::MatchResumed { prefix, .. }
+            | LogEntry::MatchPaused { prefix, .. }
+            | LogEntry::KilledByBomb { prefix, .. }
+            | LogEntry::Accolade { prefix, .. }
+            | LogEntry::Accolades { prefix, .. }
+            | LogEntry::GameOver { prefix, .. }
+            | LogEntry::ChangedNickname { prefix, .. }
+            | LogEntry::CommittedSuicide { prefix, .. }
+            | LogEntry::ServerMessage { prefix, .. }
+            | LogEntry::SteamAuthFailure { prefix, .. }
+            | LogEntry::MetaModPluginsLoaded { prefix, .. } => prefix,
+        }
+    }
+}
+
 lazy_static! {
     /// Prefix for a CS:GO log line
     static ref LOG_PREFIX: String = r"^L (?P<log_month>\d\d)/(?P<log_day>\d\d)/(?P<log_year>\d\d\d\d) - (?P<log_hour>\d\d):(?P<log_minute>\d\d):(?P<log_second>\d\d): ".to_string();
@@ -535,13 +638,35 @@ lazy_static! {
 
 #[async_trait]
 pub trait LogEntryReader<E> {
-    async fn read_log_line(self) -> Result<String, E>;
+    async fn read_log_line(&mut self) -> Result<String, E>;
 }
 
+/// Parses log lines into log entries, and puts together the groups of lines that belongs together
+///
+/// The processor is meant to be long-lived, one per log stream, such that state is kept between
+/// lines.
 pub struct LogProcessor<R: LogEntryReader<E>, E> {
     _phantom: PhantomData<E>,
     reader: R,
-    reading_cvar_dump: bool,
+    /// Cvar dump currently being read
+    cvar_dump: Option<PartialCvarDump>,
+    /// Accolades read since the last non-accolade entry, and the prefix of the first of them
+    accolades: Option<(LogPrefix, Vec<PlayerAccolades>)>,
+    /// Entries ready to be returned before reading the next line
+    pending: VecDeque<LogEntry>,
+}
+
+struct PartialCvarDump {
+    start: LogPrefix,
+    cvars: HashMap<String, String>,
+}
+
+/// Log line that is either a complete entry, or a part of a group of lines
+enum Line {
+    Entry(LogEntry),
+    CvarDumpStart(LogPrefix),
+    CvarDumpCvar(String, String),
+    CvarDumpEnd(LogPrefix),
 }
 
 #[derive(Debug)]
@@ -571,14 +696,10 @@ fn extract_parse_optional<E: FromStr>(captures: &Captures, group: &str) -> Optio
 where
     E::Err: Debug,
 {
-    match captures.name(group) {
-        None => None,
-        Some(group) => {
-            // TODO build expect messages at compile time using something like the concat! macro. A combination of macros and functions could be effective, https://godbolt.org/z/bAJUG9
-            Some(group.as_str().parse().unwrap())
-            //.expect(format!("Failed to parse \"{}\"", group).as_str())
-        }
-    }
+    // TODO build expect messages at compile time using something like the concat! macro. A combination of macros and functions could be effective, https://godbolt.org/z/bAJUG9
+    captures
+        .name(group)
+        .map(|group| group.as_str().parse().unwrap())
 }
 
 fn extract_into<'t, E: From<&'t str>>(captures: &Captures<'t>, group: &str) -> E {
@@ -591,10 +712,7 @@ fn extract_into<'t, E: From<&'t str>>(captures: &Captures<'t>, group: &str) -> E
 }
 
 fn extract_optional_into<'t, E: From<&'t str>>(captures: &Captures<'t>, group: &str) -> Option<E> {
-    match captures.name(group) {
-        None => None,
-        Some(group) => Some(group.as_str().into()),
-    }
+    captures.name(group).map(|group| group.as_str().into())
 }
 
 fn extract_str<'t>(captures: &Captures<'t>, group: &str) -> &'t str {
@@ -679,18 +797,18 @@ fn extract_player_id(captures: &Captures, group: &str) -> PlayerID {
 // TODO convert this to a macro such that we don't have to do string building
 fn extract_player(captures: &Captures, prefix: &str) -> Player {
     Player {
-        nick: extract_into(&captures, (prefix.to_owned() + "_nick").as_str()),
-        entity_index: extract_parse(&captures, (prefix.to_owned() + "_entindex").as_str()),
-        id: extract_player_id(&captures, (prefix.to_owned() + "_id").as_str()),
-        team: extract_team_all(&captures, (prefix.to_owned() + "_team").as_str()),
+        nick: extract_into(captures, (prefix.to_owned() + "_nick").as_str()),
+        entity_index: extract_parse(captures, (prefix.to_owned() + "_entindex").as_str()),
+        id: extract_player_id(captures, (prefix.to_owned() + "_id").as_str()),
+        team: extract_team_all(captures, (prefix.to_owned() + "_team").as_str()),
     }
 }
 
 fn extract_vector3(captures: &Captures, prefix: &str) -> Vector3 {
     Vector3 {
-        x: extract_parse(&captures, (prefix.to_owned() + "_x").as_str()),
-        y: extract_parse(&captures, (prefix.to_owned() + "_y").as_str()),
-        z: extract_parse(&captures, (prefix.to_owned() + "_z").as_str()),
+        x: extract_parse(captures, (prefix.to_owned() + "_x").as_str()),
+        y: extract_parse(captures, (prefix.to_owned() + "_y").as_str()),
+        z: extract_parse(captures, (prefix.to_owned() + "_z").as_str()),
     }
 }
 
@@ -750,16 +868,107 @@ impl<R: LogEntryReader<E>, E> LogProcessor<R, E> {
         LogProcessor {
             _phantom: Default::default(),
             reader,
-            reading_cvar_dump: false,
+            cvar_dump: None,
+            accolades: None,
+            pending: VecDeque::new(),
         }
     }
 
-    pub async fn read_entry(self) -> Result<LogEntry, Error<E>> {
-        let line = match self.reader.read_log_line().await {
-            Ok(line) => line,
-            Err(err) => return Err(Error::ReaderError(err)),
-        };
+    /// Reads lines until a log entry is complete
+    ///
+    /// Lines that are part of a group (cvar dump) are consumed without returning an entry until
+    /// the group is complete.
+    pub async fn read_entry(&mut self) -> Result<LogEntry, Error<E>> {
+        if let Some(entry) = self.pending.pop_front() {
+            return Ok(entry);
+        }
+
+        loop {
+            let line = match self.reader.read_log_line().await {
+                Ok(line) => line,
+                Err(err) => return Err(Error::ReaderError(err)),
+            };
+
+            match Self::parse_line(line)? {
+                Line::Entry(entry) => return Ok(self.group_accolades(entry)),
+                Line::CvarDumpStart(start) => {
+                    self.cvar_dump = Some(PartialCvarDump {
+                        start,
+                        cvars: HashMap::new(),
+                    });
+                }
+                Line::CvarDumpCvar(key, value) => {
+                    // Discarded if processing started in the middle of the dump
+                    if let Some(dump) = &mut self.cvar_dump {
+                        dump.cvars.insert(key, value);
+                    }
+                }
+                Line::CvarDumpEnd(end) => {
+                    if let Some(dump) = self.cvar_dump.take() {
+                        return Ok(self.group_accolades(LogEntry::CvarDump {
+                            start: dump.start,
+                            end,
+                            cvars: dump.cvars,
+                        }));
+                    }
+                }
+            }
+        }
+    }
 
+    /// Collects accolade entries, and puts a summary in front of the entry that ends a group
+    fn group_accolades(&mut self, entry: LogEntry) -> LogEntry {
+        if let LogEntry::Accolade {
+            prefix,
+            categorie,
+            player,
+            player_entindex,
+            value,
+            pos,
+            score,
+        } = &entry
+        {
+            let (_, players) = self
+                .accolades
+                .get_or_insert_with(|| (prefix.clone(), vec![]));
+            let award = Award {
+                value: *value,
+                pos: *pos,
+                score: *score,
+            };
+
+            match players
+                .iter_mut()
+                .find(|p| &p.nick == player && p.entity_index == *player_entindex)
+            {
+                Some(p) => {
+                    p.awards.insert(categorie.clone(), award);
+                }
+                None => {
+                    let mut awards = HashMap::new();
+                    awards.insert(categorie.clone(), award);
+                    players.push(PlayerAccolades {
+                        nick: player.clone(),
+                        entity_index: *player_entindex,
+                        awards,
+                    });
+                }
+            }
+
+            return entry;
+        }
+
+        match self.accolades.take() {
+            Some((prefix, players)) => {
+                self.pending.push_back(entry);
+                LogEntry::Accolades { prefix, players }
+            }
+            None => entry,
+        }
+    }
+
+    /// Parses a single line, without regard to the lines before it
+    fn parse_line(line: String) -> Result<Line, Error<E>> {
         let matchs: Vec<usize> = REGEX.matches(&line).iter().collect();
 
         let index = match matchs.len() {
@@ -776,7 +985,7 @@ impl<R: LogEntryReader<E>, E> LogProcessor<R, E> {
             .captures(line.as_str())
             .expect("Log line matches REGEX_SET but fails SINGLE_REGEXES");
 
-        match index {
+        let entry = match index {
             0 => Ok(LogEntry::LogFileStart {
                 prefix: extract_prefix(&captures),
                 file: extract_into(&captures, "file"),
@@ -817,20 +1026,15 @@ impl<R: LogEntryReader<E>, E> LogProcessor<R, E> {
                 prefix: extract_prefix(&captures),
                 map: extract_into(&captures, "map"),
             }),
-            // TODO for cvar dump, process by recursion. If a non cvar_dump is found return that, otherwise return the completed cvar_dump when it has completed. (tail recursion!)
-            8 => {
-                // begin cvar dump
-                // TODO write stateful handling of LogEntry::CvarDump
-                unimplemented!()
-            }
+            // The cvar dump is put together by the `LogProcessor`
+            8 => return Ok(Line::CvarDumpStart(extract_prefix(&captures))),
             9 => {
-                // cvar from dump
-                unimplemented!()
-            }
-            10 => {
-                // ended cvar dump
-                unimplemented!()
+                return Ok(Line::CvarDumpCvar(
+                    extract_into(&captures, "cvar_key"),
+                    extract_into(&captures, "cvar_value"),
+                ))
             }
+            10 => return Ok(Line::CvarDumpEnd(extract_prefix(&captures))),
             11 => Ok(LogEntry::StartedMap {
                 prefix: extract_prefix(&captures),
                 map: extract_into(&captures, "map"),
@@ -1103,17 +1307,22 @@ impl<R: LogEntryReader<E>, E> LogProcessor<R, E> {
                     index
                 );
             }
-        }
+        };
+
+        entry.map(Line::Entry)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::legacy_numeric_constants)]
 mod test {
+    use std::collections::VecDeque;
     use std::fs::File;
     use std::io::{prelude::*, BufReader};
     use std::time::Duration;
 
     use assert_approx_eq::assert_approx_eq;
+    use chrono::NaiveDate;
 
     use crate::csgo::logs::{HitGroup, KillAttributes, LogEntry, LogProcessor, Team, TeamAll};
 
@@ -1122,19 +1331,32 @@ mod test {
     #[async_trait]
     impl super::LogEntryReader<LogLine> for LogLine {
         // replace Err type with the never type once stabilized, https://github.com/rust-lang/rust/issues/35121
-        async fn read_log_line(self) -> Result<String, String> {
-            Ok(self)
+        async fn read_log_line(&mut self) -> Result<String, String> {
+            Ok(std::mem::take(self))
+        }
+    }
+
+    type LogLines = VecDeque<String>;
+
+    #[async_trait]
+    impl super::LogEntryReader<LogLine> for LogLines {
+        async fn read_log_line(&mut self) -> Result<String, String> {
+            self.pop_front().ok_or_else(|| "end of log".to_string())
         }
     }
 
     async fn parse_line(line: &str) -> LogEntry {
         let logline: LogLine = line.to_string();
-        let processor = LogProcessor::new(logline);
+        let mut processor = LogProcessor::new(logline);
         let result = processor.read_entry().await;
 
         result.unwrap()
     }
 
+    fn processor_for_lines(lines: &[&str]) -> LogProcessor<LogLines, String> {
+        LogProcessor::new(lines.iter().map(|line| line.to_string()).collect())
+    }
+
     #[async_std::test]
     async fn log_start() {
         let logentry = parse_line(r#"L 01/02/2020 - 03:04:05: Log file started (file "logs/L000_000_000_000_0_202001020304_000.log") (game "/home/steam/csgo/csgo") (version "7713")"#).await;
@@ -1159,6 +1381,20 @@ mod test {
         }
     }
 
+    #[async_std::test]
+    async fn log_prefix_timestamp() {
+        let logentry = parse_line(r#"L 01/02/2020 - 03:04:05: Log file closed"#).await;
+        assert_eq!(
+            logentry.prefix().timestamp(),
+            Some(
+                NaiveDate::from_ymd_opt(2020, 1, 2)
+                    .unwrap()
+                    .and_hms_opt(3, 4, 5)
+                    .unwrap()
+            )
+        );
+    }
+
     #[async_std::test]
     async fn log_closed() {
         let logentry = parse_line(r#"L 01/02/2020 - 03:04:05: Log file closed"#).await;
@@ -1307,7 +1543,43 @@ mod test {
         }
     }
 
-    // TODO LogEntry::CvarDump, this needs a custom test for multiline
+    #[async_std::test]
+    async fn log_cvar_dump() {
+        let mut processor = processor_for_lines(&[
+            r#"L 01/02/2020 - 03:04:05: server cvars start"#,
+            r#"L 01/02/2020 - 03:04:05: "mp_maxrounds" = "30""#,
+            r#"L 01/02/2020 - 03:04:05: "mp_overtime_enable" = "1""#,
+            r#"L 01/02/2020 - 03:04:06: server cvars end"#,
+            r#"L 01/02/2020 - 03:04:07: Log file closed"#,
+        ]);
+
+        let logentry = processor.read_entry().await.unwrap();
+        if let super::LogEntry::CvarDump { start, end, cvars } = logentry {
+            assert_eq!(start.second, 5);
+            assert_eq!(end.second, 6);
+            assert_eq!(cvars.len(), 2);
+            assert_eq!(cvars.get("mp_maxrounds"), Some(&"30".to_string()));
+            assert_eq!(cvars.get("mp_overtime_enable"), Some(&"1".to_string()));
+        } else {
+            panic!("wrong LogEntry type received, {:#?}", logentry)
+        }
+
+        let logentry = processor.read_entry().await.unwrap();
+        assert!(matches!(logentry, LogEntry::LogFileClosed { .. }));
+    }
+
+    #[async_std::test]
+    async fn log_cvar_dump_partial() {
+        // Processing started in the middle of the dump, the partial dump is discarded
+        let mut processor = processor_for_lines(&[
+            r#"L 01/02/2020 - 03:04:05: "mp_overtime_enable" = "1""#,
+            r#"L 01/02/2020 - 03:04:06: server cvars end"#,
+            r#"L 01/02/2020 - 03:04:07: Log file closed"#,
+        ]);
+
+        let logentry = processor.read_entry().await.unwrap();
+        assert!(matches!(logentry, LogEntry::LogFileClosed { .. }));
+    }
 
     #[async_std::test]
     async fn log_started_map() {
@@ -2442,6 +2714,43 @@ mod test {
         }
     }
 
+    #[async_std::test]
+    async fn log_accolades() {
+        let mut processor = processor_for_lines(&[
+            "L 01/02/2020 - 03:04:05: ACCOLADE, FINAL: {assists},\tfoobar<2>,\tVALUE: 3.000000,\tPOS: 1,\tSCORE: 0.357143",
+            "L 01/02/2020 - 03:04:05: ACCOLADE, FINAL: {hsp},\tbazgaz<3>,\tVALUE: 50.000000,\tPOS: 1,\tSCORE: 2.000000",
+            "L 01/02/2020 - 03:04:05: ACCOLADE, FINAL: {3k},\tfoobar<2>,\tVALUE: 1.000000,\tPOS: 2,\tSCORE: 1.500000",
+            "L 01/02/2020 - 03:04:06: Game Over: competitive mg_active de_inferno score 11:16 after 50 min",
+        ]);
+
+        for _ in 0..3 {
+            let logentry = processor.read_entry().await.unwrap();
+            assert!(matches!(logentry, LogEntry::Accolade { .. }));
+        }
+
+        let logentry = processor.read_entry().await.unwrap();
+        if let super::LogEntry::Accolades { prefix, players } = logentry {
+            assert_eq!(prefix.second, 5);
+            assert_eq!(players.len(), 2);
+
+            assert_eq!(players[0].nick, "foobar");
+            assert_eq!(players[0].entity_index, 2);
+            assert_eq!(players[0].awards.len(), 2);
+            assert_eq!(players[0].awards["assists"].pos, 1);
+            assert_eq!(players[0].awards["3k"].pos, 2);
+            assert_approx_eq!(players[0].awards["3k"].score, 1.5f32, std::f32::EPSILON);
+
+            assert_eq!(players[1].nick, "bazgaz");
+            assert_eq!(players[1].entity_index, 3);
+            assert_approx_eq!(players[1].awards["hsp"].value, 50.0f32, std::f32::EPSILON);
+        } else {
+            panic!("wrong LogEntry type received, {:#?}", logentry)
+        }
+
+        let logentry = processor.read_entry().await.unwrap();
+        assert!(matches!(logentry, LogEntry::GameOver { .. }));
+    }
+
     #[async_std::test]
     async fn log_game_over() {
         let logentry = parse_line(r#"L 01/02/2020 - 03:04:05: Game Over: competitive mg_active de_inferno score 11:16 after 50 min"#).await;
@@ -1,2 +1,9 @@
-mod logs;
-mod rcon;
+pub mod cvars;
+pub mod economy;
+pub mod ingest;
+pub mod logs;
+pub mod rcon;
+pub mod receiver;
+pub mod rounds;
+pub mod stats;
+pub mod utility;
@@ -0,0 +1,135 @@
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+lazy_static! {
+    /// Name of a backup written by Get5, see the `get5_backup_path` cvar
+    static ref BACKUP_FILE: Regex = Regex::new(
+        r"^get5_backup_match(?P<matchid>.+)_map(?P<map_number>\d+)_round(?P<round>\d+)\.cfg$"
+    )
+    .unwrap();
+    /// Line of `get5_listbackups`: the file, followed by the time it was written, the team names,
+    /// the map and the score, if Get5 could read them from the backup
+    static ref BACKUP_LINE: Regex = Regex::new(
+        r#"^(?P<file>\S+\.cfg)(?:\s+(?P<timestamp>\d{4}-\d\d-\d\d \d\d:\d\d:\d\d)\s+"(?P<team1>[^"]*)"\s+"(?P<team2>[^"]*)"\s+(?P<map_name>\S+)\s+(?P<team1_score>\d+)\s+(?P<team2_score>\d+))?$"#
+    )
+    .unwrap();
+}
+
+/// Details of a backup, read from the output of `get5_listbackups`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Get5Backup {
+    /// Name of the backup, as given to `get5_loadbackup`
+    pub file: String,
+    pub matchid: String,
+    /// Zero-based index of the map in the series
+    pub map_number: i32,
+    /// Round the backup restores the map to
+    pub round: i32,
+    /// Local time of the game server when the backup was written
+    pub timestamp: Option<NaiveDateTime>,
+    pub team1_name: Option<String>,
+    pub team2_name: Option<String>,
+    pub map_name: Option<String>,
+    pub team1_score: Option<i32>,
+    pub team2_score: Option<i32>,
+}
+
+impl Get5Backup {
+    /// Parses a line of `get5_listbackups`, `None` if it doesn't describe a backup
+    fn parse_line(line: &str) -> Option<Self> {
+        let line_caps = BACKUP_LINE.captures(line.trim())?;
+        let file = line_caps.name("file")?.as_str();
+        let file_caps = BACKUP_FILE.captures(file)?;
+
+        let text = |name: &str| line_caps.name(name).map(|m| m.as_str().to_string());
+        let number = |name: &str| line_caps.name(name).and_then(|m| m.as_str().parse().ok());
+
+        Some(Get5Backup {
+            file: file.to_string(),
+            matchid: file_caps["matchid"].to_string(),
+            map_number: file_caps["map_number"].parse().ok()?,
+            round: file_caps["round"].parse().ok()?,
+            timestamp: line_caps
+                .name("timestamp")
+                .and_then(|m| NaiveDateTime::parse_from_str(m.as_str(), "%Y-%m-%d %H:%M:%S").ok()),
+            team1_name: text("team1"),
+            team2_name: text("team2"),
+            map_name: text("map_name"),
+            team1_score: number("team1_score"),
+            team2_score: number("team2_score"),
+        })
+    }
+}
+
+/// Parses the reply of `get5_listbackups`, ordered by map and round
+///
+/// Lines that don't describe a backup, like the reply when there are none, are skipped.
+pub fn parse_backup_list(reply: &str) -> Vec<Get5Backup> {
+    let mut backups = reply
+        .lines()
+        .filter_map(Get5Backup::parse_line)
+        .collect::<Vec<Get5Backup>>();
+    backups.sort_by_key(|backup| (backup.map_number, backup.round));
+
+    backups
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{parse_backup_list, Get5Backup};
+
+    #[test]
+    fn backup_list() {
+        let reply = concat!(
+            "get5_backup_match3a1e1f1c-1f0e-4c4b-9a9e-4bb6f2c3d6a1_map1_round2.cfg 2021-07-10 15:02:44 \"Team A\" \"Team B\" de_inferno 1 0\n",
+            "get5_backup_match3a1e1f1c-1f0e-4c4b-9a9e-4bb6f2c3d6a1_map0_round12.cfg 2021-07-10 14:33:02 \"Team A\" \"Team B\" de_dust2 7 4\n",
+            "get5_backup_match3a1e1f1c-1f0e-4c4b-9a9e-4bb6f2c3d6a1_map0_round3.cfg\n",
+            "Unknown line\n",
+        );
+
+        let backups = parse_backup_list(reply);
+        assert_eq!(
+            backups
+                .iter()
+                .map(|backup| (backup.map_number, backup.round))
+                .collect::<Vec<(i32, i32)>>(),
+            vec![(0, 3), (0, 12), (1, 2)]
+        );
+
+        assert_eq!(
+            backups[1],
+            Get5Backup {
+                file: "get5_backup_match3a1e1f1c-1f0e-4c4b-9a9e-4bb6f2c3d6a1_map0_round12.cfg"
+                    .to_string(),
+                matchid: "3a1e1f1c-1f0e-4c4b-9a9e-4bb6f2c3d6a1".to_string(),
+                map_number: 0,
+                round: 12,
+                timestamp: Some(
+                    NaiveDate::from_ymd_opt(2021, 7, 10)
+                        .unwrap()
+                        .and_hms_opt(14, 33, 2)
+                        .unwrap()
+                ),
+                team1_name: Some("Team A".to_string()),
+                team2_name: Some("Team B".to_string()),
+                map_name: Some("de_dust2".to_string()),
+                team1_score: Some(7),
+                team2_score: Some(4),
+            }
+        );
+
+        // Only the name of the file is known
+        assert_eq!(backups[0].timestamp, None);
+        assert_eq!(backups[0].map_name, None);
+    }
+
+    #[test]
+    fn no_backups() {
+        assert!(
+            parse_backup_list("Found no backup files matching the provided parameters.").is_empty()
+        );
+        assert!(parse_backup_list("").is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+/// Commands allowed by default: looking at the server, talking to the players, and the commands
+/// of Get5 and CS:GO to run a match
+pub const DEFAULT_ALLOWED_COMMANDS: &[&str] = &[
+    "status",
+    "stats",
+    "users",
+    "ping",
+    "say",
+    "get5_status",
+    "get5_listbackups",
+    "get5_forceready",
+    "sm_pause",
+    "sm_unpause",
+    "mp_pause_match",
+    "mp_unpause_match",
+    "mp_warmup_end",
+];
+
+/// Commands rejected even when they are allowed, as they stop the server, take control of it away
+/// from us, or run other commands that would not be checked
+pub const DEFAULT_DENIED_COMMANDS: &[&str] = &[
+    "quit",
+    "exit",
+    "restart",
+    "_restart",
+    "killserver",
+    "rcon_password",
+    "sv_password",
+    "log",
+    "sv_logsecret",
+    "logaddress_del",
+    "logaddress_delall",
+    "logaddress_delall_http",
+    // Run commands that are not checked
+    "alias",
+    "bind",
+    "exec",
+    "execifexists",
+    "sm_execcfg",
+    "sm_rcon",
+    "sm_cvar",
+    // Plugins, Get5 itself included
+    "sm",
+    "sm_plugins",
+    "meta",
+    "plugin_load",
+    "plugin_unload",
+    "plugin_pause",
+];
+
+/// Decides which commands typed in a console may be sent to a server
+///
+/// A command is rejected if it is on the deny list, or if the allow list is not empty and it is
+/// not on it. The server runs every part of a line separated by a semicolon or a new line, so
+/// every part is checked on its own. Commands are compared case-insensitively, without the quotes
+/// and the `+`/`-` prefix the server ignores, see `command_name`.
+#[derive(Clone, Debug)]
+pub struct CommandFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl CommandFilter {
+    pub fn new<A, D>(allow: A, deny: D) -> Self
+    where
+        A: IntoIterator,
+        A::Item: AsRef<str>,
+        D: IntoIterator,
+        D::Item: AsRef<str>,
+    {
+        CommandFilter {
+            allow: allow
+                .into_iter()
+                .map(|command| command.as_ref().trim().to_lowercase())
+                .filter(|command| !command.is_empty())
+                .collect(),
+            deny: deny
+                .into_iter()
+                .map(|command| command.as_ref().trim().to_lowercase())
+                .filter(|command| !command.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Checks every command of the line, and returns the first one that is rejected
+    pub fn check<'a>(&self, line: &'a str) -> Result<(), &'a str> {
+        for part in line.split([';', '\n']) {
+            let command = match command_name(part) {
+                Some(command) => command,
+                None => continue,
+            };
+            if !self.allows(command) {
+                return Err(command);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn allows(&self, command: &str) -> bool {
+        let command = command.to_lowercase();
+
+        if self.deny.contains(&command) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.contains(&command)
+    }
+}
+
+impl Default for CommandFilter {
+    fn default() -> Self {
+        CommandFilter::new(DEFAULT_ALLOWED_COMMANDS, DEFAULT_DENIED_COMMANDS)
+    }
+}
+
+/// Name of the command the server runs for a part of a line, `None` if the part is blank
+///
+/// The server reads a quoted name, e.g. `"quit"`, as the name without the quotes, and runs the
+/// commands starting with `+` or `-` the same way as the ones without, e.g. `+quit`.
+fn command_name(part: &str) -> Option<&str> {
+    let part = part.trim_start();
+    let name = match part.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or(quoted),
+        None => part
+            .split(|c: char| c.is_whitespace() || c == '"')
+            .next()
+            .unwrap_or(part),
+    };
+    let name = name.trim().trim_start_matches(['+', '-']);
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandFilter, DEFAULT_DENIED_COMMANDS};
+
+    #[test]
+    fn deny_list() {
+        let filter = CommandFilter::new(Vec::<String>::new(), DEFAULT_DENIED_COMMANDS);
+
+        assert_eq!(filter.check("status"), Ok(()));
+        assert_eq!(filter.check("say \"gl hf\""), Ok(()));
+        assert_eq!(filter.check("  "), Ok(()));
+        assert_eq!(filter.check("quit"), Err("quit"));
+        assert_eq!(filter.check("EXIT"), Err("EXIT"));
+        // Chained commands can't sneak past the filter
+        assert_eq!(filter.check("status; quit"), Err("quit"));
+        assert_eq!(filter.check("status\nexit now"), Err("exit"));
+    }
+
+    #[test]
+    fn allow_list() {
+        let filter = CommandFilter::new(vec!["status", "get5_status"], vec!["quit"]);
+
+        assert_eq!(filter.check("status"), Ok(()));
+        assert_eq!(filter.check("Get5_Status"), Ok(()));
+        assert_eq!(filter.check("mp_restartgame 1"), Err("mp_restartgame"));
+        assert_eq!(
+            filter.check("status;mp_restartgame 1"),
+            Err("mp_restartgame")
+        );
+
+        // The deny list wins over the allow list
+        let filter = CommandFilter::new(vec!["quit"], vec!["quit"]);
+        assert_eq!(filter.check("quit"), Err("quit"));
+    }
+
+    #[test]
+    fn default_allow_list() {
+        let filter = CommandFilter::default();
+
+        assert_eq!(filter.check("status"), Ok(()));
+        assert_eq!(filter.check("say \"gl hf\"; sm_pause"), Ok(()));
+        assert_eq!(filter.check("mp_restartgame 1"), Err("mp_restartgame"));
+        assert_eq!(filter.check("changelevel de_dust2"), Err("changelevel"));
+    }
+
+    #[test]
+    fn bypasses() {
+        let filters = [
+            CommandFilter::default(),
+            CommandFilter::new(Vec::<String>::new(), DEFAULT_DENIED_COMMANDS),
+        ];
+
+        for filter in filters.iter() {
+            // Quotes and prefixes the server ignores
+            assert_eq!(filter.check("\"quit\""), Err("quit"));
+            assert_eq!(filter.check("  \"QUIT\" now"), Err("QUIT"));
+            assert_eq!(filter.check("status;\"exit\""), Err("exit"));
+            assert_eq!(filter.check("quit\"\""), Err("quit"));
+            assert_eq!(filter.check("+quit"), Err("quit"));
+            assert_eq!(filter.check("-exit"), Err("exit"));
+            assert_eq!(filter.check("\"+quit\""), Err("quit"));
+            // Commands that run other commands
+            assert_eq!(filter.check("alias x quit; x"), Err("alias"));
+            assert_eq!(filter.check("exec server.cfg"), Err("exec"));
+            assert_eq!(filter.check("sm_rcon quit"), Err("sm_rcon"));
+            assert_eq!(filter.check("sm_cvar rcon_password x"), Err("sm_cvar"));
+            // Plugins, and the logs the matches are followed with
+            assert_eq!(filter.check("sm plugins unload get5"), Err("sm"));
+            assert_eq!(filter.check("sm_plugins unload get5"), Err("sm_plugins"));
+            assert_eq!(filter.check("plugin_unload 1"), Err("plugin_unload"));
+            assert_eq!(filter.check("log off"), Err("log"));
+            assert_eq!(filter.check("sv_logsecret 0"), Err("sv_logsecret"));
+        }
+    }
+}
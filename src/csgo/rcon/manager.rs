@@ -0,0 +1,278 @@
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_std::future::timeout;
+use async_std::sync::Mutex as AsyncMutex;
+use chrono::{NaiveDateTime, Utc};
+use rcon::Connection;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+
+use crate::csgo::rcon::{RCONError, RconConnection};
+use crate::database::get_server;
+
+/// Time allowed to connect and authenticate to a server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time allowed for a server to reply to a command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before reconnecting after the first failure, doubled at every consecutive failure
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Health of the RCON connection to a server
+#[derive(Debug, Clone)]
+pub struct ServerHealth {
+    pub server_id: Uuid,
+    /// An authenticated connection is open
+    pub connected: bool,
+    /// Failures since the last successful command
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// Time of the last successful command
+    pub last_success: Option<NaiveDateTime>,
+}
+
+/// Connection to a server, and the bookkeeping for reconnecting
+struct ServerState {
+    conn: Option<Connection>,
+    consecutive_failures: u32,
+    /// No connection attempt is made before this time
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+    last_success: Option<NaiveDateTime>,
+}
+
+impl ServerState {
+    fn new() -> Self {
+        ServerState {
+            conn: None,
+            consecutive_failures: 0,
+            retry_at: None,
+            last_error: None,
+            last_success: None,
+        }
+    }
+
+    fn failed(&mut self, err: &RCONError) {
+        self.conn = None;
+        self.consecutive_failures += 1;
+        self.last_error = Some(err.to_string());
+
+        let backoff = RECONNECT_BACKOFF_MIN * 2u32.saturating_pow(self.consecutive_failures - 1);
+        self.retry_at = Some(Instant::now() + min(backoff, RECONNECT_BACKOFF_MAX));
+    }
+
+    fn succeeded(&mut self) {
+        self.consecutive_failures = 0;
+        self.retry_at = None;
+        self.last_error = None;
+        self.last_success = Some(Utc::now().naive_utc());
+    }
+}
+
+/// Keeps one authenticated RCON connection per game server in the `servers` table
+///
+/// Commands to a server are sent one at a time. A connection is opened on the first command, and
+/// reopened after it failed, waiting longer after every consecutive failure. The address and
+/// password of the server are read from the database on every connection attempt, such that
+/// changes to the server apply from the next connection.
+#[derive(Clone)]
+pub struct RconManager {
+    db_pool: Pool<Postgres>,
+    servers: Arc<Mutex<HashMap<Uuid, Arc<AsyncMutex<ServerState>>>>>,
+}
+
+impl RconManager {
+    pub fn new(db_pool: Pool<Postgres>) -> Self {
+        RconManager {
+            db_pool,
+            servers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn server_state(&self, server_id: Uuid) -> Arc<AsyncMutex<ServerState>> {
+        self.servers
+            .lock()
+            .unwrap()
+            .entry(server_id)
+            .or_insert_with(|| Arc::new(AsyncMutex::new(ServerState::new())))
+            .clone()
+    }
+
+    /// Handle sending the commands of a RCON feature to the server, see `RconConnection`
+    pub fn server(&self, server_id: Uuid) -> ServerRcon<'_> {
+        ServerRcon {
+            manager: self,
+            server_id,
+        }
+    }
+
+    /// Sends a command to the server, and returns its reply
+    pub async fn cmd(&self, server_id: Uuid, command: &str) -> Result<String, RCONError> {
+        let state = self.server_state(server_id);
+        let mut state = state.lock().await;
+
+        let result = self.cmd_locked(server_id, &mut state, command).await;
+        match &result {
+            Ok(_) => state.succeeded(),
+            // The connection is fine, the server just did not understand the command
+            Err(RCONError::UnknownCmd) | Err(RCONError::UnexpectedReply) => {}
+            // Keep counting down to the next attempt
+            Err(RCONError::Unreachable(_)) => {}
+            Err(err) => {
+                warn!("RCON command failed on server (id={}): {}", server_id, err);
+                state.failed(err);
+            }
+        }
+
+        result
+    }
+
+    async fn cmd_locked(
+        &self,
+        server_id: Uuid,
+        state: &mut ServerState,
+        command: &str,
+    ) -> Result<String, RCONError> {
+        if state.conn.is_none() {
+            if let Some(retry_at) = state.retry_at {
+                let now = Instant::now();
+                if retry_at > now {
+                    return Err(RCONError::Unreachable(retry_at - now));
+                }
+            }
+
+            state.conn = Some(self.connect(server_id).await?);
+        }
+
+        let conn = state.conn.as_mut().unwrap();
+        match timeout(COMMAND_TIMEOUT, Connection::cmd(conn, command)).await {
+            Ok(reply) => reply.map_err(RCONError::Conn),
+            Err(_) => Err(RCONError::Timeout),
+        }
+    }
+
+    async fn connect(&self, server_id: Uuid) -> Result<Connection, RCONError> {
+        let mut db_conn = self
+            .db_pool
+            .acquire()
+            .await
+            .map_err(|err| RCONError::Other(err.into()))?;
+        let server = get_server(&mut db_conn, server_id)
+            .await
+            .map_err(|err| RCONError::Other(err.into()))?
+            .ok_or(RCONError::NoSuchServer)?;
+        let address = format!("{}:{}", server.host.ip(), server.port);
+
+        debug!(
+            "connecting to RCON of server (id={}) at {}",
+            server_id, address
+        );
+        match timeout(
+            CONNECT_TIMEOUT,
+            Connection::connect(address.as_str(), server.password.as_str()),
+        )
+        .await
+        {
+            Ok(conn) => Ok(conn?),
+            Err(_) => Err(RCONError::Timeout),
+        }
+    }
+
+    /// Closes the connection to the server, e.g. after the server has been changed or deleted
+    pub async fn disconnect(&self, server_id: Uuid) {
+        let state = self.servers.lock().unwrap().remove(&server_id);
+        if let Some(state) = state {
+            // Wait for the command being sent, if any
+            state.lock().await.conn = None;
+        }
+    }
+
+    /// Health of the connections to the servers a command has been sent to
+    pub async fn health(&self) -> Vec<ServerHealth> {
+        let servers = self
+            .servers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(server_id, state)| (*server_id, state.clone()))
+            .collect::<Vec<_>>();
+
+        let mut health = Vec::with_capacity(servers.len());
+        for (server_id, state) in servers {
+            health.push(Self::server_health(server_id, &*state.lock().await));
+        }
+
+        health
+    }
+
+    /// Health of the connection to the server, `None` if no command has been sent to it
+    pub async fn health_of(&self, server_id: Uuid) -> Option<ServerHealth> {
+        let state = self.servers.lock().unwrap().get(&server_id).cloned()?;
+        let state = state.lock().await;
+
+        Some(Self::server_health(server_id, &state))
+    }
+
+    fn server_health(server_id: Uuid, state: &ServerState) -> ServerHealth {
+        ServerHealth {
+            server_id,
+            connected: state.conn.is_some(),
+            consecutive_failures: state.consecutive_failures,
+            last_error: state.last_error.clone(),
+            last_success: state.last_success,
+        }
+    }
+}
+
+/// RCON of a server, through `RconManager`
+///
+/// Every command is sent on its own, commands of other tasks may be sent in between.
+pub struct ServerRcon<'a> {
+    manager: &'a RconManager,
+    server_id: Uuid,
+}
+
+#[async_trait]
+impl RconConnection for ServerRcon<'_> {
+    async fn cmd(&mut self, command: &str) -> Result<String, RCONError> {
+        self.manager.cmd(self.server_id, command).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{RCONError, ServerState, RECONNECT_BACKOFF_MAX};
+
+    #[test]
+    fn reconnect_backoff() {
+        let mut state = ServerState::new();
+        let err = RCONError::Timeout;
+
+        let backoff = |state: &ServerState| state.retry_at.unwrap() - Instant::now();
+
+        state.failed(&err);
+        assert!(backoff(&state) <= Duration::from_secs(1));
+        state.failed(&err);
+        assert!(backoff(&state) > Duration::from_secs(1));
+        assert!(backoff(&state) <= Duration::from_secs(2));
+        for _ in 0..40 {
+            state.failed(&err);
+        }
+        assert!(backoff(&state) <= RECONNECT_BACKOFF_MAX);
+        assert_eq!(state.consecutive_failures, 42);
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("The server did not reply in time")
+        );
+
+        state.succeeded();
+        assert_eq!(state.consecutive_failures, 0);
+        assert!(state.retry_at.is_none());
+        assert!(state.last_success.is_some());
+    }
+}
@@ -0,0 +1,899 @@
+use std::time::Duration;
+
+use async_std::task;
+use rcon::Connection;
+
+use crate::common::generate_password;
+use backup::parse_backup_list;
+use get5status::Get5Status;
+
+pub use backup::Get5Backup;
+pub use filter::{CommandFilter, DEFAULT_ALLOWED_COMMANDS, DEFAULT_DENIED_COMMANDS};
+pub use manager::{RconManager, ServerHealth, ServerRcon};
+
+mod backup;
+mod filter;
+mod manager;
+pub mod poller;
+
+/// Number of times the state of Get5 is checked after asking it to load a match
+const LOAD_MATCH_CHECKS: u32 = 10;
+/// Time between the checks of the state of Get5 after asking it to load a match
+const LOAD_MATCH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// NOTE: If the value of the CVAR start with a double quote, everything following it will be
+// assumed to be part of the value, except the last character if it a double quote.
+// If the value is not started with a double quote, you can chain commands/CVARs together with
+// a semicolon as separator
+
+// enum definition: https://github.com/splewis/get5/blob/51fe79d0da8131f7104e4a78551f4364f06be950/scripting/include/get5.inc#L6
+// serialization code: https://github.com/splewis/get5/blob/d5dd9f8fa501261cd2f15067d55b1f7f25e1530b/scripting/get5.sp#L1324
+// maps: json object, key "map%d", value: map name, https://github.com/splewis/get5/blob/51fe79d0da8131f7104e4a78551f4364f06be950/scripting/get5.sp#L1357
+pub mod get5status {
+    use std::collections::HashMap;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer};
+    use serde_json::{Map, Value};
+
+    use crate::common::Get5State;
+
+    /// Value of `gamestate_string` for every value of `gamestate`, see `Get5State` in get5.inc
+    const GAMESTATE_STRINGS: [&str; 9] = [
+        "none",
+        "waiting for map veto",
+        "map veto",
+        "warmup",
+        "knife round",
+        "waiting for knife round decision",
+        "going live",
+        "live",
+        "postgame",
+    ];
+
+    /// Reply of `get5_status`
+    ///
+    /// The state is read from the integer `gamestate` field, or from `gamestate_string` when the
+    /// former is missing. Serde can't tag enums by integers (see
+    /// https://github.com/serde-rs/serde/pull/1392), so `gamestate` is translated to the matching
+    /// `gamestate_string` before the variant is decoded.
+    #[derive(Deserialize, Debug)]
+    #[serde(remote = "Self", tag = "gamestate_string")]
+    pub enum Get5Status {
+        #[serde(rename = "none")]
+        Idle {
+            plugin_version: String,
+            paused: bool,
+        },
+
+        #[serde(rename = "waiting for map veto")]
+        PreVeto {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+        },
+
+        #[serde(rename = "map veto")]
+        Veto {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+
+        #[serde(rename = "warmup")]
+        Warmup {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+
+        #[serde(rename = "knife round")]
+        KnifeRound {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+
+        #[serde(rename = "waiting for knife round decision")]
+        WaitingForKnifeRoundDecision {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+
+        #[serde(rename = "going live")]
+        GoingLive {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+
+        #[serde(rename = "live")]
+        Live {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+
+        #[serde(rename = "postgame")]
+        PostGame {
+            matchid: String,
+            loaded_config_file: String,
+            plugin_version: String,
+            map_number: i32,
+            team2: Team,
+            team1: Team,
+            paused: bool,
+            #[serde(deserialize_with = "deserialize_maps")]
+            maps: Vec<String>,
+        },
+    }
+
+    impl<'de> Deserialize<'de> for Get5Status {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let mut status = Map::<String, Value>::deserialize(deserializer)?;
+
+            if let Some(gamestate) = status.get("gamestate").and_then(Value::as_u64) {
+                let gamestate_string = GAMESTATE_STRINGS
+                    .get(gamestate as usize)
+                    .ok_or_else(|| D::Error::custom(format!("unknown gamestate {}", gamestate)))?;
+                status.insert(
+                    "gamestate_string".to_string(),
+                    Value::String(gamestate_string.to_string()),
+                );
+            }
+
+            // Decodes the tagged variant, see `#[serde(remote = "Self")]`
+            Get5Status::deserialize(Value::Object(status)).map_err(D::Error::custom)
+        }
+    }
+
+    /// Decodes the `map0`..`mapN` object into a list of maps, ordered by their number
+    fn deserialize_maps<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let maps = HashMap::<String, String>::deserialize(deserializer)?;
+
+        let mut numbered_maps = maps
+            .into_iter()
+            .map(|(key, map)| {
+                key.strip_prefix("map")
+                    .and_then(|number| number.parse::<usize>().ok())
+                    .map(|number| (number, map))
+                    .ok_or_else(|| D::Error::custom(format!("unexpected map key {:?}", key)))
+            })
+            .collect::<Result<Vec<(usize, String)>, D::Error>>()?;
+        numbered_maps.sort_by_key(|(number, _)| *number);
+
+        Ok(numbered_maps.into_iter().map(|(_, map)| map).collect())
+    }
+
+    impl Get5Status {
+        pub fn state(&self) -> Get5State {
+            match self {
+                Get5Status::Idle { .. } => Get5State::Idle,
+                Get5Status::PreVeto { .. } => Get5State::PreVeto,
+                Get5Status::Veto { .. } => Get5State::Veto,
+                Get5Status::Warmup { .. } => Get5State::Warmup,
+                Get5Status::KnifeRound { .. } => Get5State::KnifeRound,
+                Get5Status::WaitingForKnifeRoundDecision { .. } => {
+                    Get5State::WaitingForKnifeRoundDecision
+                }
+                Get5Status::GoingLive { .. } => Get5State::GoingLive,
+                Get5Status::Live { .. } => Get5State::Live,
+                Get5Status::PostGame { .. } => Get5State::PostGame,
+            }
+        }
+
+        pub fn plugin_version(&self) -> &str {
+            match self {
+                Get5Status::Idle { plugin_version, .. }
+                | Get5Status::PreVeto { plugin_version, .. }
+                | Get5Status::Veto { plugin_version, .. }
+                | Get5Status::Warmup { plugin_version, .. }
+                | Get5Status::KnifeRound { plugin_version, .. }
+                | Get5Status::WaitingForKnifeRoundDecision { plugin_version, .. }
+                | Get5Status::GoingLive { plugin_version, .. }
+                | Get5Status::Live { plugin_version, .. }
+                | Get5Status::PostGame { plugin_version, .. } => plugin_version,
+            }
+        }
+
+        pub fn paused(&self) -> bool {
+            match self {
+                Get5Status::Idle { paused, .. }
+                | Get5Status::PreVeto { paused, .. }
+                | Get5Status::Veto { paused, .. }
+                | Get5Status::Warmup { paused, .. }
+                | Get5Status::KnifeRound { paused, .. }
+                | Get5Status::WaitingForKnifeRoundDecision { paused, .. }
+                | Get5Status::GoingLive { paused, .. }
+                | Get5Status::Live { paused, .. }
+                | Get5Status::PostGame { paused, .. } => *paused,
+            }
+        }
+
+        /// Id of the loaded match, `None` when Get5 is idle
+        pub fn matchid(&self) -> Option<&str> {
+            match self {
+                Get5Status::Idle { .. } => None,
+                Get5Status::PreVeto { matchid, .. }
+                | Get5Status::Veto { matchid, .. }
+                | Get5Status::Warmup { matchid, .. }
+                | Get5Status::KnifeRound { matchid, .. }
+                | Get5Status::WaitingForKnifeRoundDecision { matchid, .. }
+                | Get5Status::GoingLive { matchid, .. }
+                | Get5Status::Live { matchid, .. }
+                | Get5Status::PostGame { matchid, .. } => Some(matchid),
+            }
+        }
+
+        /// Zero-based index of the map being played, `None` when Get5 is idle
+        pub fn map_number(&self) -> Option<i32> {
+            match self {
+                Get5Status::Idle { .. } => None,
+                Get5Status::PreVeto { map_number, .. }
+                | Get5Status::Veto { map_number, .. }
+                | Get5Status::Warmup { map_number, .. }
+                | Get5Status::KnifeRound { map_number, .. }
+                | Get5Status::WaitingForKnifeRoundDecision { map_number, .. }
+                | Get5Status::GoingLive { map_number, .. }
+                | Get5Status::Live { map_number, .. }
+                | Get5Status::PostGame { map_number, .. } => Some(*map_number),
+            }
+        }
+
+        /// Team1 and team2 of the loaded match, `None` when Get5 is idle
+        pub fn teams(&self) -> Option<(&Team, &Team)> {
+            match self {
+                Get5Status::Idle { .. } => None,
+                Get5Status::PreVeto { team1, team2, .. }
+                | Get5Status::Veto { team1, team2, .. }
+                | Get5Status::Warmup { team1, team2, .. }
+                | Get5Status::KnifeRound { team1, team2, .. }
+                | Get5Status::WaitingForKnifeRoundDecision { team1, team2, .. }
+                | Get5Status::GoingLive { team1, team2, .. }
+                | Get5Status::Live { team1, team2, .. }
+                | Get5Status::PostGame { team1, team2, .. } => Some((team1, team2)),
+            }
+        }
+
+        /// Maps of the series in the order they are played, empty until the veto is over
+        pub fn maps(&self) -> &[String] {
+            match self {
+                Get5Status::Idle { .. } | Get5Status::PreVeto { .. } => &[],
+                Get5Status::Veto { maps, .. }
+                | Get5Status::Warmup { maps, .. }
+                | Get5Status::KnifeRound { maps, .. }
+                | Get5Status::WaitingForKnifeRoundDecision { maps, .. }
+                | Get5Status::GoingLive { maps, .. }
+                | Get5Status::Live { maps, .. }
+                | Get5Status::PostGame { maps, .. } => maps,
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Team {
+        connected_clients: i32,
+        current_map_score: i32,
+        ready: bool,
+        name: String,
+        series_score: i32,
+        side: Side,
+    }
+
+    impl Team {
+        pub fn connected_clients(&self) -> i32 {
+            self.connected_clients
+        }
+
+        pub fn current_map_score(&self) -> i32 {
+            self.current_map_score
+        }
+
+        pub fn ready(&self) -> bool {
+            self.ready
+        }
+
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        pub fn series_score(&self) -> i32 {
+            self.series_score
+        }
+
+        pub fn side(&self) -> Side {
+            self.side
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Side {
+        #[serde(alias = "t")]
+        T,
+        #[serde(alias = "ct")]
+        CT,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RCONError {
+    #[error("Connection error")]
+    Conn(#[from] rcon::Error),
+    #[error("The server did not reply in time")]
+    Timeout,
+    #[error("The server is unreachable, next connection attempt in {0:?}")]
+    Unreachable(std::time::Duration),
+    #[error("No such server")]
+    NoSuchServer,
+    #[error("Get5 is not in the expected state")]
+    UnexpectedState(Get5Status),
+    #[error("The server is not playing the match")]
+    NotPlayingMatch(Get5Status),
+    #[error("Get5 did not load the match")]
+    MatchNotLoaded,
+    #[error("Get5 could not load the backup {0}")]
+    BackupNotLoaded(String),
+    #[error("Could not interpret response")]
+    UnexpectedReply,
+    #[error("Unknown command, it is not supported by the server. A plugin might not be installed or loaded")]
+    UnknownCmd,
+    #[error("Something unexpected happened")]
+    Other(anyhow::Error),
+}
+
+/// Connection to the RCON of a game server
+#[async_trait]
+pub trait RconConnection: Send {
+    /// Sends a command, and returns the reply of the server
+    async fn cmd(&mut self, command: &str) -> Result<String, RCONError>;
+}
+
+#[async_trait]
+impl RconConnection for Connection {
+    async fn cmd(&mut self, command: &str) -> Result<String, RCONError> {
+        Connection::cmd(self, command)
+            .await
+            .map_err(RCONError::Conn)
+    }
+}
+
+pub(crate) async fn get5_status(conn: &mut impl RconConnection) -> Result<Get5Status, RCONError> {
+    // Send command to CS:GO server
+    let full_resp = conn.cmd("get5_status").await?;
+
+    // Pick out the relevant line
+    let reply = full_resp.lines().next().ok_or(RCONError::UnexpectedReply)?;
+
+    if reply == "Unknown command \"get5_status\"" {
+        // Get5 is not installed
+        return Result::Err(RCONError::UnknownCmd);
+    }
+
+    let status: Get5Status = serde_json::from_str(reply).map_err(|_| RCONError::UnexpectedReply)?;
+
+    Result::Ok(status)
+}
+
+/// Sets the password required from a player before the can login to the CS:GO server
+pub(crate) async fn set_player_password(
+    password: &str,
+    conn: &mut impl RconConnection,
+) -> Result<(), RCONError> {
+    // Send command to CS:GO server
+    // Note: The server gives a empty reply on success
+    conn.cmd(format!("sv_password \"{}\"", password).as_str())
+        .await?;
+
+    Ok(())
+}
+
+/// Sets the password required from a player before the can login to the CS:GO server to a random value
+pub(crate) async fn scramble_player_password(
+    conn: &mut impl RconConnection,
+) -> Result<String, RCONError> {
+    let password = generate_password().map_err(RCONError::Other)?;
+    set_player_password(password.as_str(), conn).await?;
+
+    Ok(password)
+}
+
+/// Makes Get5 download and load a match configuration
+///
+/// Get5 downloads the configuration in the background, see `load_match` to know when it is loaded.
+pub(crate) async fn get5_loadmatch_url(
+    url: &str,
+    conn: &mut impl RconConnection,
+) -> Result<(), RCONError> {
+    let reply = conn
+        .cmd(format!("get5_loadmatch_url \"{}\"", url).as_str())
+        .await?;
+
+    if reply.starts_with("Unknown command") {
+        // Get5 is not installed
+        return Err(RCONError::UnknownCmd);
+    }
+
+    Ok(())
+}
+
+/// Loads a match on an idle server, from the Get5 configuration served at the URL
+///
+/// The player password is scrambled before loading the match, such that no one can join before
+/// the password is handed out. Returns the new player password, and the state of Get5 once the
+/// match is loaded.
+pub(crate) async fn load_match(
+    url: &str,
+    conn: &mut impl RconConnection,
+) -> Result<(String, Get5Status), RCONError> {
+    match get5_status(conn).await? {
+        Get5Status::Idle { .. } => {}
+        status => return Err(RCONError::UnexpectedState(status)),
+    }
+
+    let password = scramble_player_password(conn).await?;
+    get5_loadmatch_url(url, conn).await?;
+
+    for check in 0..LOAD_MATCH_CHECKS {
+        if check > 0 {
+            task::sleep(LOAD_MATCH_CHECK_INTERVAL).await;
+        }
+
+        match get5_status(conn).await? {
+            // Still downloading the configuration
+            Get5Status::Idle { .. } => continue,
+            status @ Get5Status::PreVeto { .. } | status @ Get5Status::Warmup { .. } => {
+                return Ok((password, status))
+            }
+            status => return Err(RCONError::UnexpectedState(status)),
+        }
+    }
+
+    Err(RCONError::MatchNotLoaded)
+}
+
+/// Sends an admin command of Get5 or SourceMod, and returns the state of Get5 once it is applied
+async fn get5_admin_cmd(
+    command: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    let reply = conn.cmd(command).await?;
+
+    if reply.starts_with("Unknown command") {
+        // Get5 is not installed
+        return Err(RCONError::UnknownCmd);
+    }
+
+    get5_status(conn).await
+}
+
+/// State of Get5 on the server, checking that it is playing the match `matchid`
+///
+/// The server of a match may have loaded another match in the meantime, a command must not be sent
+/// to it on behalf of the match.
+async fn get5_status_of_match(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    let status = get5_status(conn).await?;
+    if status.matchid() != Some(matchid) {
+        return Err(RCONError::NotPlayingMatch(status));
+    }
+
+    Ok(status)
+}
+
+/// Pauses a live match at the next freeze time
+pub(crate) async fn pause_match(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    match get5_status_of_match(matchid, conn).await? {
+        Get5Status::KnifeRound { paused: false, .. } | Get5Status::Live { paused: false, .. } => {}
+        status => return Err(RCONError::UnexpectedState(status)),
+    }
+
+    get5_admin_cmd("sm_pause", conn).await
+}
+
+/// Resumes a paused match, without waiting for the teams to agree
+pub(crate) async fn unpause_match(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    let status = get5_status_of_match(matchid, conn).await?;
+    if !status.paused() {
+        return Err(RCONError::UnexpectedState(status));
+    }
+
+    get5_admin_cmd("sm_unpause", conn).await
+}
+
+/// Marks every team ready, such that the veto or the map starts
+pub(crate) async fn force_ready(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    match get5_status_of_match(matchid, conn).await? {
+        Get5Status::PreVeto { .. } | Get5Status::Warmup { .. } => {}
+        status => return Err(RCONError::UnexpectedState(status)),
+    }
+
+    get5_admin_cmd("get5_forceready", conn).await
+}
+
+/// Ends the loaded match without a winner, Get5 is idle afterwards
+pub(crate) async fn end_match(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    get5_status_of_match(matchid, conn).await?;
+
+    get5_admin_cmd("get5_endmatch", conn).await
+}
+
+/// Starts the series with the maps in the order of the map list, instead of vetoing them
+pub(crate) async fn skip_veto(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    match get5_status_of_match(matchid, conn).await? {
+        Get5Status::PreVeto { .. } | Get5Status::Veto { .. } => {}
+        status => return Err(RCONError::UnexpectedState(status)),
+    }
+
+    get5_admin_cmd("get5_skipveto", conn).await
+}
+
+/// Lists the backups Get5 wrote for a match, see `get5_backup_path`
+pub(crate) async fn list_backups(
+    matchid: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Vec<Get5Backup>, RCONError> {
+    let reply = conn
+        .cmd(format!("get5_listbackups \"{}\"", matchid).as_str())
+        .await?;
+
+    if reply.starts_with("Unknown command") {
+        // Get5 is not installed
+        return Err(RCONError::UnknownCmd);
+    }
+
+    Ok(parse_backup_list(reply.as_str()))
+}
+
+/// Restores a match from a backup listed by `list_backups`
+///
+/// The match is loaded from the backup if it is not loaded already, e.g. after the server crashed.
+/// The server must be idle or playing the match `matchid`.
+pub(crate) async fn restore_backup(
+    matchid: &str,
+    backup: &str,
+    conn: &mut impl RconConnection,
+) -> Result<Get5Status, RCONError> {
+    let status = get5_status(conn).await?;
+    if !matches!(status, Get5Status::Idle { .. }) && status.matchid() != Some(matchid) {
+        return Err(RCONError::NotPlayingMatch(status));
+    }
+
+    let reply = conn
+        .cmd(format!("get5_loadbackup \"{}\"", backup).as_str())
+        .await?;
+
+    if reply.starts_with("Unknown command") {
+        // Get5 is not installed
+        return Err(RCONError::UnknownCmd);
+    }
+    if reply.contains("Failed to load backup") {
+        return Err(RCONError::BackupNotLoaded(backup.to_string()));
+    }
+
+    get5_status(conn).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::get5status::{Get5Status, Side};
+    use super::{
+        end_match, list_backups, load_match, pause_match, restore_backup, RCONError, RconConnection,
+    };
+    use crate::common::Get5State;
+
+    const RCON_ADDRESS: &str = "127.0.0.1:27015";
+    const RCON_PASSWORD: &str = "password";
+
+    const IDLE: &str =
+        r#"{"plugin_version":"0.7.1","gamestate":0,"paused":false,"gamestate_string":"none"}"#;
+    const PRE_VETO: &str = r#"{"matchid":"example_match","loaded_config_file":"","plugin_version":"0.7.1","map_number":0,"team1":{"connected_clients":0,"current_map_score":0,"ready":false,"name":"EnvyUs","series_score":0,"side":"CT"},"team2":{"connected_clients":0,"current_map_score":0,"ready":false,"name":"Fnatic","series_score":0,"side":"T"},"paused":false,"gamestate":1,"gamestate_string":"waiting for map veto"}"#;
+
+    /// Replies to the commands with scripted replies, and records the commands
+    struct ScriptedConnection {
+        replies: VecDeque<String>,
+        commands: Vec<String>,
+    }
+
+    impl ScriptedConnection {
+        fn new(replies: &[&str]) -> Self {
+            ScriptedConnection {
+                replies: replies.iter().map(|reply| reply.to_string()).collect(),
+                commands: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RconConnection for ScriptedConnection {
+        async fn cmd(&mut self, command: &str) -> Result<String, RCONError> {
+            self.commands.push(command.to_string());
+            Ok(self.replies.pop_front().unwrap_or_default())
+        }
+    }
+
+    /// Status of a live match on the first map
+    fn live_status(paused: bool) -> String {
+        PRE_VETO
+            .replace(r#""gamestate":1,"#, r#""gamestate":7,"#)
+            .replace(
+                r#""gamestate_string":"waiting for map veto""#,
+                r#""gamestate_string":"live""#,
+            )
+            .replace(r#""paused":false"#, &format!(r#""paused":{}"#, paused))
+            .replace(
+                r#""map_number":0,"#,
+                r#""map_number":0,"maps":{"map0":"de_dust2"},"#,
+            )
+    }
+
+    #[async_std::test]
+    async fn load_match_on_idle_server() {
+        let mut conn = ScriptedConnection::new(&[IDLE, "", "", PRE_VETO]);
+
+        let (password, status) = load_match("http://minictrl/api/get5/config?id=1", &mut conn)
+            .await
+            .unwrap();
+
+        assert!(matches!(status, Get5Status::PreVeto { .. }));
+        assert_eq!(conn.commands[0], "get5_status");
+        assert_eq!(conn.commands[1], format!("sv_password \"{}\"", password));
+        assert_eq!(
+            conn.commands[2],
+            "get5_loadmatch_url \"http://minictrl/api/get5/config?id=1\""
+        );
+        assert_eq!(conn.commands[3], "get5_status");
+    }
+
+    #[async_std::test]
+    async fn load_match_on_busy_server() {
+        let mut conn = ScriptedConnection::new(&[PRE_VETO]);
+
+        match load_match("http://minictrl/api/get5/config?id=1", &mut conn).await {
+            Err(RCONError::UnexpectedState(Get5Status::PreVeto { .. })) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        // Nothing is changed on the server
+        assert_eq!(conn.commands, vec!["get5_status".to_string()]);
+    }
+
+    #[async_std::test]
+    async fn pause_live_match() {
+        let live = live_status(false);
+        let paused = live_status(true);
+        let mut conn = ScriptedConnection::new(&[&live, "", &paused]);
+
+        let status = pause_match("example_match", &mut conn).await.unwrap();
+        assert!(status.paused());
+        assert_eq!(
+            conn.commands,
+            vec![
+                "get5_status".to_string(),
+                "sm_pause".to_string(),
+                "get5_status".to_string()
+            ]
+        );
+
+        // Already paused, nothing is sent to the server
+        let mut conn = ScriptedConnection::new(&[&paused]);
+        match pause_match("example_match", &mut conn).await {
+            Err(RCONError::UnexpectedState(Get5Status::Live { .. })) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(conn.commands, vec!["get5_status".to_string()]);
+    }
+
+    #[async_std::test]
+    async fn commands_only_sent_for_the_loaded_match() {
+        // The server loaded another match in the meantime
+        let live = live_status(false);
+        let mut conn = ScriptedConnection::new(&[&live]);
+        match pause_match("other_match", &mut conn).await {
+            Err(RCONError::NotPlayingMatch(Get5Status::Live { .. })) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(conn.commands, vec!["get5_status".to_string()]);
+
+        let mut conn = ScriptedConnection::new(&[IDLE]);
+        match end_match("example_match", &mut conn).await {
+            Err(RCONError::NotPlayingMatch(Get5Status::Idle { .. })) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(conn.commands, vec!["get5_status".to_string()]);
+
+        // A backup of a match is only restored on an idle server or the one playing the match
+        let mut conn = ScriptedConnection::new(&[&live]);
+        match restore_backup(
+            "other_match",
+            "get5_backup_matchother_match_map0_round4.cfg",
+            &mut conn,
+        )
+        .await
+        {
+            Err(RCONError::NotPlayingMatch(Get5Status::Live { .. })) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(conn.commands, vec!["get5_status".to_string()]);
+    }
+
+    #[async_std::test]
+    async fn list_and_restore_backups() {
+        let mut conn = ScriptedConnection::new(&[
+            "get5_backup_matchexample_match_map0_round4.cfg 2021-07-10 14:33:02 \"EnvyUs\" \"Fnatic\" de_dust2 2 1\n",
+        ]);
+        let backups = list_backups("example_match", &mut conn).await.unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].round, 4);
+        assert_eq!(
+            conn.commands,
+            vec!["get5_listbackups \"example_match\"".to_string()]
+        );
+
+        let live = live_status(false);
+        let mut conn = ScriptedConnection::new(&[
+            IDLE,
+            "[Get5] Sucessfully loaded backup get5_backup_matchexample_match_map0_round4.cfg",
+            &live,
+        ]);
+        let status = restore_backup("example_match", backups[0].file.as_str(), &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(status.state(), Get5State::Live);
+        assert_eq!(
+            conn.commands[1],
+            "get5_loadbackup \"get5_backup_matchexample_match_map0_round4.cfg\""
+        );
+
+        let mut conn = ScriptedConnection::new(&[
+            &live,
+            "[Get5] Failed to load backup get5_backup_matchexample_match_map0_round40.cfg - check error logs",
+        ]);
+        match restore_backup(
+            "example_match",
+            "get5_backup_matchexample_match_map0_round40.cfg",
+            &mut conn,
+        )
+        .await
+        {
+            Err(RCONError::BackupNotLoaded(backup)) => {
+                assert_eq!(backup, "get5_backup_matchexample_match_map0_round40.cfg")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn status_numeric_gamestate() {
+        // Without `gamestate_string`, the state is read from `gamestate`
+        let status: Get5Status =
+            serde_json::from_str(r#"{"plugin_version":"0.7.1","gamestate":0,"paused":true}"#)
+                .unwrap();
+        assert_eq!(status.state(), Get5State::Idle);
+        assert!(status.paused());
+
+        // `gamestate` wins over a stale `gamestate_string`
+        let status: Get5Status = serde_json::from_str(&PRE_VETO.replace(
+            r#""gamestate_string":"waiting for map veto""#,
+            r#""gamestate_string":"none""#,
+        ))
+        .unwrap();
+        assert_eq!(status.state(), Get5State::PreVeto);
+
+        assert!(serde_json::from_str::<Get5Status>(
+            r#"{"plugin_version":"0.7.1","gamestate":42,"paused":false}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn status_maps_in_order() {
+        let maps = (0..11)
+            .rev()
+            .map(|n| format!(r#""map{}":"de_map{}""#, n, n))
+            .collect::<Vec<String>>()
+            .join(",");
+        let live = PRE_VETO
+            .replace(r#""gamestate":1,"#, r#""gamestate":7,"#)
+            .replace(
+                r#""gamestate_string":"waiting for map veto""#,
+                r#""gamestate_string":"live""#,
+            )
+            .replace(
+                r#""map_number":0,"#,
+                &format!(r#""map_number":0,"maps":{{{}}},"#, maps),
+            );
+        let status: Get5Status = serde_json::from_str(&live).unwrap();
+
+        assert_eq!(status.state(), Get5State::Live);
+        let expected = (0..11)
+            .map(|n| format!("de_map{}", n))
+            .collect::<Vec<String>>();
+        assert_eq!(status.maps(), expected.as_slice());
+
+        let (team1, team2) = status.teams().unwrap();
+        assert_eq!(team1.name(), "EnvyUs");
+        assert_eq!(team1.side(), Side::CT);
+        assert_eq!(team2.side(), Side::T);
+
+        let pre_veto: Get5Status = serde_json::from_str(PRE_VETO).unwrap();
+        assert!(pre_veto.maps().is_empty());
+    }
+
+    #[cfg(feature = "test_live_rcon")]
+    #[async_std::test]
+    async fn get5_status() {
+        let mut conn = rcon::Connection::connect(RCON_ADDRESS, RCON_PASSWORD)
+            .await
+            .unwrap();
+
+        match super::get5_status(&mut conn).await {
+            Ok(reply) => println!("{:?}", reply),
+            Err(err) => panic!("{:?}", err),
+        };
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use async_std::task;
+use chrono::Utc;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, Pool, Postgres};
+
+use crate::csgo::rcon::get5status::Get5Status;
+use crate::csgo::rcon::{get5_status, RCONError, RconManager};
+use crate::database::models::ServerStatus;
+use crate::database::{
+    get_match, get_server_status, get_servers, set_match_series_score, set_server_status,
+    set_server_unreachable,
+};
+use crate::events::{Event, EventBus};
+use crate::status::{observe_match_status, status_of_get5_state, StatusTrigger};
+
+/// Polls the status of every server in the `servers` table, and stores it in `server_status`
+///
+/// The servers are polled concurrently, once every `interval`. A server is marked unreachable when
+/// no connection could be made to it, the status of its last successful poll is kept. The stored
+/// status is published to `events` after every poll, and the status of the match being played
+/// follows the state of Get5. Errors are logged, and the servers are polled again at the next
+/// interval.
+pub async fn poll_server_status(
+    db_pool: Pool<Postgres>,
+    rcon: RconManager,
+    events: EventBus,
+    interval: Duration,
+) {
+    loop {
+        if let Err(err) = poll_servers(&db_pool, &rcon, &events).await {
+            error!("failed to load the servers to poll: {}", err);
+        }
+
+        task::sleep(interval).await;
+    }
+}
+
+/// Polls every server once, and waits until all of them are polled
+async fn poll_servers(
+    db_pool: &Pool<Postgres>,
+    rcon: &RconManager,
+    events: &EventBus,
+) -> anyhow::Result<()> {
+    let servers = {
+        let mut db_conn = db_pool.acquire().await?;
+        get_servers(&mut db_conn).await?
+    };
+
+    let polls = servers
+        .into_iter()
+        .map(|server| {
+            let db_pool = db_pool.clone();
+            let rcon = rcon.clone();
+            let events = events.clone();
+            task::spawn(async move {
+                if let Err(err) = poll_server(&db_pool, &rcon, &events, server.id).await {
+                    error!(
+                        "failed to store the status of server (id={}): {}",
+                        server.id, err
+                    );
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for poll in polls {
+        poll.await;
+    }
+
+    Ok(())
+}
+
+async fn poll_server(
+    db_pool: &Pool<Postgres>,
+    rcon: &RconManager,
+    events: &EventBus,
+    server_id: Uuid,
+) -> anyhow::Result<()> {
+    let result = get5_status(&mut rcon.server(server_id)).await;
+    let polled_at = Utc::now().naive_utc();
+
+    let mut db_conn = db_pool.acquire().await?;
+    match result {
+        Ok(status) => {
+            set_server_status(&mut db_conn, &server_status(server_id, polled_at, &status)).await?;
+            track_match_status(&mut db_conn, server_id, &status).await?
+        }
+        Err(err @ RCONError::Conn(_))
+        | Err(err @ RCONError::Timeout)
+        | Err(err @ RCONError::Unreachable(_)) => {
+            debug!("server (id={}) is unreachable: {}", server_id, err);
+            set_server_unreachable(&mut db_conn, server_id, err.to_string().as_str(), polled_at)
+                .await?
+        }
+        // The server was deleted since the list of servers was fetched
+        Err(RCONError::NoSuchServer) => return Ok(()),
+        // The server is up, but Get5 did not reply as expected
+        Err(err) => {
+            set_server_status(
+                &mut db_conn,
+                &ServerStatus {
+                    server_id,
+                    reachable: true,
+                    error: Some(err.to_string()),
+                    polled_at,
+                    state: None,
+                    matchid: None,
+                    map_number: None,
+                    paused: None,
+                    plugin_version: None,
+                    team1_connected: None,
+                    team1_ready: None,
+                    team2_connected: None,
+                    team2_ready: None,
+                },
+            )
+            .await?
+        }
+    }
+
+    // The last successful poll is kept when the server is unreachable, so the stored status is
+    // published rather than the result of this poll
+    if let Some(status) = get_server_status(&mut db_conn, server_id).await? {
+        events.publish(Event::ServerStatus(status));
+    }
+
+    Ok(())
+}
+
+/// Follows the status and the series score of the match the server is playing, see
+/// `crate::status`
+async fn track_match_status(
+    db: &mut PgConnection,
+    server_id: Uuid,
+    status: &Get5Status,
+) -> anyhow::Result<()> {
+    let match_status = match status_of_get5_state(status.state(), status.paused()) {
+        Some(match_status) => match_status,
+        None => return Ok(()),
+    };
+    // No match is loaded, or the match was not loaded by us
+    let match_id = match status.matchid().map(Uuid::parse_str) {
+        Some(Ok(match_id)) => match_id,
+        _ => return Ok(()),
+    };
+
+    let assigned = get_match(db, match_id)
+        .await?
+        .is_some_and(|r#match| r#match.server_id == server_id);
+    if assigned {
+        observe_match_status(
+            db,
+            match_id,
+            match_status,
+            StatusTrigger::poll(status.state()),
+        )
+        .await?;
+        if let Some((team1, team2)) = status.teams() {
+            set_match_series_score(db, match_id, team1.series_score(), team2.series_score())
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn server_status(
+    server_id: Uuid,
+    polled_at: chrono::NaiveDateTime,
+    status: &Get5Status,
+) -> ServerStatus {
+    let teams = status.teams();
+
+    ServerStatus {
+        server_id,
+        reachable: true,
+        error: None,
+        polled_at,
+        state: Some(status.state()),
+        matchid: status.matchid().map(str::to_string),
+        map_number: status.map_number(),
+        paused: Some(status.paused()),
+        plugin_version: Some(status.plugin_version().to_string()),
+        team1_connected: teams.map(|(team1, _)| team1.connected_clients()),
+        team1_ready: teams.map(|(team1, _)| team1.ready()),
+        team2_connected: teams.map(|(_, team2)| team2.connected_clients()),
+        team2_ready: teams.map(|(_, team2)| team2.ready()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use sqlx::types::Uuid;
+
+    use crate::common::Get5State;
+    use crate::csgo::rcon::get5status::Get5Status;
+
+    use super::server_status;
+
+    #[test]
+    fn status_of_live_server() {
+        let status: Get5Status = serde_json::from_str(
+            r#"{"matchid":"example_match","loaded_config_file":"","plugin_version":"0.7.1","map_number":1,"team1":{"connected_clients":5,"current_map_score":3,"ready":true,"name":"EnvyUs","series_score":1,"side":"CT"},"team2":{"connected_clients":4,"current_map_score":2,"ready":false,"name":"Fnatic","series_score":0,"side":"T"},"paused":true,"maps":{"map0":"de_dust2","map1":"de_inferno"},"gamestate":7,"gamestate_string":"live"}"#,
+        )
+        .unwrap();
+        let polled_at = NaiveDate::from_ymd_opt(2020, 1, 2)
+            .unwrap()
+            .and_hms_opt(3, 4, 5)
+            .unwrap();
+
+        let status = server_status(Uuid::nil(), polled_at, &status);
+        assert!(status.reachable);
+        assert_eq!(status.state, Some(Get5State::Live));
+        assert_eq!(status.matchid.as_deref(), Some("example_match"));
+        assert_eq!(status.map_number, Some(1));
+        assert_eq!(status.paused, Some(true));
+        assert_eq!(status.plugin_version.as_deref(), Some("0.7.1"));
+        assert_eq!(status.team1_connected, Some(5));
+        assert_eq!(status.team1_ready, Some(true));
+        assert_eq!(status.team2_connected, Some(4));
+        assert_eq!(status.team2_ready, Some(false));
+    }
+
+    #[test]
+    fn status_of_idle_server() {
+        let status: Get5Status = serde_json::from_str(
+            r#"{"plugin_version":"0.7.1","gamestate":0,"paused":false,"gamestate_string":"none"}"#,
+        )
+        .unwrap();
+        let polled_at = NaiveDate::from_ymd_opt(2020, 1, 2)
+            .unwrap()
+            .and_hms_opt(3, 4, 5)
+            .unwrap();
+
+        let status = server_status(Uuid::nil(), polled_at, &status);
+        assert_eq!(status.state, Some(Get5State::Idle));
+        assert_eq!(status.matchid, None);
+        assert_eq!(status.team1_connected, None);
+    }
+}
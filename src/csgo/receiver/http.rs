@@ -0,0 +1,104 @@
+use regex::Regex;
+use sqlx::types::Uuid;
+use sqlx::{Acquire, Postgres};
+use tide::{Response, StatusCode};
+use tide_sqlx::SQLxRequestExt;
+
+use crate::database::get_server;
+use crate::web::token::{request_token, token_matches};
+use crate::web::State;
+
+lazy_static! {
+    /// Prefix of a log line send by `logaddress_add_http`
+    static ref HTTP_LOG_PREFIX: Regex = Regex::new(
+        r"^(?P<date>\d\d/\d\d/\d\d\d\d - \d\d:\d\d:\d\d)\.\d\d\d - "
+    )
+    .unwrap();
+}
+
+/// Rewrites a log line received over HTTP to the format used in log files
+///
+/// The HTTP log lines carries millisecond precision timestamps and no `L` marker, e.g.
+/// `01/02/2020 - 03:04:05.678 - Log file closed` becomes `L 01/02/2020 - 03:04:05: Log file closed`
+fn normalize_line(line: &str) -> String {
+    HTTP_LOG_PREFIX.replace(line, "L ${date}: ").into_owned()
+}
+
+/// Checks that the token given with the log lines is the log secret of the server, servers
+/// without a log secret can't post logs
+fn accepts_token(log_secret: Option<&str>, token: Option<&str>) -> bool {
+    match (log_secret, token) {
+        (Some(log_secret), Some(token)) => token_matches(token, log_secret),
+        _ => false,
+    }
+}
+
+/// Receives the log lines a CS:GO server posts after
+/// `logaddress_add_http "<url>/api/logs/<server id>?token=<log secret>"`
+///
+/// The log secret of the server may also be given as a bearer token.
+pub async fn handler_log_receiver(mut req: tide::Request<State>) -> tide::Result<Response> {
+    let server_id = match Uuid::parse_str(req.param("server_id")?) {
+        Ok(server_id) => server_id,
+        Err(_) => return Ok(Response::new(StatusCode::BadRequest)),
+    };
+
+    // Only accept logs for known servers, posted with their log secret
+    {
+        let token = request_token(&req)?;
+        let mut pool = req.sqlx_conn::<Postgres>().await;
+        let db_conn = pool.acquire().await?;
+
+        match get_server(db_conn, server_id).await {
+            Ok(Some(server)) => {
+                if !accepts_token(server.log_secret.as_deref(), token.as_deref()) {
+                    return Ok(Response::new(StatusCode::Unauthorized));
+                }
+            }
+            Ok(None) => return Ok(Response::new(StatusCode::NotFound)),
+            Err(err) => {
+                return Err(tide::Error::new(StatusCode::InternalServerError, err));
+            }
+        }
+    }
+
+    let body = req.body_string().await?;
+    let lines = body
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .map(normalize_line)
+        .collect::<Vec<String>>();
+
+    req.state().log_router.route(server_id, lines).await;
+
+    Ok(Response::new(StatusCode::Ok))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accepts_token, normalize_line};
+
+    #[test]
+    fn normalize_http_line() {
+        assert_eq!(
+            normalize_line(r#"01/02/2020 - 03:04:05.678 - World triggered "Round_Start""#),
+            r#"L 01/02/2020 - 03:04:05: World triggered "Round_Start""#
+        );
+    }
+
+    #[test]
+    fn normalize_file_line() {
+        let line = r#"L 01/02/2020 - 03:04:05: World triggered "Round_Start""#;
+        assert_eq!(normalize_line(line), line);
+    }
+
+    #[test]
+    fn log_secret_required() {
+        assert!(accepts_token(Some("12345"), Some("12345")));
+        assert!(!accepts_token(Some("12345"), Some("12346")));
+        assert!(!accepts_token(Some("12345"), None));
+        assert!(!accepts_token(None, Some("12345")));
+        assert!(!accepts_token(None, None));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_std::channel::{self, Receiver, RecvError, Sender, TrySendError};
+use async_std::task;
+use serde_json::Value as JsonValue;
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+
+use crate::csgo::ingest::MatchEventIngest;
+use crate::csgo::logs::{Error, LogEntry, LogEntryReader, LogProcessor};
+use crate::events::EventBus;
+
+pub mod http;
+pub mod udp;
+
+/// Number of received lines that may be waiting for the log processor of a single server
+const SERVER_LINE_QUEUE_SIZE: usize = 256;
+/// Number of log entries that may be waiting for a watcher, further entries are dropped
+const WATCHER_QUEUE_SIZE: usize = 256;
+
+/// Log entry parsed from the log stream of a game server
+#[derive(Debug)]
+pub struct ServerLogEntry {
+    /// Server that emitted the log line
+    pub server_id: Uuid,
+    pub entry: LogEntry,
+}
+
+pub type LogEntrySender = Sender<ServerLogEntry>;
+pub type LogEntryReceiver = Receiver<ServerLogEntry>;
+
+#[async_trait]
+impl LogEntryReader<RecvError> for Receiver<String> {
+    async fn read_log_line(&mut self) -> Result<String, RecvError> {
+        self.recv().await
+    }
+}
+
+/// Followers of the log entries of the game servers, see `LogRouter::watch`
+#[derive(Clone, Default)]
+struct LogWatchers(Arc<Mutex<HashMap<Uuid, Vec<Sender<JsonValue>>>>>);
+
+impl LogWatchers {
+    fn add(&self, server_id: Uuid) -> Receiver<JsonValue> {
+        let (sender, receiver) = channel::bounded(WATCHER_QUEUE_SIZE);
+        self.0
+            .lock()
+            .unwrap()
+            .entry(server_id)
+            .or_default()
+            .push(sender);
+
+        receiver
+    }
+
+    /// Passes the entry on to the watchers of the server, without waiting for slow watchers
+    fn notify(&self, server_id: Uuid, entry: &LogEntry) {
+        let mut watchers = self.0.lock().unwrap();
+        let senders = match watchers.get_mut(&server_id) {
+            Some(senders) => senders,
+            None => return,
+        };
+
+        let entry = match serde_json::to_value(entry) {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("failed to serialize log entry {:?}: {}", entry, err);
+                return;
+            }
+        };
+        senders.retain(|sender| match sender.try_send(entry.clone()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Closed(_)) => false,
+        });
+
+        if senders.is_empty() {
+            watchers.remove(&server_id);
+        }
+    }
+}
+
+/// Passes received log lines on to a log processor per game server
+///
+/// The log processor of a server lives as long as the server keeps sending logs, such that groups
+/// of lines spanning multiple requests or packets, like the cvar dump, are put together.
+#[derive(Clone)]
+pub struct LogRouter {
+    servers: Arc<Mutex<HashMap<Uuid, Sender<String>>>>,
+    sender: LogEntrySender,
+    watchers: LogWatchers,
+}
+
+impl LogRouter {
+    pub fn new(sender: LogEntrySender) -> Self {
+        LogRouter {
+            servers: Arc::new(Mutex::new(HashMap::new())),
+            sender,
+            watchers: LogWatchers::default(),
+        }
+    }
+
+    /// Follows the log entries of a server, serialized to JSON, from now on
+    ///
+    /// Entries are dropped while the receiver is full, such that a slow watcher doesn't hold up
+    /// the processing of the logs. Dropping the receiver stops watching.
+    pub fn watch(&self, server_id: Uuid) -> Receiver<JsonValue> {
+        self.watchers.add(server_id)
+    }
+
+    /// Returns the queue of the log processor for the server, starting the processor if necessary
+    fn server_queue(&self, server_id: Uuid) -> Sender<String> {
+        let mut servers = self.servers.lock().unwrap();
+
+        if let Some(queue) = servers.get(&server_id) {
+            if !queue.is_closed() {
+                return queue.clone();
+            }
+        }
+
+        let (queue, lines) = channel::bounded(SERVER_LINE_QUEUE_SIZE);
+        task::spawn(process_server_log(
+            server_id,
+            lines,
+            self.sender.clone(),
+            self.watchers.clone(),
+        ));
+        servers.insert(server_id, queue.clone());
+
+        queue
+    }
+
+    /// Queues log lines received from a game server for processing, in the order they were logged
+    pub async fn route<I>(&self, server_id: Uuid, lines: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let queue = self.server_queue(server_id);
+
+        for line in lines {
+            if queue.send(line).await.is_err() {
+                error!(
+                    "log processor has stopped, dropping log lines from server (id={})",
+                    server_id
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Parses the log lines of a single game server, and passes the resulting entries on to `sender`
+/// and the watchers of the server
+///
+/// Lines that can't be parsed are logged and skipped, such that a single unknown line doesn't
+/// cause the rest of the lines to be dropped.
+async fn process_server_log(
+    server_id: Uuid,
+    lines: Receiver<String>,
+    sender: LogEntrySender,
+    watchers: LogWatchers,
+) {
+    let mut processor = LogProcessor::new(lines);
+
+    loop {
+        match processor.read_entry().await {
+            Ok(entry) => {
+                watchers.notify(server_id, &entry);
+
+                if sender
+                    .send(ServerLogEntry { server_id, entry })
+                    .await
+                    .is_err()
+                {
+                    error!(
+                        "log entry queue is closed, dropping log entries from server (id={})",
+                        server_id
+                    );
+                    return;
+                }
+            }
+            // All routers has been dropped
+            Err(Error::ReaderError(RecvError)) => return,
+            Err(Error::Unknown(line)) => {
+                debug!("unknown log line from server (id={}): {}", server_id, line)
+            }
+            Err(err) => warn!(
+                "failed to parse log line from server (id={}): {:?}",
+                server_id, err
+            ),
+        }
+    }
+}
+
+/// Consumes the log entries received from all game servers, the changes to the matches are
+/// published to `events`
+pub async fn process_log_entries(
+    receiver: LogEntryReceiver,
+    db_pool: Pool<Postgres>,
+    events: EventBus,
+) {
+    let mut ingest = MatchEventIngest::new(db_pool, events);
+
+    while let Ok(ServerLogEntry { server_id, entry }) = receiver.recv().await {
+        trace!("server (id={}) logged {:?}", server_id, entry);
+
+        if let Err(err) = ingest.ingest(server_id, &entry).await {
+            error!(
+                "failed to record log entry from server (id={}): {}",
+                server_id, err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Uuid;
+
+    use crate::csgo::logs::{LogEntry, LogPrefix};
+
+    use super::LogWatchers;
+
+    fn round_start() -> LogEntry {
+        LogEntry::WorldTriggeredEvent {
+            prefix: LogPrefix {
+                month: 1,
+                day: 2,
+                year: 2020,
+                hour: 3,
+                minute: 4,
+                second: 5,
+            },
+            event: "Round_Start".to_string(),
+        }
+    }
+
+    #[test]
+    fn watchers_of_server() {
+        let watchers = LogWatchers::default();
+        let server_id = Uuid::new_v4();
+        let other_server_id = Uuid::new_v4();
+
+        let watcher = watchers.add(server_id);
+        let other_watcher = watchers.add(other_server_id);
+        watchers.notify(server_id, &round_start());
+
+        let entry = watcher.try_recv().unwrap();
+        assert_eq!(entry["WorldTriggeredEvent"]["event"], "Round_Start");
+        assert!(other_watcher.try_recv().is_err());
+
+        // Watchers that went away are forgotten
+        drop(watcher);
+        watchers.notify(server_id, &round_start());
+        assert!(!watchers.0.lock().unwrap().contains_key(&server_id));
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use async_std::net::{ToSocketAddrs, UdpSocket};
+use sqlx::types::Uuid;
+use sqlx::{Pool, Postgres};
+
+use crate::csgo::receiver::LogRouter;
+use crate::database::get_servers;
+
+/// Largest packet the Source engine sends
+const MAX_PACKET_SIZE: usize = 2048;
+
+/// Minimum time between reloading the server list, limits the load caused by unknown sources
+const SERVER_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of sources whose dropped packets are counted, the counts start over once there are more
+const MAX_DROPPED_SOURCES: usize = 1024;
+
+/// Log line decoded from a `logaddress_add` packet
+#[derive(Debug, PartialEq)]
+struct LogPacket {
+    /// Value of `sv_logsecret`, only present when the server has a log secret set
+    secret: Option<String>,
+    line: String,
+}
+
+/// Decodes a packet of the Source engine log protocol
+///
+/// Packets start with four `0xFF` bytes followed by `R` for a plain log line, or `S` when the
+/// server has `sv_logsecret` set. In the latter case the secret is put directly in front of the
+/// log line. The log line is terminated by a newline and a null byte.
+fn decode_packet(packet: &[u8]) -> Option<LogPacket> {
+    let body = packet.strip_prefix(&[0xFF, 0xFF, 0xFF, 0xFF])?;
+    let (kind, body) = body.split_first()?;
+    let body = String::from_utf8_lossy(body);
+    let body = body.trim_end_matches(['\0', '\n', '\r']);
+
+    match kind {
+        b'R' => Some(LogPacket {
+            secret: None,
+            line: body.to_string(),
+        }),
+        b'S' => {
+            let line_start = body.find("L ")?;
+            Some(LogPacket {
+                secret: Some(body[..line_start].to_string()),
+                line: body[line_start..].to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Game server the logs are received from
+#[derive(Debug, Clone, PartialEq)]
+struct KnownServer {
+    id: Uuid,
+    /// Value of `sv_logsecret` on the server, `None` if the server has no log secret
+    log_secret: Option<String>,
+}
+
+impl KnownServer {
+    /// Checks that the packet carries the log secret of the server, such that log lines can't be
+    /// injected by spoofing the address of the server
+    fn accepts(&self, packet: &LogPacket) -> bool {
+        self.log_secret == packet.secret
+    }
+}
+
+/// Lookup table from the address of a game server to the server
+struct ServerDirectory {
+    db_pool: Pool<Postgres>,
+    servers: HashMap<SocketAddr, KnownServer>,
+    refreshed: Option<Instant>,
+}
+
+impl ServerDirectory {
+    fn new(db_pool: Pool<Postgres>) -> Self {
+        ServerDirectory {
+            db_pool,
+            servers: HashMap::new(),
+            refreshed: None,
+        }
+    }
+
+    async fn refresh(&mut self) -> anyhow::Result<()> {
+        let mut db_conn = self.db_pool.acquire().await?;
+        let servers = get_servers(&mut db_conn).await?;
+
+        self.servers = servers
+            .into_iter()
+            .map(|server| {
+                (
+                    SocketAddr::new(server.host.ip(), server.port as u16),
+                    KnownServer {
+                        id: server.id,
+                        log_secret: server.log_secret,
+                    },
+                )
+            })
+            .collect();
+        self.refreshed = Some(Instant::now());
+
+        Ok(())
+    }
+
+    fn find(&self, addr: &SocketAddr) -> Option<&KnownServer> {
+        if let Some(server) = self.servers.get(addr) {
+            return Some(server);
+        }
+
+        // Logs might be send from another port than the one registered, accept them as long as
+        // only one server is registered at the address
+        let mut candidates = self
+            .servers
+            .iter()
+            .filter(|(server_addr, _)| server_addr.ip() == addr.ip());
+        match (candidates.next(), candidates.next()) {
+            (Some((_, server)), None) => Some(server),
+            _ => None,
+        }
+    }
+
+    /// Finds the server at the address, reloading the list of servers if necessary
+    async fn resolve(&mut self, addr: &SocketAddr) -> Option<KnownServer> {
+        if let Some(server) = self.find(addr) {
+            return Some(server.clone());
+        }
+
+        let stale = match self.refreshed {
+            None => true,
+            Some(refreshed) => refreshed.elapsed() >= SERVER_REFRESH_INTERVAL,
+        };
+        if stale {
+            if let Err(err) = self.refresh().await {
+                error!("failed to load the list of servers: {}", err);
+            }
+        }
+
+        self.find(addr).cloned()
+    }
+}
+
+/// Number of packets dropped per source address, for rate-limiting the warnings about them
+///
+/// At most `MAX_DROPPED_SOURCES` sources are counted, such that packets from spoofed addresses
+/// can't exhaust the memory.
+#[derive(Default)]
+struct DroppedPackets(HashMap<IpAddr, u64>);
+
+impl DroppedPackets {
+    /// Counts a packet dropped from the source, returns the number of packets dropped from it
+    fn count(&mut self, source: IpAddr) -> u64 {
+        if !self.0.contains_key(&source) && self.0.len() >= MAX_DROPPED_SOURCES {
+            self.0.clear();
+        }
+
+        let count = self.0.entry(source).or_default();
+        *count += 1;
+        *count
+    }
+}
+
+/// Receives the log lines CS:GO servers sends after `logaddress_add <ip>:<port>`
+///
+/// Lines from addresses that don't belong to a registered server are dropped, as are lines without
+/// the log secret of the server (see `sv_logsecret`).
+pub async fn udp_log_receiver<A: ToSocketAddrs>(
+    addr: A,
+    db_pool: Pool<Postgres>,
+    router: LogRouter,
+) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(addr).await?;
+    let mut servers = ServerDirectory::new(db_pool);
+    let mut dropped = DroppedPackets::default();
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+
+    info!("receiving logs on udp://{}", socket.local_addr()?);
+
+    loop {
+        let (len, source) = socket.recv_from(&mut buf).await?;
+
+        let packet = match decode_packet(&buf[..len]) {
+            Some(packet) => packet,
+            None => {
+                debug!("received a malformed log packet from {}", source);
+                continue;
+            }
+        };
+
+        match servers.resolve(&source).await {
+            Some(server) if server.accepts(&packet) => {
+                router.route(server.id, vec![packet.line]).await
+            }
+            Some(server) => {
+                let count = dropped.count(source.ip());
+                if count % 1000 == 1 {
+                    warn!(
+                        "dropped {} log line(s) from {} without the log secret of server (id={})",
+                        count, source, server.id
+                    );
+                }
+            }
+            None => {
+                let count = dropped.count(source.ip());
+                if count % 1000 == 1 {
+                    warn!(
+                        "dropped {} log line(s) from {}, which is not a registered server",
+                        count, source
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use sqlx::types::Uuid;
+
+    use super::{decode_packet, DroppedPackets, KnownServer, LogPacket, MAX_DROPPED_SOURCES};
+
+    #[test]
+    fn decode_plain_packet() {
+        let packet = b"\xFF\xFF\xFF\xFFRL 01/02/2020 - 03:04:05: Log file closed\n\0";
+        assert_eq!(
+            decode_packet(packet),
+            Some(LogPacket {
+                secret: None,
+                line: "L 01/02/2020 - 03:04:05: Log file closed".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_secret_packet() {
+        let packet = b"\xFF\xFF\xFF\xFFS12345L 01/02/2020 - 03:04:05: Log file closed\n\0";
+        assert_eq!(
+            decode_packet(packet),
+            Some(LogPacket {
+                secret: Some("12345".to_string()),
+                line: "L 01/02/2020 - 03:04:05: Log file closed".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_malformed_packet() {
+        assert_eq!(
+            decode_packet(b"L 01/02/2020 - 03:04:05: Log file closed"),
+            None
+        );
+        assert_eq!(decode_packet(b"\xFF\xFF\xFF\xFFX"), None);
+        assert_eq!(decode_packet(b"\xFF\xFF\xFF\xFFS12345"), None);
+    }
+
+    #[test]
+    fn log_secret() {
+        let packet = |secret: Option<&str>| LogPacket {
+            secret: secret.map(str::to_string),
+            line: "L 01/02/2020 - 03:04:05: Log file closed".to_string(),
+        };
+        let server = KnownServer {
+            id: Uuid::nil(),
+            log_secret: Some("12345".to_string()),
+        };
+
+        assert!(server.accepts(&packet(Some("12345"))));
+        assert!(!server.accepts(&packet(Some("54321"))));
+        assert!(!server.accepts(&packet(None)));
+
+        // Servers without a log secret send plain packets only
+        let server = KnownServer {
+            id: Uuid::nil(),
+            log_secret: None,
+        };
+        assert!(server.accepts(&packet(None)));
+        assert!(!server.accepts(&packet(Some("12345"))));
+    }
+
+    #[test]
+    fn dropped_packets_are_bounded() {
+        let mut dropped = DroppedPackets::default();
+        let source = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(dropped.count(source), 1);
+        assert_eq!(dropped.count(source), 2);
+
+        for n in 0..MAX_DROPPED_SOURCES as u32 {
+            dropped.count(IpAddr::V4(Ipv4Addr::from(n)));
+        }
+        assert!(dropped.0.len() <= MAX_DROPPED_SOURCES);
+        // The counts started over
+        assert_eq!(dropped.count(source), 1);
+    }
+}
@@ -0,0 +1,626 @@
+use crate::common::GameSide;
+use crate::csgo::logs::{LogEntry, Team, TeamAll};
+use crate::get5::events::{Get5EventKind, Get5Side, Get5Team};
+
+/// Default of `mp_maxrounds`
+const DEFAULT_MAX_ROUNDS: i32 = 30;
+/// Default of `mp_overtime_maxrounds`
+const DEFAULT_OVERTIME_MAX_ROUNDS: i32 = 6;
+
+/// Round played on a map
+#[derive(Debug, Clone, PartialEq)]
+pub struct Round {
+    /// Number of the round on the map, starting from 1
+    pub number: i32,
+    /// Half of the map the round was played in, starting from 1 and increased at every side swap
+    pub half: i32,
+    /// Overtime the round was played in, 0 for regulation time
+    pub overtime: i32,
+    pub winner: Team,
+    /// Why the round was won, e.g. `SFUI_Notice_Target_Bombed`
+    pub reason: String,
+    /// Score of the CT side after the round
+    pub ct_score: i32,
+    /// Score of the T side after the round
+    pub t_score: i32,
+    /// Score of Get5's team1 after the round, `None` until the side of the team is known
+    pub team1_score: Option<i32>,
+    /// Score of Get5's team2 after the round, `None` until the side of the team is known
+    pub team2_score: Option<i32>,
+}
+
+/// Change of the rounds of a map made by a log entry or a Get5 event
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundUpdate {
+    /// The round was won, it is the last round played
+    Finished(Round),
+    /// The scores of the last round were corrected after it was won
+    Corrected(Round),
+}
+
+impl RoundUpdate {
+    pub fn round(&self) -> &Round {
+        match self {
+            RoundUpdate::Finished(round) | RoundUpdate::Corrected(round) => round,
+        }
+    }
+
+    pub fn into_round(self) -> Round {
+        match self {
+            RoundUpdate::Finished(round) | RoundUpdate::Corrected(round) => round,
+        }
+    }
+}
+
+impl From<Team> for GameSide {
+    fn from(team: Team) -> Self {
+        match team {
+            Team::CT => GameSide::CT,
+            Team::TERRORIST => GameSide::T,
+        }
+    }
+}
+
+fn opposite(side: Team) -> Team {
+    match side {
+        Team::CT => Team::TERRORIST,
+        Team::TERRORIST => Team::CT,
+    }
+}
+
+fn get5_side(side: Get5Side) -> Option<Team> {
+    match side {
+        Get5Side::CT => Some(Team::CT),
+        Get5Side::T => Some(Team::TERRORIST),
+        Get5Side::None => None,
+    }
+}
+
+/// Puts together the rounds played on a map from the log of the game server
+///
+/// Rounds are numbered by the score after the round, such that tracking can start in the middle
+/// of a map, and rounds replayed after restoring a backup replaces the rounds they were restored
+/// from. Side swaps are noticed from the scores moving between the sides, from players switching
+/// sides and from Get5's `side_swap` event.
+pub struct RoundTracker {
+    /// Value of `mp_maxrounds`
+    max_rounds: i32,
+    /// Value of `mp_overtime_maxrounds`
+    overtime_max_rounds: i32,
+    rounds: Vec<Round>,
+    /// Number of the round being played, 0 before the first round started
+    current: i32,
+    half: i32,
+    /// Rounds are not tracked during the knife round
+    live: bool,
+    team1_side: Option<Team>,
+    /// Side of team1 after the next side swap, as announced by Get5
+    team1_side_after_swap: Option<Team>,
+    /// Sides players switched to since the last round ended
+    switched_to_ct: bool,
+    switched_to_t: bool,
+}
+
+impl Default for RoundTracker {
+    fn default() -> Self {
+        RoundTracker {
+            max_rounds: DEFAULT_MAX_ROUNDS,
+            overtime_max_rounds: DEFAULT_OVERTIME_MAX_ROUNDS,
+            rounds: vec![],
+            current: 0,
+            half: 1,
+            live: true,
+            team1_side: None,
+            team1_side_after_swap: None,
+            switched_to_ct: false,
+            switched_to_t: false,
+        }
+    }
+}
+
+impl RoundTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rounds played on the map, in order
+    pub fn rounds(&self) -> &[Round] {
+        &self.rounds
+    }
+
+    /// Number of the round being played, 0 before the first round started
+    pub fn current_round(&self) -> i32 {
+        self.current
+    }
+
+    /// Forgets the rounds played, but keeps the settings of the server
+    pub fn restart(&mut self) {
+        *self = RoundTracker {
+            max_rounds: self.max_rounds,
+            overtime_max_rounds: self.overtime_max_rounds,
+            ..Self::default()
+        };
+    }
+
+    /// Tracks a log entry, and returns the round if it was finished or corrected by the entry
+    pub fn process_entry(&mut self, entry: &LogEntry) -> Option<RoundUpdate> {
+        match entry {
+            LogEntry::WorldTriggeredEvent { event, .. } if event == "Round_Start" => {
+                self.current = self.rounds.last().map_or(0, |round| round.number) + 1;
+                None
+            }
+            LogEntry::TeamTriggeredEventScore {
+                team,
+                event,
+                ct_score,
+                t_score,
+                ..
+            } => {
+                if !self.live {
+                    return None;
+                }
+                self.finish_round(*team, event.clone(), *ct_score, *t_score)
+            }
+            LogEntry::TeamScored { team, score, .. } => self.correct_score(*team, *score),
+            LogEntry::SwitchedTeam { from, to, .. } => {
+                match (from, to) {
+                    (TeamAll::TERRORIST, TeamAll::CT) => self.switched_to_ct = true,
+                    (TeamAll::CT, TeamAll::TERRORIST) => self.switched_to_t = true,
+                    _ => {}
+                }
+                None
+            }
+            LogEntry::Cvar { key, value, .. } => {
+                self.set_cvar(key, value);
+                None
+            }
+            LogEntry::CvarDump { cvars, .. } => {
+                for (key, value) in cvars {
+                    self.set_cvar(key, value);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Tracks a Get5 event, and returns the last round if the event corrected its team scores
+    pub fn process_get5_event(&mut self, event: &Get5EventKind) -> Option<RoundUpdate> {
+        match event {
+            Get5EventKind::KnifeStart { .. } => {
+                self.live = false;
+                None
+            }
+            Get5EventKind::GoingLive { .. } => {
+                self.restart();
+                None
+            }
+            Get5EventKind::SideSwap { team1_side, .. } => {
+                self.team1_side_after_swap = get5_side(*team1_side);
+                None
+            }
+            Get5EventKind::RoundEnd {
+                winner,
+                winner_side,
+                ..
+            } => {
+                self.live = true;
+
+                let winner_side = get5_side(*winner_side)?;
+                let team1_side = match winner {
+                    Get5Team::Team1 => winner_side,
+                    Get5Team::Team2 => opposite(winner_side),
+                    _ => return None,
+                };
+                if self.team1_side == Some(team1_side) {
+                    return None;
+                }
+                self.team1_side = Some(team1_side);
+
+                // The event is logged after the round it describes
+                let team_scores = self.team_scores_for_last_round()?;
+                let round = self.rounds.last_mut()?;
+                if round.winner != winner_side {
+                    return None;
+                }
+                round.team1_score = Some(team_scores.0);
+                round.team2_score = Some(team_scores.1);
+                Some(RoundUpdate::Corrected(round.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn set_cvar(&mut self, key: &str, value: &str) {
+        let value = match value.parse::<i32>() {
+            Ok(value) if value > 0 => value,
+            _ => return,
+        };
+
+        match key {
+            "mp_maxrounds" => self.max_rounds = value,
+            "mp_overtime_maxrounds" => self.overtime_max_rounds = value,
+            _ => {}
+        }
+    }
+
+    /// Half a round is played in when the server follows the standard format
+    fn expected_half(&self, number: i32) -> i32 {
+        if number <= self.max_rounds {
+            if number <= self.max_rounds / 2 {
+                1
+            } else {
+                2
+            }
+        } else {
+            let overtime_round = number - self.max_rounds - 1;
+            let overtime = overtime_round / self.overtime_max_rounds;
+            let second_half =
+                overtime_round % self.overtime_max_rounds >= self.overtime_max_rounds / 2;
+            3 + overtime * 2 + second_half as i32
+        }
+    }
+
+    fn overtime(&self, number: i32) -> i32 {
+        if number <= self.max_rounds {
+            0
+        } else {
+            (number - self.max_rounds - 1) / self.overtime_max_rounds + 1
+        }
+    }
+
+    fn finish_round(
+        &mut self,
+        winner: Team,
+        reason: String,
+        ct_score: i32,
+        t_score: i32,
+    ) -> Option<RoundUpdate> {
+        let number = ct_score + t_score;
+        if number <= 0 {
+            return None;
+        }
+
+        // The round is played again, e.g. after restoring a backup
+        self.rounds.retain(|round| round.number < number);
+
+        let won_ct = (winner == Team::CT) as i32;
+        let won_t = (winner == Team::TERRORIST) as i32;
+        let swapped = match self.rounds.last() {
+            Some(previous) if previous.number == number - 1 => {
+                let kept_sides =
+                    ct_score == previous.ct_score + won_ct && t_score == previous.t_score + won_t;
+                let swapped_sides =
+                    ct_score == previous.t_score + won_ct && t_score == previous.ct_score + won_t;
+                let players_swapped = self.switched_to_ct && self.switched_to_t;
+                let get5_swapped = self.team1_side_after_swap.is_some();
+
+                // Both are possible when the sides have the same score
+                swapped_sides && (!kept_sides || players_swapped || get5_swapped)
+            }
+            Some(_) => false,
+            None => {
+                // Tracking started in the middle of the map
+                self.half = self.expected_half(number);
+                false
+            }
+        };
+
+        if swapped {
+            self.half += 1;
+            self.team1_side = self
+                .team1_side_after_swap
+                .or_else(|| self.team1_side.map(opposite));
+        }
+        self.team1_side_after_swap = None;
+        self.switched_to_ct = false;
+        self.switched_to_t = false;
+
+        let mut round = Round {
+            number,
+            half: self.half,
+            overtime: self.overtime(number),
+            winner,
+            reason,
+            ct_score,
+            t_score,
+            team1_score: None,
+            team2_score: None,
+        };
+        if let Some(team1_side) = self.team1_side {
+            let (team1_score, team2_score) = scores_for_side(&round, team1_side);
+            round.team1_score = Some(team1_score);
+            round.team2_score = Some(team2_score);
+        }
+
+        self.current = number;
+        self.rounds.push(round.clone());
+        Some(RoundUpdate::Finished(round))
+    }
+
+    /// Corrects the score of the last round, from the score reported at the end of the round
+    fn correct_score(&mut self, side: Team, score: i32) -> Option<RoundUpdate> {
+        let team1_side = self.team1_side;
+        let round = self.rounds.last_mut()?;
+
+        let side_score = match side {
+            Team::CT => &mut round.ct_score,
+            Team::TERRORIST => &mut round.t_score,
+        };
+        if *side_score == score {
+            return None;
+        }
+        *side_score = score;
+
+        if let Some(team1_side) = team1_side {
+            if team1_side == side {
+                round.team1_score = Some(score);
+            } else {
+                round.team2_score = Some(score);
+            }
+        }
+
+        Some(RoundUpdate::Corrected(round.clone()))
+    }
+
+    fn team_scores_for_last_round(&self) -> Option<(i32, i32)> {
+        let team1_side = self.team1_side?;
+        let round = self.rounds.last()?;
+
+        Some(scores_for_side(round, team1_side))
+    }
+}
+
+/// Score of team1 and team2 after the round, when team1 played on `team1_side`
+fn scores_for_side(round: &Round, team1_side: Team) -> (i32, i32) {
+    match team1_side {
+        Team::CT => (round.ct_score, round.t_score),
+        Team::TERRORIST => (round.t_score, round.ct_score),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::csgo::logs::{LogEntry, LogPrefix, Player, PlayerID, Team, TeamAll};
+    use crate::get5::events::{Get5EventKind, Get5Side, Get5Team};
+
+    use super::{RoundTracker, RoundUpdate};
+
+    fn prefix() -> LogPrefix {
+        LogPrefix {
+            month: 1,
+            day: 2,
+            year: 2020,
+            hour: 3,
+            minute: 4,
+            second: 5,
+        }
+    }
+
+    fn round_start() -> LogEntry {
+        LogEntry::WorldTriggeredEvent {
+            prefix: prefix(),
+            event: "Round_Start".to_string(),
+        }
+    }
+
+    fn round_won(team: Team, event: &str, ct_score: i32, t_score: i32) -> LogEntry {
+        LogEntry::TeamTriggeredEventScore {
+            prefix: prefix(),
+            team,
+            event: event.to_string(),
+            ct_score,
+            t_score,
+        }
+    }
+
+    fn switched_team(entity_index: i32, from: TeamAll, to: TeamAll) -> LogEntry {
+        LogEntry::SwitchedTeam {
+            prefix: prefix(),
+            player: Player {
+                nick: "alice".to_string(),
+                entity_index,
+                id: PlayerID::BOT,
+                team: TeamAll::UNASSIGNED,
+            },
+            from,
+            to,
+        }
+    }
+
+    fn play(tracker: &mut RoundTracker, winner: Team, ct_score: i32, t_score: i32) {
+        tracker.process_entry(&round_start());
+        let event = match winner {
+            Team::CT => "SFUI_Notice_CTs_Win",
+            Team::TERRORIST => "SFUI_Notice_Terrorists_Win",
+        };
+        let update = tracker.process_entry(&round_won(winner, event, ct_score, t_score));
+        assert!(matches!(update, Some(RoundUpdate::Finished(_))));
+    }
+
+    #[test]
+    fn rounds_in_order() {
+        let mut tracker = RoundTracker::new();
+        assert_eq!(tracker.current_round(), 0);
+
+        tracker.process_entry(&round_start());
+        assert_eq!(tracker.current_round(), 1);
+        let update = tracker
+            .process_entry(&round_won(
+                Team::TERRORIST,
+                "SFUI_Notice_Target_Bombed",
+                0,
+                1,
+            ))
+            .unwrap();
+        assert!(matches!(update, RoundUpdate::Finished(_)));
+        let round = update.round();
+        assert_eq!(round.number, 1);
+        assert_eq!(round.half, 1);
+        assert_eq!(round.overtime, 0);
+        assert_eq!(round.winner, Team::TERRORIST);
+        assert_eq!(round.reason, "SFUI_Notice_Target_Bombed");
+        assert_eq!((round.ct_score, round.t_score), (0, 1));
+        assert_eq!(round.team1_score, None);
+
+        play(&mut tracker, Team::CT, 1, 1);
+        assert_eq!(tracker.current_round(), 2);
+        assert_eq!(tracker.rounds().len(), 2);
+    }
+
+    #[test]
+    fn team_scores_from_get5() {
+        let mut tracker = RoundTracker::new();
+        play(&mut tracker, Team::TERRORIST, 0, 1);
+
+        let update = tracker
+            .process_get5_event(&Get5EventKind::RoundEnd {
+                map_name: "de_inferno".to_string(),
+                map_number: 0,
+                winner_side: Get5Side::T,
+                winner: Get5Team::Team2,
+                reason: 9,
+            })
+            .unwrap();
+        assert!(matches!(update, RoundUpdate::Corrected(_)));
+        let round = update.round();
+        assert_eq!(round.team1_score, Some(0));
+        assert_eq!(round.team2_score, Some(1));
+
+        play(&mut tracker, Team::CT, 1, 1);
+        let round = tracker.rounds().last().unwrap();
+        assert_eq!(round.team1_score, Some(1));
+        assert_eq!(round.team2_score, Some(1));
+    }
+
+    #[test]
+    fn score_corrected_after_the_round() {
+        let mut tracker = RoundTracker::new();
+        play(&mut tracker, Team::CT, 1, 0);
+
+        let update = tracker.process_entry(&LogEntry::TeamScored {
+            prefix: prefix(),
+            team: Team::CT,
+            score: 2,
+            player_count: 5,
+        });
+        let round = match update {
+            Some(RoundUpdate::Corrected(round)) => round,
+            update => panic!("expected a corrected round, got {:?}", update),
+        };
+        assert_eq!((round.number, round.ct_score), (1, 2));
+
+        // The score is already known
+        assert_eq!(
+            tracker.process_entry(&LogEntry::TeamScored {
+                prefix: prefix(),
+                team: Team::CT,
+                score: 2,
+                player_count: 5,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn side_swap_at_halftime() {
+        let mut tracker = RoundTracker::new();
+        tracker.process_entry(&LogEntry::CvarDump {
+            start: prefix(),
+            end: prefix(),
+            cvars: [("mp_maxrounds".to_string(), "4".to_string())]
+                .iter()
+                .cloned()
+                .collect::<HashMap<String, String>>(),
+        });
+
+        play(&mut tracker, Team::CT, 1, 0);
+        tracker.process_get5_event(&Get5EventKind::RoundEnd {
+            map_name: "de_inferno".to_string(),
+            map_number: 0,
+            winner_side: Get5Side::CT,
+            winner: Get5Team::Team1,
+            reason: 8,
+        });
+        play(&mut tracker, Team::CT, 2, 0);
+
+        // Team1 has 2 points on the T side after the swap
+        play(&mut tracker, Team::CT, 1, 2);
+        let round = tracker.rounds().last().unwrap();
+        assert_eq!(round.number, 3);
+        assert_eq!(round.half, 2);
+        assert_eq!(round.team1_score, Some(2));
+        assert_eq!(round.team2_score, Some(1));
+    }
+
+    #[test]
+    fn side_swap_with_equal_scores() {
+        let mut tracker = RoundTracker::new();
+        play(&mut tracker, Team::CT, 1, 0);
+        play(&mut tracker, Team::TERRORIST, 1, 1);
+
+        // The scores alone can't tell whether the sides were swapped
+        tracker.process_entry(&switched_team(1, TeamAll::CT, TeamAll::TERRORIST));
+        tracker.process_entry(&switched_team(2, TeamAll::TERRORIST, TeamAll::CT));
+        play(&mut tracker, Team::CT, 2, 1);
+        assert_eq!(tracker.rounds().last().unwrap().half, 2);
+
+        play(&mut tracker, Team::CT, 3, 1);
+        assert_eq!(tracker.rounds().last().unwrap().half, 2);
+    }
+
+    #[test]
+    fn overtime() {
+        let mut tracker = RoundTracker::new();
+
+        // Tracking starts in the first half of the first overtime
+        play(&mut tracker, Team::CT, 16, 15);
+        let round = tracker.rounds().last().unwrap();
+        assert_eq!(round.number, 31);
+        assert_eq!(round.half, 3);
+        assert_eq!(round.overtime, 1);
+
+        play(&mut tracker, Team::CT, 17, 15);
+        play(&mut tracker, Team::CT, 18, 15);
+        play(&mut tracker, Team::CT, 16, 18);
+        let round = tracker.rounds().last().unwrap();
+        assert_eq!(round.number, 34);
+        assert_eq!(round.half, 4);
+        assert_eq!(round.overtime, 1);
+    }
+
+    #[test]
+    fn replayed_rounds_replace_old_rounds() {
+        let mut tracker = RoundTracker::new();
+        play(&mut tracker, Team::CT, 1, 0);
+        play(&mut tracker, Team::CT, 2, 0);
+        play(&mut tracker, Team::CT, 3, 0);
+
+        // Backup of round 2 restored
+        play(&mut tracker, Team::TERRORIST, 1, 1);
+        assert_eq!(tracker.rounds().len(), 2);
+        assert_eq!(tracker.rounds()[1].winner, Team::TERRORIST);
+    }
+
+    #[test]
+    fn knife_round_is_not_tracked() {
+        let mut tracker = RoundTracker::new();
+        tracker.process_get5_event(&Get5EventKind::KnifeStart {
+            map_name: "de_inferno".to_string(),
+            map_number: 0,
+        });
+        tracker.process_entry(&round_start());
+        assert!(tracker
+            .process_entry(&round_won(Team::CT, "SFUI_Notice_CTs_Win", 1, 0))
+            .is_none());
+
+        tracker.process_get5_event(&Get5EventKind::GoingLive {
+            map_name: "de_inferno".to_string(),
+            map_number: 0,
+        });
+        play(&mut tracker, Team::CT, 1, 0);
+        assert_eq!(tracker.rounds().len(), 1);
+    }
+}
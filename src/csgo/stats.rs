@@ -0,0 +1,735 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::AddAssign;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::csgo::logs::{LogEntry, LogPrefix, Player, PlayerID, Team, TeamAll};
+use crate::csgo::rounds::Round;
+
+/// Time after a death within which killing the killer counts as a trade
+const TRADE_WINDOW_SECONDS: i64 = 5;
+
+/// Health of a player at the start of a round
+const FULL_HEALTH: i32 = 100;
+
+/// Statistics of a player, over any number of rounds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerStats {
+    pub rounds: i32,
+    pub kills: i32,
+    pub deaths: i32,
+    pub assists: i32,
+    pub flash_assists: i32,
+    pub headshot_kills: i32,
+    /// Health removed from enemies, damage in excess of the remaining health is not counted
+    pub damage: i32,
+    /// Rounds with a kill, assist, survival or trade
+    pub kast_rounds: i32,
+    /// First kill of the round
+    pub entry_kills: i32,
+    /// First death of the round
+    pub entry_deaths: i32,
+    /// Rounds by number of kills, index 0 are the rounds with one kill
+    pub kill_rounds: [i32; 5],
+    /// Rounds where the player was the last one alive on their team, facing at least one enemy
+    pub clutches_played: i32,
+    pub clutches_won: i32,
+}
+
+impl AddAssign<&PlayerStats> for PlayerStats {
+    fn add_assign(&mut self, other: &PlayerStats) {
+        self.rounds += other.rounds;
+        self.kills += other.kills;
+        self.deaths += other.deaths;
+        self.assists += other.assists;
+        self.flash_assists += other.flash_assists;
+        self.headshot_kills += other.headshot_kills;
+        self.damage += other.damage;
+        self.kast_rounds += other.kast_rounds;
+        self.entry_kills += other.entry_kills;
+        self.entry_deaths += other.entry_deaths;
+        for (rounds, other_rounds) in self.kill_rounds.iter_mut().zip(other.kill_rounds.iter()) {
+            *rounds += other_rounds;
+        }
+        self.clutches_played += other.clutches_played;
+        self.clutches_won += other.clutches_won;
+    }
+}
+
+impl PlayerStats {
+    /// Average damage per round
+    pub fn adr(&self) -> f64 {
+        ratio(self.damage, self.rounds)
+    }
+
+    /// Share of the kills that were headshots, between 0 and 1
+    pub fn headshot_percentage(&self) -> f64 {
+        ratio(self.headshot_kills, self.kills)
+    }
+
+    /// Share of the rounds with a kill, assist, survival or trade, between 0 and 1
+    pub fn kast(&self) -> f64 {
+        ratio(self.kast_rounds, self.rounds)
+    }
+
+    /// Rounds with more than one kill
+    pub fn multi_kill_rounds(&self) -> i32 {
+        self.kill_rounds[1..].iter().sum()
+    }
+
+    /// HLTV rating 1.0
+    ///
+    /// See https://www.hltv.org/news/10333/what-is-that-rating-thing-in-stats
+    pub fn rating(&self) -> f64 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+
+        let rounds = self.rounds as f64;
+        let kill_rating = self.kills as f64 / rounds / 0.679;
+        let survival_rating = (self.rounds - self.deaths) as f64 / rounds / 0.317;
+        let multi_kill_score: i32 = self
+            .kill_rounds
+            .iter()
+            .enumerate()
+            .map(|(i, rounds)| (i as i32 + 1).pow(2) * rounds)
+            .sum();
+        let multi_kill_rating = multi_kill_score as f64 / rounds / 1.277;
+
+        (kill_rating + 0.7 * survival_rating + multi_kill_rating) / 2.7
+    }
+}
+
+fn ratio(numerator: i32, denominator: i32) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Key identifying a player across the log, the Steam ID for players and the nickname for bots
+pub(super) fn player_key(player: &Player) -> Option<String> {
+    match &player.id {
+        PlayerID::STAMID(steamid) => Some(steamid.clone()),
+        PlayerID::BOT => Some(format!("BOT {}", player.nick)),
+        PlayerID::CONSOLE => None,
+    }
+}
+
+pub(super) fn player_side(player: &Player) -> Option<Team> {
+    match player.team {
+        TeamAll::CT => Some(Team::CT),
+        TeamAll::TERRORIST => Some(Team::TERRORIST),
+        _ => None,
+    }
+}
+
+/// What a player did in a single round
+#[derive(Debug, Default)]
+struct PlayerRound {
+    side: Option<Team>,
+    kills: i32,
+    headshot_kills: i32,
+    died: bool,
+    traded: bool,
+    assists: i32,
+    flash_assists: i32,
+    damage: i32,
+    entry_kill: bool,
+    entry_death: bool,
+    clutch: bool,
+}
+
+struct Death {
+    victim: String,
+    victim_side: Option<Team>,
+    killer: String,
+    time: Option<NaiveDateTime>,
+}
+
+/// State of the round being played
+#[derive(Default)]
+struct RoundState {
+    players: HashMap<String, PlayerRound>,
+    /// Players alive, by side
+    alive: HashMap<String, Team>,
+    /// Remaining health of the players that took damage
+    health: HashMap<String, i32>,
+    deaths: Vec<Death>,
+    /// Player in a clutch, by side
+    clutches: HashMap<Team, String>,
+}
+
+impl RoundState {
+    fn player(&mut self, key: &str, side: Option<Team>) -> &mut PlayerRound {
+        let player = self.players.entry(key.to_string()).or_default();
+        if side.is_some() {
+            player.side = side;
+        }
+        player
+    }
+
+    fn alive_on(&self, side: Team) -> impl Iterator<Item = &String> {
+        self.alive
+            .iter()
+            .filter(move |(_, player_side)| **player_side == side)
+            .map(|(key, _)| key)
+    }
+
+    fn died(&mut self, victim: &str, victim_side: Option<Team>) {
+        self.player(victim, victim_side).died = true;
+        self.alive.remove(victim);
+
+        // A clutch starts when a single player is left on a side, facing at least one enemy
+        for side in [Team::CT, Team::TERRORIST].iter() {
+            if self.clutches.contains_key(side) {
+                continue;
+            }
+
+            let enemy = match side {
+                Team::CT => Team::TERRORIST,
+                Team::TERRORIST => Team::CT,
+            };
+            let survivors = self.alive_on(*side).cloned().collect::<Vec<String>>();
+            let last_alive = match survivors.as_slice() {
+                [key] => key.clone(),
+                _ => continue,
+            };
+            if self.alive_on(enemy).next().is_some() {
+                self.clutches.insert(*side, last_alive);
+            }
+        }
+    }
+}
+
+/// Computes the statistics of the players on a map from the log of the game server
+///
+/// The statistics are kept per round, such that rounds replayed after restoring a backup replaces
+/// the rounds they were restored from. Rounds are delimited by the `Round_Start` entries and the
+/// rounds put together by `crate::csgo::rounds::RoundTracker`.
+#[derive(Default)]
+pub struct StatsTracker {
+    /// Side of the players in the game, by player key
+    roster: HashMap<String, Team>,
+    /// Latest nickname of the players, by player key
+    nicks: HashMap<String, String>,
+    round: RoundState,
+    /// Statistics of the finished rounds, by round number and player key
+    rounds: BTreeMap<i32, HashMap<String, PlayerStats>>,
+    /// Number of the round finished since the last `Round_Start`, if any
+    finished: Option<i32>,
+}
+
+impl StatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the rounds played, but keeps the players in the game
+    pub fn restart(&mut self) {
+        self.round = RoundState::default();
+        self.rounds.clear();
+        self.finished = None;
+    }
+
+    /// Latest nickname of the player
+    pub fn nick(&self, key: &str) -> Option<&str> {
+        self.nicks.get(key).map(String::as_str)
+    }
+
+    /// Statistics of every player over the finished rounds, by player key
+    ///
+    /// Bots are keyed by `BOT <nickname>`, players by their Steam ID.
+    pub fn totals(&self) -> HashMap<String, PlayerStats> {
+        let mut totals: HashMap<String, PlayerStats> = HashMap::new();
+        for players in self.rounds.values() {
+            for (key, stats) in players {
+                *totals.entry(key.clone()).or_default() += stats;
+            }
+        }
+
+        totals
+    }
+
+    fn see_player(&mut self, player: &Player) -> Option<(String, Option<Team>)> {
+        let key = player_key(player)?;
+        let side = player_side(player);
+
+        self.nicks.insert(key.clone(), player.nick.clone());
+        if let Some(side) = side {
+            self.roster.insert(key.clone(), side);
+        }
+
+        Some((key, side))
+    }
+
+    pub fn process_entry(&mut self, entry: &LogEntry) {
+        match entry {
+            LogEntry::WorldTriggeredEvent { event, .. } if event == "Round_Start" => {
+                self.finished = None;
+                self.round = RoundState {
+                    alive: self.roster.clone(),
+                    ..RoundState::default()
+                };
+                for (key, side) in self.roster.iter() {
+                    self.round.player(key, Some(*side));
+                }
+            }
+            LogEntry::SwitchedTeam { player, to, .. } => {
+                if let Some(key) = player_key(player) {
+                    match to {
+                        TeamAll::CT => self.roster.insert(key, Team::CT),
+                        TeamAll::TERRORIST => self.roster.insert(key, Team::TERRORIST),
+                        _ => self.roster.remove(&key),
+                    };
+                }
+            }
+            LogEntry::PlayerDisconnected { player, .. } => {
+                if let Some(key) = player_key(player) {
+                    self.roster.remove(&key);
+                }
+            }
+            LogEntry::PlayerKilledPlayer {
+                prefix,
+                offender,
+                victim,
+                kill_attributes,
+                ..
+            } => self.kill(prefix, offender, victim, kill_attributes.headshot),
+            LogEntry::KilledByBomb { player, .. } | LogEntry::CommittedSuicide { player, .. } => {
+                if let Some((key, side)) = self.see_player(player) {
+                    self.round.died(&key, side);
+                }
+            }
+            LogEntry::PlayerAttackedPlayer {
+                offender,
+                victim,
+                damage,
+                health,
+                ..
+            } => {
+                let offender = self.see_player(offender);
+                let victim = self.see_player(victim);
+                if let (Some((offender, offender_side)), Some((victim, victim_side))) =
+                    (offender, victim)
+                {
+                    let remaining = self
+                        .round
+                        .health
+                        .insert(victim, *health)
+                        .unwrap_or(FULL_HEALTH);
+                    if offender_side.is_some() && offender_side != victim_side {
+                        self.round.player(&offender, offender_side).damage +=
+                            (*damage).min(remaining).max(0);
+                    }
+                }
+            }
+            LogEntry::PlayerAssistedKillingPlayer { offender, .. } => {
+                if let Some((key, side)) = self.see_player(offender) {
+                    self.round.player(&key, side).assists += 1;
+                }
+            }
+            LogEntry::PlayerAssistedBlindingPlayer { offender, .. } => {
+                if let Some((key, side)) = self.see_player(offender) {
+                    self.round.player(&key, side).flash_assists += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn kill(&mut self, prefix: &LogPrefix, offender: &Player, victim: &Player, headshot: bool) {
+        let (offender, offender_side) = match self.see_player(offender) {
+            Some(offender) => offender,
+            None => return,
+        };
+        let (victim, victim_side) = match self.see_player(victim) {
+            Some(victim) => victim,
+            None => return,
+        };
+        let time = prefix.timestamp();
+
+        // Team kills are not credited
+        if offender_side != victim_side {
+            let entry = self.round.deaths.is_empty();
+
+            let killer = self.round.player(&offender, offender_side);
+            killer.kills += 1;
+            if headshot {
+                killer.headshot_kills += 1;
+            }
+            if entry {
+                killer.entry_kill = true;
+                self.round.player(&victim, victim_side).entry_death = true;
+            }
+
+            // The victim avenged a teammate of the killer
+            let traded = self
+                .round
+                .deaths
+                .iter()
+                .filter(|death| death.killer == victim && death.victim_side == offender_side)
+                .filter(|death| match (death.time, time) {
+                    (Some(death_time), Some(time)) => {
+                        time - death_time <= Duration::seconds(TRADE_WINDOW_SECONDS)
+                    }
+                    _ => false,
+                })
+                .map(|death| death.victim.clone())
+                .collect::<Vec<String>>();
+            for key in traded {
+                self.round.player(&key, None).traded = true;
+            }
+        }
+
+        self.round.deaths.push(Death {
+            victim: victim.clone(),
+            victim_side,
+            killer: offender,
+            time,
+        });
+        self.round.died(&victim, victim_side);
+    }
+
+    /// Stores the statistics of the round that just ended
+    ///
+    /// A round is finished once until the next round starts: the state of the round is cleared
+    /// when it is stored, so finishing it again would replace its statistics with an empty round.
+    pub fn finish_round(&mut self, round: &Round) {
+        if self.finished == Some(round.number) {
+            return;
+        }
+
+        // The round is played again, e.g. after restoring a backup
+        let _ = self.rounds.split_off(&round.number);
+
+        for (side, key) in self.round.clutches.iter() {
+            if let Some(player) = self.round.players.get_mut(key) {
+                player.clutch = true;
+                player.side = Some(*side);
+            }
+        }
+
+        let players = self
+            .round
+            .players
+            .iter()
+            .map(|(key, player)| {
+                let mut stats = PlayerStats {
+                    rounds: 1,
+                    kills: player.kills,
+                    deaths: player.died as i32,
+                    assists: player.assists,
+                    flash_assists: player.flash_assists,
+                    headshot_kills: player.headshot_kills,
+                    damage: player.damage,
+                    kast_rounds: (player.kills > 0
+                        || player.assists > 0
+                        || !player.died
+                        || player.traded) as i32,
+                    entry_kills: player.entry_kill as i32,
+                    entry_deaths: player.entry_death as i32,
+                    clutches_played: player.clutch as i32,
+                    clutches_won: (player.clutch && player.side == Some(round.winner)) as i32,
+                    ..PlayerStats::default()
+                };
+                if player.kills > 0 {
+                    stats.kill_rounds[player.kills.min(5) as usize - 1] = 1;
+                }
+
+                (key.clone(), stats)
+            })
+            .collect();
+
+        self.rounds.insert(round.number, players);
+        self.round = RoundState::default();
+        self.finished = Some(round.number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use crate::csgo::logs::{
+        HitGroup, KillAttributes, LogEntry, LogPrefix, Player, PlayerID, Team, TeamAll, Vector3,
+    };
+    use crate::csgo::rounds::Round;
+
+    use super::{PlayerStats, StatsTracker};
+
+    fn prefix(second: i32) -> LogPrefix {
+        LogPrefix {
+            month: 1,
+            day: 2,
+            year: 2020,
+            hour: 3,
+            minute: 4,
+            second,
+        }
+    }
+
+    fn player(id: u8, team: TeamAll) -> Player {
+        Player {
+            nick: format!("player{}", id),
+            entity_index: id as i32,
+            id: PlayerID::STAMID(format!("STEAM_1:0:{}", id)),
+            team,
+        }
+    }
+
+    fn origin() -> Vector3 {
+        Vector3 { x: 0, y: 0, z: 0 }
+    }
+
+    fn round_start() -> LogEntry {
+        LogEntry::WorldTriggeredEvent {
+            prefix: prefix(0),
+            event: "Round_Start".to_string(),
+        }
+    }
+
+    fn kill(second: i32, offender: Player, victim: Player, headshot: bool) -> LogEntry {
+        LogEntry::PlayerKilledPlayer {
+            prefix: prefix(second),
+            offender,
+            offender_location: origin(),
+            victim,
+            victim_location: origin(),
+            instrument: "ak47".to_string(),
+            kill_attributes: KillAttributes {
+                headshot,
+                penetrated: false,
+            },
+        }
+    }
+
+    fn attack(offender: Player, victim: Player, damage: i32, health: i32) -> LogEntry {
+        LogEntry::PlayerAttackedPlayer {
+            prefix: prefix(0),
+            offender,
+            offender_location: origin(),
+            victim,
+            victim_location: origin(),
+            instrument: "ak47".to_string(),
+            damage,
+            damage_armor: 0,
+            health,
+            armor: 0,
+            hitgroup: HitGroup::Head,
+        }
+    }
+
+    fn round(number: i32, winner: Team) -> Round {
+        Round {
+            number,
+            half: 1,
+            overtime: 0,
+            winner,
+            reason: "SFUI_Notice_CTs_Win".to_string(),
+            ct_score: number,
+            t_score: 0,
+            team1_score: None,
+            team2_score: None,
+        }
+    }
+
+    /// Two CTs (1, 2) against two Ts (3, 4)
+    fn tracker() -> StatsTracker {
+        let mut tracker = StatsTracker::new();
+        for id in 1..=4 {
+            let team = if id <= 2 {
+                TeamAll::CT
+            } else {
+                TeamAll::TERRORIST
+            };
+            tracker.process_entry(&LogEntry::SwitchedTeam {
+                prefix: prefix(0),
+                player: player(id, TeamAll::UNASSIGNED),
+                from: TeamAll::UNASSIGNED,
+                to: team,
+            });
+        }
+        tracker
+    }
+
+    fn stats(tracker: &StatsTracker, id: u8) -> PlayerStats {
+        tracker
+            .totals()
+            .remove(&format!("STEAM_1:0:{}", id))
+            .unwrap()
+    }
+
+    #[test]
+    fn kills_deaths_and_damage() {
+        let mut tracker = tracker();
+        tracker.process_entry(&round_start());
+        tracker.process_entry(&attack(
+            player(1, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            30,
+            70,
+        ));
+        // Only the remaining health counts as damage
+        tracker.process_entry(&attack(
+            player(1, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            120,
+            0,
+        ));
+        tracker.process_entry(&kill(
+            1,
+            player(1, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            true,
+        ));
+        tracker.process_entry(&LogEntry::PlayerAssistedKillingPlayer {
+            prefix: prefix(1),
+            offender: player(2, TeamAll::CT),
+            victim: player(3, TeamAll::TERRORIST),
+        });
+        tracker.process_entry(&kill(
+            2,
+            player(1, TeamAll::CT),
+            player(4, TeamAll::TERRORIST),
+            false,
+        ));
+        tracker.finish_round(&round(1, Team::CT));
+
+        let alice = stats(&tracker, 1);
+        assert_eq!(alice.rounds, 1);
+        assert_eq!(alice.kills, 2);
+        assert_eq!(alice.headshot_kills, 1);
+        assert_eq!(alice.damage, 100);
+        assert_eq!(alice.entry_kills, 1);
+        assert_eq!(alice.kill_rounds, [0, 1, 0, 0, 0]);
+        assert_eq!(alice.multi_kill_rounds(), 1);
+        assert_eq!(alice.kast_rounds, 1);
+        assert_approx_eq!(alice.headshot_percentage(), 0.5);
+
+        let bob = stats(&tracker, 2);
+        assert_eq!(bob.assists, 1);
+        assert_eq!(bob.kast_rounds, 1);
+
+        let carol = stats(&tracker, 3);
+        assert_eq!(carol.deaths, 1);
+        assert_eq!(carol.entry_deaths, 1);
+        assert_eq!(carol.kast_rounds, 0);
+    }
+
+    #[test]
+    fn trade_counts_for_kast() {
+        let mut tracker = tracker();
+        tracker.process_entry(&round_start());
+        tracker.process_entry(&kill(
+            10,
+            player(3, TeamAll::TERRORIST),
+            player(1, TeamAll::CT),
+            false,
+        ));
+        tracker.process_entry(&kill(
+            13,
+            player(2, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            false,
+        ));
+        tracker.finish_round(&round(1, Team::CT));
+
+        assert_eq!(stats(&tracker, 1).kast_rounds, 1);
+    }
+
+    #[test]
+    fn clutch() {
+        let mut tracker = tracker();
+        tracker.process_entry(&round_start());
+        tracker.process_entry(&kill(
+            1,
+            player(3, TeamAll::TERRORIST),
+            player(1, TeamAll::CT),
+            false,
+        ));
+        // Player 2 is alone against two enemies
+        tracker.process_entry(&kill(
+            20,
+            player(2, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            false,
+        ));
+        tracker.process_entry(&kill(
+            30,
+            player(2, TeamAll::CT),
+            player(4, TeamAll::TERRORIST),
+            false,
+        ));
+        tracker.finish_round(&round(1, Team::CT));
+
+        let clutcher = stats(&tracker, 2);
+        assert_eq!(clutcher.clutches_played, 1);
+        assert_eq!(clutcher.clutches_won, 1);
+        assert_eq!(stats(&tracker, 4).clutches_played, 1);
+        assert_eq!(stats(&tracker, 4).clutches_won, 0);
+    }
+
+    #[test]
+    fn replayed_rounds_replace_old_rounds() {
+        let mut tracker = tracker();
+        for number in 1..=3 {
+            tracker.process_entry(&round_start());
+            tracker.process_entry(&kill(
+                1,
+                player(1, TeamAll::CT),
+                player(3, TeamAll::TERRORIST),
+                false,
+            ));
+            tracker.finish_round(&round(number, Team::CT));
+        }
+        assert_eq!(stats(&tracker, 1).kills, 3);
+
+        // Backup of round 2 restored
+        tracker.process_entry(&round_start());
+        tracker.finish_round(&round(2, Team::CT));
+        let alice = stats(&tracker, 1);
+        assert_eq!(alice.rounds, 2);
+        assert_eq!(alice.kills, 1);
+    }
+
+    #[test]
+    fn rounds_are_finished_once() {
+        let mut tracker = tracker();
+        tracker.process_entry(&round_start());
+        tracker.process_entry(&attack(
+            player(1, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            100,
+            0,
+        ));
+        tracker.process_entry(&kill(
+            1,
+            player(1, TeamAll::CT),
+            player(3, TeamAll::TERRORIST),
+            false,
+        ));
+        tracker.finish_round(&round(1, Team::CT));
+        tracker.finish_round(&round(1, Team::CT));
+
+        let alice = stats(&tracker, 1);
+        assert_eq!(alice.rounds, 1);
+        assert_eq!(alice.kills, 1);
+        assert_eq!(alice.damage, 100);
+        assert_eq!(stats(&tracker, 3).deaths, 1);
+    }
+
+    #[test]
+    fn rating() {
+        let stats = PlayerStats {
+            rounds: 20,
+            kills: 20,
+            deaths: 14,
+            kill_rounds: [10, 3, 0, 1, 0],
+            ..PlayerStats::default()
+        };
+        assert_approx_eq!(stats.rating(), 1.342, 0.001);
+        assert_approx_eq!(PlayerStats::default().rating(), 0.0);
+    }
+}
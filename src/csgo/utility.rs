@@ -0,0 +1,398 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::AddAssign;
+
+use crate::csgo::logs::{LogEntry, Player};
+use crate::csgo::rounds::Round;
+use crate::csgo::stats::{player_key, player_side};
+
+/// Health of a player at the start of a round
+const FULL_HEALTH: i32 = 100;
+
+/// Use of grenades by a player, over any number of rounds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UtilityStats {
+    pub flashbangs_thrown: i32,
+    pub smokes_thrown: i32,
+    pub he_grenades_thrown: i32,
+    /// Molotovs and incendiary grenades
+    pub molotovs_thrown: i32,
+    pub decoys_thrown: i32,
+    pub enemies_flashed: i32,
+    /// Teammates blinded by the player, the player blinding themselves is not counted
+    pub teammates_flashed: i32,
+    /// Time enemies spent blinded by the player, in seconds
+    pub enemy_blind_time: f64,
+    /// Health removed from enemies by HE grenades
+    pub he_damage: i32,
+    /// Health removed from enemies by fires
+    pub molotov_damage: i32,
+}
+
+impl AddAssign<&UtilityStats> for UtilityStats {
+    fn add_assign(&mut self, other: &UtilityStats) {
+        self.flashbangs_thrown += other.flashbangs_thrown;
+        self.smokes_thrown += other.smokes_thrown;
+        self.he_grenades_thrown += other.he_grenades_thrown;
+        self.molotovs_thrown += other.molotovs_thrown;
+        self.decoys_thrown += other.decoys_thrown;
+        self.enemies_flashed += other.enemies_flashed;
+        self.teammates_flashed += other.teammates_flashed;
+        self.enemy_blind_time += other.enemy_blind_time;
+        self.he_damage += other.he_damage;
+        self.molotov_damage += other.molotov_damage;
+    }
+}
+
+impl UtilityStats {
+    pub fn grenades_thrown(&self) -> i32 {
+        self.flashbangs_thrown
+            + self.smokes_thrown
+            + self.he_grenades_thrown
+            + self.molotovs_thrown
+            + self.decoys_thrown
+    }
+
+    /// Average time an enemy flashed by the player stayed blind, in seconds
+    pub fn average_blind_time(&self) -> f64 {
+        if self.enemies_flashed == 0 {
+            0.0
+        } else {
+            self.enemy_blind_time / self.enemies_flashed as f64
+        }
+    }
+
+    pub fn grenades_per_round(&self, rounds: i32) -> f64 {
+        if rounds == 0 {
+            0.0
+        } else {
+            self.grenades_thrown() as f64 / rounds as f64
+        }
+    }
+}
+
+/// Computes the use of grenades by the players on a map from the log of the game server
+///
+/// Like `crate::csgo::stats::StatsTracker`, the statistics are kept per round such that rounds
+/// replayed after restoring a backup replace the rounds they were restored from.
+#[derive(Default)]
+pub struct UtilityTracker {
+    /// Statistics of the round being played, by player key
+    round: HashMap<String, UtilityStats>,
+    /// Remaining health of the players that took damage in the round being played
+    health: HashMap<String, i32>,
+    /// Statistics of the finished rounds, by round number and player key
+    rounds: BTreeMap<i32, HashMap<String, UtilityStats>>,
+}
+
+impl UtilityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the rounds played
+    pub fn restart(&mut self) {
+        self.round.clear();
+        self.health.clear();
+        self.rounds.clear();
+    }
+
+    /// Statistics of every player over the finished rounds, by player key
+    pub fn totals(&self) -> HashMap<String, UtilityStats> {
+        let mut totals: HashMap<String, UtilityStats> = HashMap::new();
+        for players in self.rounds.values() {
+            for (key, stats) in players {
+                *totals.entry(key.clone()).or_default() += stats;
+            }
+        }
+
+        totals
+    }
+
+    fn player(&mut self, player: &Player) -> Option<&mut UtilityStats> {
+        let key = player_key(player)?;
+        Some(self.round.entry(key).or_default())
+    }
+
+    pub fn process_entry(&mut self, entry: &LogEntry) {
+        match entry {
+            LogEntry::WorldTriggeredEvent { event, .. } if event == "Round_Start" => {
+                self.round.clear();
+                self.health.clear();
+            }
+            LogEntry::ThrewFlashbang { player, .. } => {
+                if let Some(stats) = self.player(player) {
+                    stats.flashbangs_thrown += 1;
+                }
+            }
+            LogEntry::PlayerThrewSmokegrenade { player, .. } => {
+                if let Some(stats) = self.player(player) {
+                    stats.smokes_thrown += 1;
+                }
+            }
+            LogEntry::PlayerThrewHEGrenade { player, .. } => {
+                if let Some(stats) = self.player(player) {
+                    stats.he_grenades_thrown += 1;
+                }
+            }
+            LogEntry::ThrewMolotov { player, .. } => {
+                if let Some(stats) = self.player(player) {
+                    stats.molotovs_thrown += 1;
+                }
+            }
+            LogEntry::ThrewDecoy { player, .. } => {
+                if let Some(stats) = self.player(player) {
+                    stats.decoys_thrown += 1;
+                }
+            }
+            LogEntry::BlindedPlayer {
+                offender,
+                victim,
+                duration,
+                ..
+            } => {
+                if player_key(offender) == player_key(victim) {
+                    return;
+                }
+                let enemy = player_side(offender) != player_side(victim);
+                if let Some(stats) = self.player(offender) {
+                    if enemy {
+                        stats.enemies_flashed += 1;
+                        stats.enemy_blind_time += duration.as_secs_f64();
+                    } else {
+                        stats.teammates_flashed += 1;
+                    }
+                }
+            }
+            LogEntry::PlayerAttackedPlayer {
+                offender,
+                victim,
+                instrument,
+                damage,
+                health,
+                ..
+            } => {
+                let victim_key = match player_key(victim) {
+                    Some(key) => key,
+                    None => return,
+                };
+                let remaining = self
+                    .health
+                    .insert(victim_key, *health)
+                    .unwrap_or(FULL_HEALTH);
+                let damage = (*damage).min(remaining).max(0);
+
+                let offender_side = player_side(offender);
+                if offender_side.is_none() || offender_side == player_side(victim) {
+                    return;
+                }
+                if let Some(stats) = self.player(offender) {
+                    match instrument.as_str() {
+                        "hegrenade" => stats.he_damage += damage,
+                        "inferno" => stats.molotov_damage += damage,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Stores the statistics of the round that just ended
+    pub fn finish_round(&mut self, round: &Round) {
+        // The round is played again, e.g. after restoring a backup
+        let _ = self.rounds.split_off(&round.number);
+
+        self.rounds
+            .insert(round.number, std::mem::take(&mut self.round));
+        self.health.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use assert_approx_eq::assert_approx_eq;
+
+    use crate::csgo::logs::{
+        HitGroup, LogEntry, LogPrefix, Player, PlayerID, Team, TeamAll, Vector3,
+    };
+    use crate::csgo::rounds::Round;
+
+    use super::{UtilityStats, UtilityTracker};
+
+    fn prefix() -> LogPrefix {
+        LogPrefix {
+            month: 1,
+            day: 2,
+            year: 2020,
+            hour: 3,
+            minute: 4,
+            second: 5,
+        }
+    }
+
+    fn player(id: u8, team: TeamAll) -> Player {
+        Player {
+            nick: format!("player{}", id),
+            entity_index: id as i32,
+            id: PlayerID::STAMID(format!("STEAM_1:0:{}", id)),
+            team,
+        }
+    }
+
+    fn origin() -> Vector3 {
+        Vector3 { x: 0, y: 0, z: 0 }
+    }
+
+    fn blind(offender: Player, victim: Player, millis: u64) -> LogEntry {
+        LogEntry::BlindedPlayer {
+            prefix: prefix(),
+            offender,
+            duration: Duration::from_millis(millis),
+            victim,
+            entindex: 123,
+        }
+    }
+
+    fn attack(
+        offender: Player,
+        victim: Player,
+        instrument: &str,
+        damage: i32,
+        health: i32,
+    ) -> LogEntry {
+        LogEntry::PlayerAttackedPlayer {
+            prefix: prefix(),
+            offender,
+            offender_location: origin(),
+            victim,
+            victim_location: origin(),
+            instrument: instrument.to_string(),
+            damage,
+            damage_armor: 0,
+            health,
+            armor: 0,
+            hitgroup: HitGroup::Generic,
+        }
+    }
+
+    fn round(number: i32) -> Round {
+        Round {
+            number,
+            half: 1,
+            overtime: 0,
+            winner: Team::CT,
+            reason: "SFUI_Notice_CTs_Win".to_string(),
+            ct_score: number,
+            t_score: 0,
+            team1_score: None,
+            team2_score: None,
+        }
+    }
+
+    fn stats(tracker: &UtilityTracker, id: u8) -> UtilityStats {
+        tracker
+            .totals()
+            .remove(&format!("STEAM_1:0:{}", id))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn flashes() {
+        let mut tracker = UtilityTracker::new();
+        let entries = [
+            LogEntry::ThrewFlashbang {
+                prefix: prefix(),
+                player: player(1, TeamAll::CT),
+                location: origin(),
+                entindex: 123,
+            },
+            blind(player(1, TeamAll::CT), player(2, TeamAll::TERRORIST), 3000),
+            blind(player(1, TeamAll::CT), player(3, TeamAll::TERRORIST), 1500),
+            blind(player(1, TeamAll::CT), player(4, TeamAll::CT), 2000),
+            blind(player(1, TeamAll::CT), player(1, TeamAll::CT), 2000),
+        ];
+        for entry in entries.iter() {
+            tracker.process_entry(entry);
+        }
+        tracker.finish_round(&round(1));
+
+        let flasher = stats(&tracker, 1);
+        assert_eq!(flasher.flashbangs_thrown, 1);
+        assert_eq!(flasher.enemies_flashed, 2);
+        assert_eq!(flasher.teammates_flashed, 1);
+        assert_approx_eq!(flasher.average_blind_time(), 2.25);
+        assert_approx_eq!(flasher.grenades_per_round(1), 1.0);
+    }
+
+    #[test]
+    fn grenade_damage() {
+        let mut tracker = UtilityTracker::new();
+        let entries = [
+            attack(
+                player(1, TeamAll::CT),
+                player(2, TeamAll::TERRORIST),
+                "hegrenade",
+                40,
+                60,
+            ),
+            attack(
+                player(1, TeamAll::CT),
+                player(2, TeamAll::TERRORIST),
+                "inferno",
+                8,
+                52,
+            ),
+            attack(
+                player(1, TeamAll::CT),
+                player(3, TeamAll::CT),
+                "hegrenade",
+                30,
+                70,
+            ),
+            attack(
+                player(1, TeamAll::CT),
+                player(2, TeamAll::TERRORIST),
+                "ak47",
+                27,
+                25,
+            ),
+            // Only the remaining health counts
+            attack(
+                player(1, TeamAll::CT),
+                player(2, TeamAll::TERRORIST),
+                "inferno",
+                40,
+                0,
+            ),
+        ];
+        for entry in entries.iter() {
+            tracker.process_entry(entry);
+        }
+        tracker.finish_round(&round(1));
+
+        let attacker = stats(&tracker, 1);
+        assert_eq!(attacker.he_damage, 40);
+        assert_eq!(attacker.molotov_damage, 33);
+    }
+
+    #[test]
+    fn replayed_rounds_replace_old_rounds() {
+        let mut tracker = UtilityTracker::new();
+        let smoke = LogEntry::PlayerThrewSmokegrenade {
+            prefix: prefix(),
+            player: player(1, TeamAll::CT),
+            location: origin(),
+        };
+
+        tracker.process_entry(&smoke);
+        tracker.finish_round(&round(1));
+        tracker.process_entry(&smoke);
+        tracker.finish_round(&round(2));
+        tracker.process_entry(&smoke);
+        tracker.finish_round(&round(2));
+
+        assert_eq!(stats(&tracker, 1).smokes_thrown, 2);
+    }
+}
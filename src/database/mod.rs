@@ -1,12 +1,22 @@
 use std::net::IpAddr;
+use std::str::FromStr;
 
+use chrono::NaiveDateTime;
+use serde_json::Value as JsonValue;
 use sqlx::migrate::Migrator;
+use sqlx::types::ipnetwork::IpNetwork;
 use sqlx::types::Uuid;
-use sqlx::PgConnection;
+use sqlx::{Acquire, PgConnection};
 use sqlx::{Pool, Postgres};
 
-use crate::common::SideType;
-use crate::database::models::{CountryCode, MapList, Match, Player, Server, Spectator, Team};
+use crate::common::{
+    BuyType, CombatKind, GameSide, Get5State, MatchActionKind, MatchOrder, MatchStatus,
+    PlayerOrder, ServerOrder, SideType, StatusSource, TeamOrder,
+};
+use crate::database::models::{
+    CountryCode, Cvar, Match, MatchAction, MatchCombat, MatchEvent, MatchRound, MatchRoundEconomy,
+    MatchStatusChange, NewMatch, Player, PlayerMapStats, Server, ServerStatus, Spectator, Team,
+};
 
 pub mod models;
 
@@ -20,37 +30,118 @@ pub async fn run_migrations(db_pool: &Pool<Postgres>) -> anyhow::Result<()> {
 
 // Match
 
-pub fn set_map_list_for_match(db: &mut PgConnection, match_id: Uuid, maps: Vec<String>) {
-    todo!()
+/// Replaces the maps to be played or voted on in a match, `None` if there is no such match
+pub async fn set_map_list_for_match(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    maps: &[String],
+) -> Result<Option<Vec<String>>, Error> {
+    let mut tx = db.begin().await?;
+
+    // Lock the match, such that concurrent updates of the map list are applied one by one
+    let exists = sqlx::query!("SELECT id FROM matches WHERE id = $1 FOR UPDATE", match_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some();
+    if !exists {
+        return Ok(None);
+    }
+
+    insert_map_list(&mut tx, match_id, maps).await?;
+    tx.commit().await?;
+
+    Ok(Some(maps.to_vec()))
 }
 
-pub fn get_match_map_list(
+/// Replaces the map list of a match, must be called in a transaction
+async fn insert_map_list(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    maps: &[String],
+) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM maplist WHERE match_id = $1", match_id)
+        .execute(&mut *db)
+        .await?;
+
+    for (order, map) in maps.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO maplist (match_id, \"order\", map) VALUES ($1, $2, $3)",
+            match_id,
+            order as i32,
+            map
+        )
+        .execute(&mut *db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Maps to be played or voted on in a match in order, `None` if there is no such match
+pub async fn get_match_map_list(
     db: &mut PgConnection,
     match_id: Uuid,
 ) -> Result<Option<Vec<String>>, Error> {
-    todo!()
+    let mut tx = db.begin().await?;
+
+    let exists = sqlx::query!("SELECT id FROM matches WHERE id = $1", match_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some();
+    if !exists {
+        return Ok(None);
+    }
+
+    let maps = sqlx::query!(
+        "SELECT map FROM maplist WHERE match_id = $1 ORDER BY \"order\"",
+        match_id
+    )
+    .fetch_all(&mut tx)
+    .await?
+    .into_iter()
+    .map(|row| row.map)
+    .collect();
+    tx.commit().await?;
+
+    Ok(Some(maps))
 }
 
-pub fn create_match(
+/// Creates a match with its map list, see `NewMatch`
+pub async fn create_match(
     db: &mut PgConnection,
-    server_id: Uuid,
-    team1_id: Uuid,
-    team2_id: Uuid,
-    team1_score: Option<i32>,
-    team2_score: Option<i32>,
-    num_maps: i32,
-    skip_veto: bool,
-    veto_first: SideType,
-    players_per_team: i32,
-    min_player_to_ready: i32,
-) -> Result<(), Error> {
-    todo!()
+    new_match: &NewMatch,
+    maps: &[String],
+) -> Result<Match, Error> {
+    let mut tx = db.begin().await?;
+
+    let created = sqlx::query_as!(
+        Match,
+        "INSERT INTO matches (server_id, team1_id, team2_id, num_maps, skip_veto, veto_first, players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score",
+        new_match.server_id,
+        new_match.team1_id,
+        new_match.team2_id,
+        new_match.num_maps,
+        new_match.skip_veto,
+        new_match.veto_first as SideType,
+        new_match.players_per_team,
+        new_match.min_player_to_ready,
+        new_match.match_title,
+        new_match.favored_percentage_team1,
+        new_match.favored_percentage_text
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    insert_map_list(&mut tx, created.id, maps).await?;
+    tx.commit().await?;
+
+    Ok(created)
 }
 
 pub async fn get_match(db: &mut PgConnection, match_id: Uuid) -> Result<Option<Match>, Error> {
     let query: sqlx::Result<Match> = sqlx::query_as!(
         Match,
-        "SELECT id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready FROM matches WHERE id = $1",
+        "SELECT id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score FROM matches WHERE id = $1",
         match_id
     )
     .fetch_one(db)
@@ -65,65 +156,778 @@ pub async fn get_match(db: &mut PgConnection, match_id: Uuid) -> Result<Option<M
     }
 }
 
-pub fn update_match(
+pub async fn set_match_score(
     db: &mut PgConnection,
     match_id: Uuid,
-    server_id: Uuid,
-    team1_id: Uuid,
-    team2_id: Uuid,
     team1_score: Option<i32>,
     team2_score: Option<i32>,
-    num_maps: i32,
-    skip_veto: bool,
-    veto_first: SideType,
-    players_per_team: i32,
-    min_player_to_ready: i32,
 ) -> Result<(), Error> {
-    todo!()
+    sqlx::query!(
+        "UPDATE matches SET team1_score = $2, team2_score = $3 WHERE id = $1",
+        match_id,
+        team1_score,
+        team2_score
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
 }
 
-pub fn delete_match(db: &mut PgConnection, match_id: Uuid) -> Result<(), Error> {
-    todo!()
+/// Sets the number of maps won by each team in the series
+pub async fn set_match_series_score(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    team1_series_score: i32,
+    team2_series_score: i32,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE matches SET team1_series_score = $2, team2_series_score = $3 WHERE id = $1",
+        match_id,
+        team1_series_score,
+        team2_series_score
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
 }
 
-pub fn add_spectator_to_match(
+/// Assigns the match to the server it will be played on
+pub async fn set_match_server(
     db: &mut PgConnection,
-    spectator_id: Uuid,
     match_id: Uuid,
+    server_id: Uuid,
 ) -> Result<(), Error> {
-    todo!()
+    sqlx::query!(
+        "UPDATE matches SET server_id = $2 WHERE id = $1",
+        match_id,
+        server_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
 }
 
-pub fn remove_spectator_from_match(
+/// Updates every field of a match, `None` if there is no such match
+pub async fn update_match(db: &mut PgConnection, r#match: &Match) -> Result<Option<Match>, Error> {
+    let updated = sqlx::query_as!(
+        Match,
+        "UPDATE matches SET server_id = $2, team1_id = $3, team2_id = $4, team1_score = $5, team2_score = $6, num_maps = $7, skip_veto = $8, veto_first = $9, players_per_team = $10, min_player_to_ready = $11, match_title = $12, favored_percentage_team1 = $13, favored_percentage_text = $14 WHERE id = $1 RETURNING id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score",
+        r#match.id,
+        r#match.server_id,
+        r#match.team1_id,
+        r#match.team2_id,
+        r#match.team1_score,
+        r#match.team2_score,
+        r#match.num_maps,
+        r#match.skip_veto,
+        r#match.veto_first as SideType,
+        r#match.players_per_team,
+        r#match.min_player_to_ready,
+        r#match.match_title,
+        r#match.favored_percentage_team1,
+        r#match.favored_percentage_text
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(updated)
+}
+
+/// Deletes a match with everything recorded about it, returns the deleted match
+pub async fn delete_match(db: &mut PgConnection, match_id: Uuid) -> Result<Option<Match>, Error> {
+    let deleted = sqlx::query_as!(
+        Match,
+        "DELETE FROM matches WHERE id = $1 RETURNING id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score",
+        match_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(deleted)
+}
+
+/// Filters of `list_matches`, a match must pass every filter that is set
+#[derive(Debug, Clone, Default)]
+pub struct MatchFilter {
+    /// Team playing the match, as team1 or team2
+    pub team_id: Option<Uuid>,
+    pub server_id: Option<Uuid>,
+    /// Earliest creation time, inclusive
+    pub created_after: Option<NaiveDateTime>,
+    /// Latest creation time, exclusive
+    pub created_before: Option<NaiveDateTime>,
+    /// Part of the title of the match or of the name of a team, case-insensitive
+    pub search: Option<String>,
+    pub status: Option<MatchStatus>,
+}
+
+/// Number of matches passing the filter
+pub async fn count_matches(db: &mut PgConnection, filter: &MatchFilter) -> Result<i64, Error> {
+    let search = filter.search.as_deref().map(contains_pattern);
+    let row = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM matches WHERE ($1::uuid IS NULL OR team1_id = $1 OR team2_id = $1) AND ($2::uuid IS NULL OR server_id = $2) AND ($3::timestamp IS NULL OR created_at >= $3) AND ($4::timestamp IS NULL OR created_at < $4) AND ($5::varchar IS NULL OR match_title ILIKE $5 OR EXISTS (SELECT 1 FROM teams WHERE teams.id IN (matches.team1_id, matches.team2_id) AND teams.name ILIKE $5)) AND ($6::match_status IS NULL OR status = $6)",
+        filter.team_id,
+        filter.server_id,
+        filter.created_after,
+        filter.created_before,
+        search,
+        filter.status as Option<MatchStatus>
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.count)
+}
+
+/// Matches passing the filter between the cursors of the keyset, ties are broken by id
+pub async fn list_matches(
+    db: &mut PgConnection,
+    filter: &MatchFilter,
+    order: MatchOrder,
+    descending: bool,
+    keyset: &Keyset,
+) -> Result<Vec<Match>, Error> {
+    let search = filter.search.as_deref().map(contains_pattern);
+    let matches = match order {
+        MatchOrder::CreatedAt => {
+            let (after, after_id) = keyset.after_key::<NaiveDateTime>()?;
+            let (before, before_id) = keyset.before_key::<NaiveDateTime>()?;
+            sqlx::query_as!(
+                Match,
+                "SELECT id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score FROM matches WHERE ($1::uuid IS NULL OR team1_id = $1 OR team2_id = $1) AND ($2::uuid IS NULL OR server_id = $2) AND ($3::timestamp IS NULL OR created_at >= $3) AND ($4::timestamp IS NULL OR created_at < $4) AND ($5::varchar IS NULL OR match_title ILIKE $5 OR EXISTS (SELECT 1 FROM teams WHERE teams.id IN (matches.team1_id, matches.team2_id) AND teams.name ILIKE $5)) AND ($6::match_status IS NULL OR status = $6) AND ($7::timestamp IS NULL OR CASE WHEN $11 THEN (created_at, id) < ($7, $8) ELSE (created_at, id) > ($7, $8) END) AND ($9::timestamp IS NULL OR CASE WHEN $11 THEN (created_at, id) > ($9, $10) ELSE (created_at, id) < ($9, $10) END) ORDER BY CASE WHEN $12 THEN created_at END DESC, CASE WHEN $12 THEN id END DESC, created_at, id LIMIT $13",
+                filter.team_id,
+                filter.server_id,
+                filter.created_after,
+                filter.created_before,
+                search,
+                filter.status as Option<MatchStatus>,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+        MatchOrder::Title => {
+            let (after, after_id) = keyset.after_key::<String>()?;
+            let (before, before_id) = keyset.before_key::<String>()?;
+            sqlx::query_as!(
+                Match,
+                "SELECT id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score FROM matches WHERE ($1::uuid IS NULL OR team1_id = $1 OR team2_id = $1) AND ($2::uuid IS NULL OR server_id = $2) AND ($3::timestamp IS NULL OR created_at >= $3) AND ($4::timestamp IS NULL OR created_at < $4) AND ($5::varchar IS NULL OR match_title ILIKE $5 OR EXISTS (SELECT 1 FROM teams WHERE teams.id IN (matches.team1_id, matches.team2_id) AND teams.name ILIKE $5)) AND ($6::match_status IS NULL OR status = $6) AND ($7::varchar IS NULL OR CASE WHEN $11 THEN (COALESCE(match_title, ''), id) < ($7, $8) ELSE (COALESCE(match_title, ''), id) > ($7, $8) END) AND ($9::varchar IS NULL OR CASE WHEN $11 THEN (COALESCE(match_title, ''), id) > ($9, $10) ELSE (COALESCE(match_title, ''), id) < ($9, $10) END) ORDER BY CASE WHEN $12 THEN COALESCE(match_title, '') END DESC, CASE WHEN $12 THEN id END DESC, COALESCE(match_title, ''), id LIMIT $13",
+                filter.team_id,
+                filter.server_id,
+                filter.created_after,
+                filter.created_before,
+                search,
+                filter.status as Option<MatchStatus>,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+    };
+
+    Ok(keyset.ordered(matches))
+}
+
+/// Cursor of a match in the lists of matches sorted by `order`
+pub fn match_cursor(r#match: &Match, order: MatchOrder) -> Cursor {
+    let key = match order {
+        MatchOrder::CreatedAt => time_key(&r#match.created_at),
+        MatchOrder::Title => r#match.match_title.clone().unwrap_or_default(),
+    };
+
+    Cursor {
+        key,
+        id: r#match.id,
+    }
+}
+
+/// Adds a spectator to a match, returns false if the spectator was already added
+pub async fn add_spectator_to_match(
     db: &mut PgConnection,
     spectator_id: Uuid,
     match_id: Uuid,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "INSERT INTO match_spectator (match_id, spectator_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        match_id,
+        spectator_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Removes a spectator from a match, returns false if the spectator was not added
+pub async fn remove_spectator_from_match(
+    db: &mut PgConnection,
+    spectator_id: Uuid,
+    match_id: Uuid,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "DELETE FROM match_spectator WHERE match_id = $1 AND spectator_id = $2",
+        match_id,
+        spectator_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Cvars
+
+pub async fn get_match_cvars(db: &mut PgConnection, match_id: Uuid) -> Result<Vec<Cvar>, Error> {
+    let cvars = sqlx::query_as!(
+        Cvar,
+        "SELECT name, value FROM match_cvars WHERE match_id = $1 ORDER BY name",
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(cvars)
+}
+
+/// Sets cvars of a match, replacing the values of those already set, and returns every cvar of
+/// the match
+pub async fn set_match_cvars(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    cvars: &[Cvar],
+) -> Result<Vec<Cvar>, Error> {
+    let mut tx = db.begin().await?;
+
+    for cvar in cvars {
+        sqlx::query!(
+            "INSERT INTO match_cvars (match_id, name, value) VALUES ($1, $2, $3) ON CONFLICT (match_id, name) DO UPDATE SET value = EXCLUDED.value",
+            match_id,
+            cvar.name,
+            cvar.value
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    let cvars = get_match_cvars(&mut tx, match_id).await?;
+    tx.commit().await?;
+
+    Ok(cvars)
+}
+
+/// Removes cvars of a match, and returns the remaining cvars of the match
+pub async fn remove_match_cvars(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    names: &[String],
+) -> Result<Vec<Cvar>, Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM match_cvars WHERE match_id = $1 AND name = ANY($2)",
+        match_id,
+        names
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let cvars = get_match_cvars(&mut tx, match_id).await?;
+    tx.commit().await?;
+
+    Ok(cvars)
+}
+
+pub async fn get_server_cvars(db: &mut PgConnection, server_id: Uuid) -> Result<Vec<Cvar>, Error> {
+    let cvars = sqlx::query_as!(
+        Cvar,
+        "SELECT name, value FROM server_cvars WHERE server_id = $1 ORDER BY name",
+        server_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(cvars)
+}
+
+/// Sets cvars of a server, replacing the values of those already set, and returns every cvar of
+/// the server
+pub async fn set_server_cvars(
+    db: &mut PgConnection,
+    server_id: Uuid,
+    cvars: &[Cvar],
+) -> Result<Vec<Cvar>, Error> {
+    let mut tx = db.begin().await?;
+
+    for cvar in cvars {
+        sqlx::query!(
+            "INSERT INTO server_cvars (server_id, name, value) VALUES ($1, $2, $3) ON CONFLICT (server_id, name) DO UPDATE SET value = EXCLUDED.value",
+            server_id,
+            cvar.name,
+            cvar.value
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    let cvars = get_server_cvars(&mut tx, server_id).await?;
+    tx.commit().await?;
+
+    Ok(cvars)
+}
+
+/// Removes cvars of a server, and returns the remaining cvars of the server
+pub async fn remove_server_cvars(
+    db: &mut PgConnection,
+    server_id: Uuid,
+    names: &[String],
+) -> Result<Vec<Cvar>, Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM server_cvars WHERE server_id = $1 AND name = ANY($2)",
+        server_id,
+        names
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let cvars = get_server_cvars(&mut tx, server_id).await?;
+    tx.commit().await?;
+
+    Ok(cvars)
+}
+
+// Match events
+
+pub async fn add_match_event(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    map_number: i32,
+    round: i32,
+    timestamp: NaiveDateTime,
+    event_type: &str,
+    payload: JsonValue,
 ) -> Result<(), Error> {
-    todo!()
+    sqlx::query!(
+        "INSERT INTO match_events (match_id, map_number, round, timestamp, event_type, payload) VALUES ($1, $2, $3, $4, $5, $6)",
+        match_id,
+        map_number,
+        round,
+        timestamp,
+        event_type,
+        payload
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_match_events(
+    db: &mut PgConnection,
+    match_id: Uuid,
+) -> Result<Vec<MatchEvent>, Error> {
+    let events = sqlx::query_as!(
+        MatchEvent,
+        "SELECT * FROM match_events WHERE match_id = $1 ORDER BY id",
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(events)
+}
+
+// Match actions
+
+/// Records a command sent to a game server to control a match, the id is assigned by the database
+pub async fn add_match_action(db: &mut PgConnection, action: &MatchAction) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO match_actions (match_id, server_id, action, argument, triggered_by, triggered_at, error) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        action.match_id,
+        action.server_id,
+        action.action as MatchActionKind,
+        action.argument,
+        action.triggered_by,
+        action.triggered_at,
+        action.error
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_match_actions(
+    db: &mut PgConnection,
+    match_id: Uuid,
+) -> Result<Vec<MatchAction>, Error> {
+    let actions = sqlx::query_as!(
+        MatchAction,
+        "SELECT id, match_id, server_id, action AS \"action: MatchActionKind\", argument, triggered_by, triggered_at, error FROM match_actions WHERE match_id = $1 ORDER BY id",
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(actions)
+}
+
+// Match status
+
+/// Current status of a match, `None` if there is no such match
+///
+/// The match is locked until the transaction ends, such that concurrent changes of its status are
+/// applied one by one. Must be called in a transaction.
+pub async fn lock_match_status(
+    db: &mut PgConnection,
+    match_id: Uuid,
+) -> Result<Option<MatchStatus>, Error> {
+    let row = sqlx::query!(
+        "SELECT status AS \"status: MatchStatus\" FROM matches WHERE id = $1 FOR UPDATE",
+        match_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| row.status))
+}
+
+/// Sets the status of a match and records the change, the id is assigned by the database
+///
+/// The transition is not checked, see `crate::status::change_match_status`. Must be called in a
+/// transaction.
+pub async fn set_match_status(
+    db: &mut PgConnection,
+    change: &MatchStatusChange,
+) -> Result<MatchStatusChange, Error> {
+    sqlx::query!(
+        "UPDATE matches SET status = $2 WHERE id = $1",
+        change.match_id,
+        change.to_status as MatchStatus
+    )
+    .execute(&mut *db)
+    .await?;
+
+    let change = sqlx::query_as!(
+        MatchStatusChange,
+        "INSERT INTO match_status_history (match_id, from_status, to_status, source, event, triggered_by, changed_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, match_id, from_status AS \"from_status: MatchStatus\", to_status AS \"to_status: MatchStatus\", source AS \"source: StatusSource\", event, triggered_by, changed_at",
+        change.match_id,
+        change.from_status as MatchStatus,
+        change.to_status as MatchStatus,
+        change.source as StatusSource,
+        change.event,
+        change.triggered_by,
+        change.changed_at
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    Ok(change)
+}
+
+// Match rounds
+
+/// Inserts the round, or replaces it if the round was already played
+pub async fn save_match_round(db: &mut PgConnection, round: &MatchRound) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO match_rounds (match_id, map_number, number, half, overtime, winner_side, reason, ct_score, t_score, team1_score, team2_score) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (match_id, map_number, number) DO UPDATE SET half = EXCLUDED.half, overtime = EXCLUDED.overtime, winner_side = EXCLUDED.winner_side, reason = EXCLUDED.reason, ct_score = EXCLUDED.ct_score, t_score = EXCLUDED.t_score, team1_score = EXCLUDED.team1_score, team2_score = EXCLUDED.team2_score",
+        round.match_id,
+        round.map_number,
+        round.number,
+        round.half,
+        round.overtime,
+        round.winner_side as GameSide,
+        round.reason,
+        round.ct_score,
+        round.t_score,
+        round.team1_score,
+        round.team2_score
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the rounds of a map played after `number`, e.g. when a backup has been restored
+pub async fn delete_match_rounds_after(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    map_number: i32,
+    number: i32,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM match_rounds WHERE match_id = $1 AND map_number = $2 AND number > $3",
+        match_id,
+        map_number,
+        number
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_match_rounds(
+    db: &mut PgConnection,
+    match_id: Uuid,
+) -> Result<Vec<MatchRound>, Error> {
+    let rounds = sqlx::query_as!(
+        MatchRound,
+        "SELECT match_id, map_number, number, half, overtime, winner_side AS \"winner_side: GameSide\", reason, ct_score, t_score, team1_score, team2_score FROM match_rounds WHERE match_id = $1 ORDER BY map_number, number",
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rounds)
+}
+
+// Match economy
+
+/// Stores the economy of a round, and removes the economy of the rounds played after it
+pub async fn save_round_economy(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    map_number: i32,
+    round: i32,
+    economy: &[MatchRoundEconomy],
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM match_round_economy WHERE match_id = $1 AND map_number = $2 AND round >= $3",
+        match_id,
+        map_number,
+        round
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for side in economy {
+        sqlx::query!(
+            "INSERT INTO match_round_economy (match_id, map_number, round, side, starting_money, money_spent, equipment_value, buy_type) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            side.match_id,
+            side.map_number,
+            side.round,
+            side.side as GameSide,
+            side.starting_money,
+            side.money_spent,
+            side.equipment_value,
+            side.buy_type as BuyType
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_match_economy(
+    db: &mut PgConnection,
+    match_id: Uuid,
+) -> Result<Vec<MatchRoundEconomy>, Error> {
+    let economy = sqlx::query_as!(
+        MatchRoundEconomy,
+        "SELECT match_id, map_number, round, side AS \"side: GameSide\", starting_money, money_spent, equipment_value, buy_type AS \"buy_type: BuyType\" FROM match_round_economy WHERE match_id = $1 ORDER BY map_number, round, side",
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(economy)
+}
+
+// Match combat
+
+/// Stores the kills and damage of a round, and removes those of the rounds played after it
+///
+/// The `id` of the events is ignored.
+pub async fn save_round_combat(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    map_number: i32,
+    round: i32,
+    combat: &[MatchCombat],
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM match_combat WHERE match_id = $1 AND map_number = $2 AND round >= $3",
+        match_id,
+        map_number,
+        round
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for event in combat {
+        sqlx::query!(
+            "INSERT INTO match_combat (match_id, map_number, map_name, round, kind, attacker, attacker_side, attacker_x, attacker_y, attacker_z, victim, victim_side, victim_x, victim_y, victim_z, weapon, damage, headshot) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)",
+            event.match_id,
+            event.map_number,
+            event.map_name,
+            event.round,
+            event.kind as CombatKind,
+            event.attacker,
+            event.attacker_side as Option<GameSide>,
+            event.attacker_x,
+            event.attacker_y,
+            event.attacker_z,
+            event.victim,
+            event.victim_side as Option<GameSide>,
+            event.victim_x,
+            event.victim_y,
+            event.victim_z,
+            event.weapon,
+            event.damage,
+            event.headshot
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Kills and damage on a map of a match
+pub async fn get_map_combat(
+    db: &mut PgConnection,
+    map_name: &str,
+    match_id: Uuid,
+) -> Result<Vec<MatchCombat>, Error> {
+    let combat = sqlx::query_as!(
+        MatchCombat,
+        "SELECT id, match_id, map_number, map_name, round, kind AS \"kind: CombatKind\", attacker, attacker_side AS \"attacker_side: GameSide\", attacker_x, attacker_y, attacker_z, victim, victim_side AS \"victim_side: GameSide\", victim_x, victim_y, victim_z, weapon, damage, headshot FROM match_combat WHERE map_name = $1 AND match_id = $2 ORDER BY id",
+        map_name,
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(combat)
+}
+
+// Player statistics
+
+/// Replaces the statistics of the players on a map
+pub async fn set_player_map_stats(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    map_number: i32,
+    player_stats: &[PlayerMapStats],
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM player_map_stats WHERE match_id = $1 AND map_number = $2",
+        match_id,
+        map_number
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for stats in player_stats {
+        sqlx::query!(
+            "INSERT INTO player_map_stats (match_id, map_number, steamid, nick, rounds, kills, deaths, assists, flash_assists, headshot_kills, damage, kast_rounds, entry_kills, entry_deaths, one_kill_rounds, two_kill_rounds, three_kill_rounds, four_kill_rounds, five_kill_rounds, clutches_played, clutches_won, flashbangs_thrown, smokes_thrown, he_grenades_thrown, molotovs_thrown, decoys_thrown, enemies_flashed, teammates_flashed, enemy_blind_time, he_damage, molotov_damage) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31)",
+            stats.match_id,
+            stats.map_number,
+            stats.steamid,
+            stats.nick,
+            stats.rounds,
+            stats.kills,
+            stats.deaths,
+            stats.assists,
+            stats.flash_assists,
+            stats.headshot_kills,
+            stats.damage,
+            stats.kast_rounds,
+            stats.entry_kills,
+            stats.entry_deaths,
+            stats.one_kill_rounds,
+            stats.two_kill_rounds,
+            stats.three_kill_rounds,
+            stats.four_kill_rounds,
+            stats.five_kill_rounds,
+            stats.clutches_played,
+            stats.clutches_won,
+            stats.flashbangs_thrown,
+            stats.smokes_thrown,
+            stats.he_grenades_thrown,
+            stats.molotovs_thrown,
+            stats.decoys_thrown,
+            stats.enemies_flashed,
+            stats.teammates_flashed,
+            stats.enemy_blind_time,
+            stats.he_damage,
+            stats.molotov_damage
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_match_player_stats(
+    db: &mut PgConnection,
+    match_id: Uuid,
+) -> Result<Vec<PlayerMapStats>, Error> {
+    let stats = sqlx::query_as!(
+        PlayerMapStats,
+        "SELECT * FROM player_map_stats WHERE match_id = $1 ORDER BY map_number, steamid",
+        match_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(stats)
 }
 
 // Player
 
 // TODO index the player by steamid
 pub fn add_player_to_team(
-    db: &mut PgConnection,
-    team_id: Uuid,
-    name: String,
-    tag: Option<String>,
-    steamid: Option<String>,
+    _db: &mut PgConnection,
+    _team_id: Uuid,
+    _name: String,
+    _tag: Option<String>,
+    _steamid: Option<String>,
 ) -> Result<Uuid, Error> {
     todo!()
 }
 
-pub fn remove_player_from_team(db: &mut PgConnection, player_id: Uuid) -> Result<(), Error> {
+pub fn remove_player_from_team(_db: &mut PgConnection, _player_id: Uuid) -> Result<(), Error> {
     todo!()
 }
 
-pub fn get_player(db: &mut PgConnection, player_id: Uuid) -> Result<(), Error> {
+pub fn get_player(_db: &mut PgConnection, _player_id: Uuid) -> Result<(), Error> {
     todo!()
 }
 
-pub fn update_player(db: &mut PgConnection, player_id: Uuid) -> Result<(), Error> {
+pub fn update_player(_db: &mut PgConnection, _player_id: Uuid) -> Result<(), Error> {
     todo!()
 }
 
@@ -145,53 +949,295 @@ pub async fn get_team_players(
     }
 }
 
+/// Number of players passing the filters, see `list_players`
+pub async fn count_players(
+    db: &mut PgConnection,
+    search: Option<&str>,
+    team_id: Option<Uuid>,
+) -> Result<i64, Error> {
+    let search = search.map(contains_pattern);
+    let row = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM players WHERE ($1::varchar IS NULL OR name ILIKE $1 OR steamid ILIKE $1) AND ($2::uuid IS NULL OR team_id = $2)",
+        search,
+        team_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.count)
+}
+
+/// Players whose name or Steam ID contains `search`, optionally of a single team, between the
+/// cursors of the keyset
+pub async fn list_players(
+    db: &mut PgConnection,
+    search: Option<&str>,
+    team_id: Option<Uuid>,
+    order: PlayerOrder,
+    descending: bool,
+    keyset: &Keyset,
+) -> Result<Vec<Player>, Error> {
+    let search = search.map(contains_pattern);
+    let players = match order {
+        PlayerOrder::Name => {
+            let (after, after_id) = keyset.after_key::<String>()?;
+            let (before, before_id) = keyset.before_key::<String>()?;
+            sqlx::query_as!(
+                Player,
+                "SELECT * FROM players WHERE ($1::varchar IS NULL OR name ILIKE $1 OR steamid ILIKE $1) AND ($2::uuid IS NULL OR team_id = $2) AND ($3::varchar IS NULL OR CASE WHEN $7 THEN (COALESCE(name, ''), id) < ($3, $4) ELSE (COALESCE(name, ''), id) > ($3, $4) END) AND ($5::varchar IS NULL OR CASE WHEN $7 THEN (COALESCE(name, ''), id) > ($5, $6) ELSE (COALESCE(name, ''), id) < ($5, $6) END) ORDER BY CASE WHEN $8 THEN COALESCE(name, '') END DESC, CASE WHEN $8 THEN id END DESC, COALESCE(name, ''), id LIMIT $9",
+                search,
+                team_id,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+        PlayerOrder::Steamid => {
+            let (after, after_id) = keyset.after_key::<String>()?;
+            let (before, before_id) = keyset.before_key::<String>()?;
+            sqlx::query_as!(
+                Player,
+                "SELECT * FROM players WHERE ($1::varchar IS NULL OR name ILIKE $1 OR steamid ILIKE $1) AND ($2::uuid IS NULL OR team_id = $2) AND ($3::varchar IS NULL OR CASE WHEN $7 THEN (steamid, id) < ($3, $4) ELSE (steamid, id) > ($3, $4) END) AND ($5::varchar IS NULL OR CASE WHEN $7 THEN (steamid, id) > ($5, $6) ELSE (steamid, id) < ($5, $6) END) ORDER BY CASE WHEN $8 THEN steamid END DESC, CASE WHEN $8 THEN id END DESC, steamid, id LIMIT $9",
+                search,
+                team_id,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+    };
+
+    Ok(keyset.ordered(players))
+}
+
+/// Cursor of a player in the lists of players sorted by `order`
+pub fn player_cursor(player: &Player, order: PlayerOrder) -> Cursor {
+    let key = match order {
+        PlayerOrder::Name => player.name.clone().unwrap_or_default(),
+        PlayerOrder::Steamid => player.steamid.clone(),
+    };
+
+    Cursor { key, id: player.id }
+}
+
 // Server
 
-pub fn get_server(db: &mut PgConnection, server_id: Uuid) -> Result<Option<Server>, Error> {
-    todo!()
+pub async fn get_server(db: &mut PgConnection, server_id: Uuid) -> Result<Option<Server>, Error> {
+    let query: sqlx::Result<Server> =
+        sqlx::query_as!(Server, "SELECT * FROM servers WHERE id = $1", server_id)
+            .fetch_one(db)
+            .await;
+
+    match query {
+        Ok(server) => Ok(Some(server)),
+        Err(err) => match err {
+            sqlx::Error::RowNotFound => Ok(None),
+            _ => Err(err.into()),
+        },
+    }
 }
 
-pub fn add_server(
+pub async fn get_servers(db: &mut PgConnection) -> Result<Vec<Server>, Error> {
+    let servers = sqlx::query_as!(Server, "SELECT * FROM servers")
+        .fetch_all(db)
+        .await?;
+
+    Ok(servers)
+}
+
+/// Number of servers passing the filter, see `list_servers`
+pub async fn count_servers(db: &mut PgConnection, search: Option<&str>) -> Result<i64, Error> {
+    let search = search.map(contains_pattern);
+    let row = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM servers WHERE ($1::varchar IS NULL OR host(host) ILIKE $1 OR type ILIKE $1)",
+        search
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.count)
+}
+
+/// Servers whose address or type contains `search`, between the cursors of the keyset
+pub async fn list_servers(
     db: &mut PgConnection,
-    host: IpAddr,
-    port: u16,
-    r#type: Option<String>,
+    search: Option<&str>,
+    order: ServerOrder,
+    descending: bool,
+    keyset: &Keyset,
+) -> Result<Vec<Server>, Error> {
+    let search = search.map(contains_pattern);
+    let servers = match order {
+        ServerOrder::Host => {
+            let (after, after_id) = keyset.after_key::<IpNetwork>()?;
+            let (before, before_id) = keyset.before_key::<IpNetwork>()?;
+            sqlx::query_as!(
+                Server,
+                "SELECT * FROM servers WHERE ($1::varchar IS NULL OR host(host) ILIKE $1 OR type ILIKE $1) AND ($2::inet IS NULL OR CASE WHEN $6 THEN (host, id) < ($2, $3) ELSE (host, id) > ($2, $3) END) AND ($4::inet IS NULL OR CASE WHEN $6 THEN (host, id) > ($4, $5) ELSE (host, id) < ($4, $5) END) ORDER BY CASE WHEN $7 THEN host END DESC, CASE WHEN $7 THEN id END DESC, host, id LIMIT $8",
+                search,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+        ServerOrder::Type => {
+            let (after, after_id) = keyset.after_key::<String>()?;
+            let (before, before_id) = keyset.before_key::<String>()?;
+            sqlx::query_as!(
+                Server,
+                "SELECT * FROM servers WHERE ($1::varchar IS NULL OR host(host) ILIKE $1 OR type ILIKE $1) AND ($2::varchar IS NULL OR CASE WHEN $6 THEN (COALESCE(type, ''), id) < ($2, $3) ELSE (COALESCE(type, ''), id) > ($2, $3) END) AND ($4::varchar IS NULL OR CASE WHEN $6 THEN (COALESCE(type, ''), id) > ($4, $5) ELSE (COALESCE(type, ''), id) < ($4, $5) END) ORDER BY CASE WHEN $7 THEN COALESCE(type, '') END DESC, CASE WHEN $7 THEN id END DESC, COALESCE(type, ''), id LIMIT $8",
+                search,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+    };
+
+    Ok(keyset.ordered(servers))
+}
+
+/// Cursor of a server in the lists of servers sorted by `order`
+pub fn server_cursor(server: &Server, order: ServerOrder) -> Cursor {
+    let key = match order {
+        ServerOrder::Host => server.host.to_string(),
+        ServerOrder::Type => server.r#type.clone().unwrap_or_default(),
+    };
+
+    Cursor { key, id: server.id }
+}
+
+pub fn add_server(
+    _db: &mut PgConnection,
+    _host: IpAddr,
+    _port: u16,
+    _type: Option<String>,
 ) -> Result<(), Error> {
     todo!()
 }
 
-pub fn remove_server(db: &mut PgConnection, server_id: Uuid) -> Result<(), Error> {
+pub fn remove_server(_db: &mut PgConnection, _server_id: Uuid) -> Result<(), Error> {
     todo!()
 }
 
-pub fn server_info(db: &mut PgConnection, server_id: Uuid) -> Result<Option<Server>, Error> {
+pub fn server_info(_db: &mut PgConnection, _server_id: Uuid) -> Result<Option<Server>, Error> {
     todo!()
 }
 
 pub fn update_server(
+    _db: &mut PgConnection,
+    _server_id: Uuid,
+    _host: IpAddr,
+    _port: u16,
+    _type: Option<String>,
+) -> Result<(), Error> {
+    todo!()
+}
+
+// Server status
+
+/// Stores the status of a server, as of a successful poll
+pub async fn set_server_status(db: &mut PgConnection, status: &ServerStatus) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO server_status (server_id, reachable, error, polled_at, state, matchid, map_number, paused, plugin_version, team1_connected, team1_ready, team2_connected, team2_ready) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) ON CONFLICT (server_id) DO UPDATE SET reachable = EXCLUDED.reachable, error = EXCLUDED.error, polled_at = EXCLUDED.polled_at, state = EXCLUDED.state, matchid = EXCLUDED.matchid, map_number = EXCLUDED.map_number, paused = EXCLUDED.paused, plugin_version = EXCLUDED.plugin_version, team1_connected = EXCLUDED.team1_connected, team1_ready = EXCLUDED.team1_ready, team2_connected = EXCLUDED.team2_connected, team2_ready = EXCLUDED.team2_ready",
+        status.server_id,
+        status.reachable,
+        status.error,
+        status.polled_at,
+        status.state as Option<Get5State>,
+        status.matchid,
+        status.map_number,
+        status.paused,
+        status.plugin_version,
+        status.team1_connected,
+        status.team1_ready,
+        status.team2_connected,
+        status.team2_ready
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Marks a server unreachable, the status of the last successful poll is kept
+pub async fn set_server_unreachable(
     db: &mut PgConnection,
     server_id: Uuid,
-    host: IpAddr,
-    port: u16,
-    r#type: Option<String>,
+    error: &str,
+    polled_at: NaiveDateTime,
 ) -> Result<(), Error> {
-    todo!()
+    sqlx::query!("INSERT INTO server_status (server_id, reachable, error, polled_at) VALUES ($1, FALSE, $2, $3) ON CONFLICT (server_id) DO UPDATE SET reachable = FALSE, error = EXCLUDED.error, polled_at = EXCLUDED.polled_at", server_id, error, polled_at)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_server_status(
+    db: &mut PgConnection,
+    server_id: Uuid,
+) -> Result<Option<ServerStatus>, Error> {
+    let status = sqlx::query_as!(
+        ServerStatus,
+        "SELECT server_id, reachable, error, polled_at, state AS \"state: Get5State\", matchid, map_number, paused, plugin_version, team1_connected, team1_ready, team2_connected, team2_ready FROM server_status WHERE server_id = $1",
+        server_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(status)
+}
+
+pub async fn get_server_statuses(db: &mut PgConnection) -> Result<Vec<ServerStatus>, Error> {
+    let statuses = sqlx::query_as!(
+        ServerStatus,
+        "SELECT server_id, reachable, error, polled_at, state AS \"state: Get5State\", matchid, map_number, paused, plugin_version, team1_connected, team1_ready, team2_connected, team2_ready FROM server_status"
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(statuses)
 }
 
 // Spectator
 
 pub fn add_spectators(
-    db: &mut PgConnection,
-    steamid: Vec<String>,
-    match_id: Uuid,
+    _db: &mut PgConnection,
+    _steamid: Vec<String>,
+    _match_id: Uuid,
 ) -> Result<(), Error> {
     todo!()
 }
 
 pub fn remove_spectators(
-    db: &mut PgConnection,
-    steamid: Vec<String>,
-    match_id: Uuid,
+    _db: &mut PgConnection,
+    _steamid: Vec<String>,
+    _match_id: Uuid,
 ) -> Result<(), Error> {
     todo!()
 }
@@ -235,34 +1281,335 @@ pub async fn get_team(db: &mut PgConnection, team_id: Uuid) -> Result<Option<Tea
 }
 
 pub fn create_team(
-    db: &mut PgConnection,
-    name: String,
-    country: Option<CountryCode>,
-    logo: Option<String>,
+    _db: &mut PgConnection,
+    _name: String,
+    _country: Option<CountryCode>,
+    _logo: Option<String>,
 ) -> Result<(), Error> {
     todo!()
 }
 
 pub fn update_team(
-    db: &mut PgConnection,
-    team_id: i32,
-    name: String,
-    country: Option<CountryCode>,
-    logo: Option<String>,
+    _db: &mut PgConnection,
+    _team_id: i32,
+    _name: String,
+    _country: Option<CountryCode>,
+    _logo: Option<String>,
 ) -> Result<(), Error> {
     todo!()
 }
 
-pub fn delete_team(db: &mut PgConnection, team_id: Uuid) -> Result<(), Error> {
+pub fn delete_team(_db: &mut PgConnection, _team_id: Uuid) -> Result<(), Error> {
     todo!()
 }
 
-pub fn get_teams(db: &mut PgConnection) -> Result<Vec<Team>, Error> {
+pub fn get_teams(_db: &mut PgConnection) -> Result<Vec<Team>, Error> {
     todo!()
 }
 
+/// Number of teams passing the filter, see `list_teams`
+pub async fn count_teams(db: &mut PgConnection, search: Option<&str>) -> Result<i64, Error> {
+    let search = search.map(contains_pattern);
+    let row = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM teams WHERE ($1::varchar IS NULL OR name ILIKE $1)",
+        search
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.count)
+}
+
+/// Teams whose name contains `search`, between the cursors of the keyset
+pub async fn list_teams(
+    db: &mut PgConnection,
+    search: Option<&str>,
+    order: TeamOrder,
+    descending: bool,
+    keyset: &Keyset,
+) -> Result<Vec<Team>, Error> {
+    let search = search.map(contains_pattern);
+    let teams = match order {
+        TeamOrder::Name => {
+            let (after, after_id) = keyset.after_key::<String>()?;
+            let (before, before_id) = keyset.before_key::<String>()?;
+            sqlx::query_as!(
+                Team,
+                "SELECT * FROM teams WHERE ($1::varchar IS NULL OR name ILIKE $1) AND ($2::varchar IS NULL OR CASE WHEN $6 THEN (name, id) < ($2, $3) ELSE (name, id) > ($2, $3) END) AND ($4::varchar IS NULL OR CASE WHEN $6 THEN (name, id) > ($4, $5) ELSE (name, id) < ($4, $5) END) ORDER BY CASE WHEN $7 THEN name END DESC, CASE WHEN $7 THEN id END DESC, name, id LIMIT $8",
+                search,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+        TeamOrder::Country => {
+            let (after, after_id) = keyset.after_key::<String>()?;
+            let (before, before_id) = keyset.before_key::<String>()?;
+            sqlx::query_as!(
+                Team,
+                "SELECT * FROM teams WHERE ($1::varchar IS NULL OR name ILIKE $1) AND ($2::varchar IS NULL OR CASE WHEN $6 THEN (COALESCE(country::varchar, ''), id) < ($2, $3) ELSE (COALESCE(country::varchar, ''), id) > ($2, $3) END) AND ($4::varchar IS NULL OR CASE WHEN $6 THEN (COALESCE(country::varchar, ''), id) > ($4, $5) ELSE (COALESCE(country::varchar, ''), id) < ($4, $5) END) ORDER BY CASE WHEN $7 THEN COALESCE(country::varchar, '') END DESC, CASE WHEN $7 THEN id END DESC, COALESCE(country::varchar, ''), id LIMIT $8",
+                search,
+                after,
+                after_id,
+                before,
+                before_id,
+                descending,
+                keyset.reversed(descending),
+                keyset.limit
+            )
+            .fetch_all(db)
+            .await?
+        }
+    };
+
+    Ok(keyset.ordered(teams))
+}
+
+/// Cursor of a team in the lists of teams sorted by `order`
+pub fn team_cursor(team: &Team, order: TeamOrder) -> Cursor {
+    let key = match order {
+        TeamOrder::Name => team.name.clone(),
+        TeamOrder::Country => team.country.clone().unwrap_or_default(),
+    };
+
+    Cursor { key, id: team.id }
+}
+
+// Lists
+
+/// Position in a list, the key of a row in the column the list is sorted by and its id, which
+/// breaks ties between equal keys
+///
+/// Keys are kept as text and parsed back to the type of their column when listing. Missing keys
+/// are the empty string, so rows without a value come first in ascending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub key: String,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    fn key<T: FromStr>(&self) -> Result<T, Error> {
+        self.key.parse().map_err(|_| Error::InvalidCursor)
+    }
+}
+
+/// Rows of a list strictly between two cursors, either of which may be missing
+///
+/// The first `limit` rows of the range are listed, or the last ones if `from_end` is set, in the
+/// order of the list either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyset {
+    pub after: Option<Cursor>,
+    pub before: Option<Cursor>,
+    pub limit: i64,
+    pub from_end: bool,
+}
+
+impl Keyset {
+    fn after_key<T: FromStr>(&self) -> Result<(Option<T>, Option<Uuid>), Error> {
+        cursor_key(self.after.as_ref())
+    }
+
+    fn before_key<T: FromStr>(&self) -> Result<(Option<T>, Option<Uuid>), Error> {
+        cursor_key(self.before.as_ref())
+    }
+
+    /// Whether the rows are fetched against the order of the list, to take them from its end
+    fn reversed(&self, descending: bool) -> bool {
+        descending != self.from_end
+    }
+
+    /// Puts the fetched rows back in the order of the list
+    fn ordered<T>(&self, mut rows: Vec<T>) -> Vec<T> {
+        if self.from_end {
+            rows.reverse();
+        }
+
+        rows
+    }
+}
+
+fn cursor_key<T: FromStr>(cursor: Option<&Cursor>) -> Result<(Option<T>, Option<Uuid>), Error> {
+    match cursor {
+        Some(cursor) => Ok((Some(cursor.key()?), Some(cursor.id))),
+        None => Ok((None, None)),
+    }
+}
+
+/// Key of a point in time, which parses back to the same `NaiveDateTime`
+fn time_key(time: &NaiveDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+}
+
+/// Pattern for `ILIKE` matching the values that contain `search`, with its wildcards escaped
+fn contains_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("An error occurred in the underlying database driver")]
-    DatabaseError(#[from] sqlx::Error),
+    DatabaseError(sqlx::Error),
+    /// A row the record refers to does not exist, e.g. the team of a match
+    #[error("A record referred to does not exist (constraint {constraint})")]
+    ForeignKeyViolation { constraint: String },
+    /// The record conflicts with an existing one
+    #[error("The record already exists (constraint {constraint})")]
+    UniqueViolation { constraint: String },
+    /// A value is out of its range, e.g. `min_player_to_ready` above `players_per_team`
+    #[error("A value is not allowed (constraint {constraint})")]
+    CheckViolation { constraint: String },
+    /// A cursor does not fit the list, e.g. it is taken from a list sorted by another column
+    #[error("The cursor is not valid for the order of the list")]
+    InvalidCursor,
+}
+
+impl From<sqlx::Error> for Error {
+    /// Maps violations of the constraints of the schema to their own variant, see
+    /// https://www.postgresql.org/docs/current/errcodes-appendix.html
+    fn from(err: sqlx::Error) -> Self {
+        let violation = err.as_database_error().and_then(|db_err| {
+            let code = db_err.code()?;
+            let constraint = db_err.constraint().unwrap_or_default().to_string();
+
+            match code.as_ref() {
+                "23503" => Some(Error::ForeignKeyViolation { constraint }),
+                "23505" => Some(Error::UniqueViolation { constraint }),
+                "23514" => Some(Error::CheckViolation { constraint }),
+                _ => None,
+            }
+        });
+
+        violation.unwrap_or(Error::DatabaseError(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::error::Error as StdError;
+    use std::fmt::{self, Display, Formatter};
+
+    use chrono::{NaiveDate, NaiveDateTime};
+    use sqlx::error::DatabaseError;
+    use sqlx::types::ipnetwork::IpNetwork;
+    use sqlx::types::Uuid;
+
+    use super::{contains_pattern, time_key, Cursor, Error};
+
+    /// Error as reported by Postgres
+    #[derive(Debug)]
+    struct PgError {
+        code: &'static str,
+        constraint: Option<&'static str>,
+    }
+
+    impl Display for PgError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "error {}", self.code)
+        }
+    }
+
+    impl StdError for PgError {}
+
+    impl DatabaseError for PgError {
+        fn message(&self) -> &str {
+            "error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.code))
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+
+        fn constraint(&self) -> Option<&str> {
+            self.constraint
+        }
+    }
+
+    fn pg_error(code: &'static str, constraint: Option<&'static str>) -> Error {
+        sqlx::Error::Database(Box::new(PgError { code, constraint })).into()
+    }
+
+    #[test]
+    fn constraint_violations() {
+        match pg_error("23514", Some("matches_min_player_to_ready_check")) {
+            Error::CheckViolation { constraint } => {
+                assert_eq!(constraint, "matches_min_player_to_ready_check")
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+        assert!(matches!(
+            pg_error("23503", Some("matches_team1_id_fkey")),
+            Error::ForeignKeyViolation { .. }
+        ));
+        assert!(matches!(
+            pg_error("23505", Some("match_spectator_pkey")),
+            Error::UniqueViolation { .. }
+        ));
+
+        // Other errors are passed on as is
+        assert!(matches!(
+            pg_error("40001", None),
+            Error::DatabaseError(sqlx::Error::Database(_))
+        ));
+        assert!(matches!(
+            Error::from(sqlx::Error::RowNotFound),
+            Error::DatabaseError(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    #[test]
+    fn search_pattern() {
+        assert_eq!(contains_pattern("navi"), "%navi%");
+        assert_eq!(contains_pattern("100%_\\"), "%100\\%\\_\\\\%");
+    }
+
+    #[test]
+    fn cursor_keys() {
+        let cursor = |key: String| Cursor {
+            key,
+            id: Uuid::nil(),
+        };
+
+        let time = NaiveDate::from_ymd_opt(2021, 9, 14)
+            .unwrap()
+            .and_hms_micro_opt(18, 30, 5, 120)
+            .unwrap();
+        assert_eq!(
+            cursor(time_key(&time)).key::<NaiveDateTime>().unwrap(),
+            time
+        );
+
+        let host = "10.0.0.2/32".parse::<IpNetwork>().unwrap();
+        assert_eq!(cursor(host.to_string()).key::<IpNetwork>().unwrap(), host);
+
+        assert!(matches!(
+            cursor("team".to_string()).key::<NaiveDateTime>(),
+            Err(Error::InvalidCursor)
+        ));
+    }
 }
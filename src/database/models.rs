@@ -1,8 +1,14 @@
+use chrono::NaiveDateTime;
 use serde::Serialize;
+use serde_json::Value as JsonValue;
 use sqlx::types::ipnetwork::IpNetwork;
 use sqlx::types::Uuid;
 
-use crate::common::SideType;
+use crate::common::{
+    BuyType, CombatKind, GameSide, Get5State, MatchActionKind, MatchStatus, SideType, StatusSource,
+};
+use crate::csgo::stats::PlayerStats;
+use crate::csgo::utility::UtilityStats;
 use crate::get5::serializer::{
     deserialize_ipnetwork, deserialize_uuid, serialize_ipnetwork, serialize_uuid,
 };
@@ -19,6 +25,8 @@ pub struct Team {
     pub name: String,
     pub country: Option<CountryCode>,
     pub logo: Option<String>,
+    /// Short name shown in front of the names of the players
+    pub tag: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow)]
@@ -53,6 +61,8 @@ pub struct Server {
     pub port: i32,
     pub r#type: Option<String>,
     pub password: String,
+    /// Value of `sv_logsecret` on the server, see `crate::csgo::receiver::udp`
+    pub log_secret: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow)]
@@ -88,6 +98,7 @@ pub struct Match {
         deserialize_with = "deserialize_uuid"
     )]
     pub team2_id: Uuid,
+    /// Rounds won by the teams on the current map
     pub team1_score: Option<i32>,
     pub team2_score: Option<i32>,
     pub num_maps: i32,
@@ -95,6 +106,41 @@ pub struct Match {
     pub veto_first: SideType,
     pub players_per_team: i32,
     pub min_player_to_ready: i32,
+    /// Shown to the players, e.g. `Map {MAPNUMBER} of {MAXMAPS}`
+    pub match_title: Option<String>,
+    /// Predicted chance of team 1 winning in percent, shown to GOTV viewers
+    pub favored_percentage_team1: Option<i32>,
+    /// Text shown to GOTV viewers along the prediction
+    pub favored_percentage_text: Option<String>,
+    /// Assigned by the database when the match is created
+    pub created_at: NaiveDateTime,
+    /// Changed only through `crate::status::change_match_status`
+    pub status: MatchStatus,
+    /// Maps won by the teams in the series, as reported by Get5
+    pub team1_series_score: i32,
+    pub team2_series_score: i32,
+}
+
+/// Match to create, see `crate::database::create_match`
+///
+/// The id, the creation time and the status of the match are assigned by the database, and the
+/// score is only known once the match is played.
+#[derive(Debug, Clone)]
+pub struct NewMatch {
+    pub server_id: Uuid,
+    pub team1_id: Uuid,
+    pub team2_id: Uuid,
+    pub num_maps: i32,
+    pub skip_veto: bool,
+    pub veto_first: SideType,
+    pub players_per_team: i32,
+    pub min_player_to_ready: i32,
+    /// Shown to the players, e.g. `Map {MAPNUMBER} of {MAXMAPS}`
+    pub match_title: Option<String>,
+    /// Predicted chance of team 1 winning in percent, shown to GOTV viewers
+    pub favored_percentage_team1: Option<i32>,
+    /// Text shown to GOTV viewers along the prediction
+    pub favored_percentage_text: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow)]
@@ -126,3 +172,289 @@ pub struct MatchSpectator {
     )]
     pub spectator_id: Uuid,
 }
+
+/// Cvar set by Get5 when a match is loaded, see `crate::csgo::cvars`
+#[derive(Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Cvar {
+    pub name: String,
+    pub value: String,
+}
+
+/// Log entry recorded while a match was played, see `crate::csgo::ingest`
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct MatchEvent {
+    /// Increases in the order the entries were logged
+    pub id: i64,
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub match_id: Uuid,
+    /// Zero-based index of the map in the series, as counted by Get5
+    pub map_number: i32,
+    /// Round being played on the map, 0 before the first round started
+    pub round: i32,
+    /// Local time of the game server
+    pub timestamp: NaiveDateTime,
+    /// Name of the `crate::csgo::logs::LogEntry` variant
+    pub event_type: String,
+    /// Fields of the log entry
+    pub payload: JsonValue,
+}
+
+/// Command sent to a game server to control a match, or change of the cvars Get5 sets, and who
+/// triggered it
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct MatchAction {
+    pub id: i64,
+    /// `None` for a change of the default cvars of a server
+    pub match_id: Option<Uuid>,
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub server_id: Uuid,
+    pub action: MatchActionKind,
+    /// Argument of the command, e.g. the restored backup
+    pub argument: Option<String>,
+    pub triggered_by: String,
+    pub triggered_at: NaiveDateTime,
+    /// Why the command failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+/// Change of the status of a match, and what caused it, see `crate::status`
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct MatchStatusChange {
+    pub id: i64,
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub match_id: Uuid,
+    pub from_status: MatchStatus,
+    pub to_status: MatchStatus,
+    pub source: StatusSource,
+    /// What caused the change, e.g. the mutation or the Get5 event
+    pub event: String,
+    /// The operator, `None` for changes observed on the server
+    pub triggered_by: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+/// Last known status of a game server, see `crate::csgo::rcon::poller`
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct ServerStatus {
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub server_id: Uuid,
+    pub reachable: bool,
+    /// Why the last poll failed
+    pub error: Option<String>,
+    pub polled_at: NaiveDateTime,
+    /// State of Get5 as of the last successful poll, `None` if Get5 did not reply
+    pub state: Option<Get5State>,
+    pub matchid: Option<String>,
+    pub map_number: Option<i32>,
+    pub paused: Option<bool>,
+    pub plugin_version: Option<String>,
+    pub team1_connected: Option<i32>,
+    pub team1_ready: Option<bool>,
+    pub team2_connected: Option<i32>,
+    pub team2_ready: Option<bool>,
+}
+
+/// Round played on a map of a match, see `crate::csgo::rounds::RoundTracker`
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct MatchRound {
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub match_id: Uuid,
+    pub map_number: i32,
+    pub number: i32,
+    pub half: i32,
+    pub overtime: i32,
+    pub winner_side: GameSide,
+    pub reason: String,
+    pub ct_score: i32,
+    pub t_score: i32,
+    pub team1_score: Option<i32>,
+    pub team2_score: Option<i32>,
+}
+
+/// Economy of a side in a round of a match, see `crate::csgo::economy::EconomyTracker`
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct MatchRoundEconomy {
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub match_id: Uuid,
+    pub map_number: i32,
+    pub round: i32,
+    pub side: GameSide,
+    pub starting_money: i32,
+    pub money_spent: i32,
+    pub equipment_value: i32,
+    pub buy_type: BuyType,
+}
+
+/// Kill or damage dealt in a round of a match, with the position of both players
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct MatchCombat {
+    pub id: i64,
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub match_id: Uuid,
+    pub map_number: i32,
+    pub map_name: String,
+    pub round: i32,
+    pub kind: CombatKind,
+    /// Steam ID of the attacker, or `BOT <nickname>` for bots
+    pub attacker: String,
+    pub attacker_side: Option<GameSide>,
+    pub attacker_x: i32,
+    pub attacker_y: i32,
+    pub attacker_z: i32,
+    /// Steam ID of the victim, or `BOT <nickname>` for bots
+    pub victim: String,
+    pub victim_side: Option<GameSide>,
+    pub victim_x: i32,
+    pub victim_y: i32,
+    pub victim_z: i32,
+    pub weapon: String,
+    /// Health removed from the victim, 0 for kills
+    pub damage: i32,
+    pub headshot: bool,
+}
+
+/// Statistics of a player on a map of a match
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+pub struct PlayerMapStats {
+    #[serde(
+        serialize_with = "serialize_uuid",
+        deserialize_with = "deserialize_uuid"
+    )]
+    pub match_id: Uuid,
+    pub map_number: i32,
+    pub steamid: String,
+    /// Nickname used in the match
+    pub nick: String,
+    pub rounds: i32,
+    pub kills: i32,
+    pub deaths: i32,
+    pub assists: i32,
+    pub flash_assists: i32,
+    pub headshot_kills: i32,
+    pub damage: i32,
+    pub kast_rounds: i32,
+    pub entry_kills: i32,
+    pub entry_deaths: i32,
+    pub one_kill_rounds: i32,
+    pub two_kill_rounds: i32,
+    pub three_kill_rounds: i32,
+    pub four_kill_rounds: i32,
+    pub five_kill_rounds: i32,
+    pub clutches_played: i32,
+    pub clutches_won: i32,
+    pub flashbangs_thrown: i32,
+    pub smokes_thrown: i32,
+    pub he_grenades_thrown: i32,
+    pub molotovs_thrown: i32,
+    pub decoys_thrown: i32,
+    pub enemies_flashed: i32,
+    pub teammates_flashed: i32,
+    pub enemy_blind_time: f64,
+    pub he_damage: i32,
+    pub molotov_damage: i32,
+}
+
+impl PlayerMapStats {
+    pub fn new(
+        match_id: Uuid,
+        map_number: i32,
+        steamid: String,
+        nick: String,
+        stats: &PlayerStats,
+        utility: &UtilityStats,
+    ) -> Self {
+        PlayerMapStats {
+            match_id,
+            map_number,
+            steamid,
+            nick,
+            rounds: stats.rounds,
+            kills: stats.kills,
+            deaths: stats.deaths,
+            assists: stats.assists,
+            flash_assists: stats.flash_assists,
+            headshot_kills: stats.headshot_kills,
+            damage: stats.damage,
+            kast_rounds: stats.kast_rounds,
+            entry_kills: stats.entry_kills,
+            entry_deaths: stats.entry_deaths,
+            one_kill_rounds: stats.kill_rounds[0],
+            two_kill_rounds: stats.kill_rounds[1],
+            three_kill_rounds: stats.kill_rounds[2],
+            four_kill_rounds: stats.kill_rounds[3],
+            five_kill_rounds: stats.kill_rounds[4],
+            clutches_played: stats.clutches_played,
+            clutches_won: stats.clutches_won,
+            flashbangs_thrown: utility.flashbangs_thrown,
+            smokes_thrown: utility.smokes_thrown,
+            he_grenades_thrown: utility.he_grenades_thrown,
+            molotovs_thrown: utility.molotovs_thrown,
+            decoys_thrown: utility.decoys_thrown,
+            enemies_flashed: utility.enemies_flashed,
+            teammates_flashed: utility.teammates_flashed,
+            enemy_blind_time: utility.enemy_blind_time,
+            he_damage: utility.he_damage,
+            molotov_damage: utility.molotov_damage,
+        }
+    }
+
+    pub fn stats(&self) -> PlayerStats {
+        PlayerStats {
+            rounds: self.rounds,
+            kills: self.kills,
+            deaths: self.deaths,
+            assists: self.assists,
+            flash_assists: self.flash_assists,
+            headshot_kills: self.headshot_kills,
+            damage: self.damage,
+            kast_rounds: self.kast_rounds,
+            entry_kills: self.entry_kills,
+            entry_deaths: self.entry_deaths,
+            kill_rounds: [
+                self.one_kill_rounds,
+                self.two_kill_rounds,
+                self.three_kill_rounds,
+                self.four_kill_rounds,
+                self.five_kill_rounds,
+            ],
+            clutches_played: self.clutches_played,
+            clutches_won: self.clutches_won,
+        }
+    }
+
+    pub fn utility(&self) -> UtilityStats {
+        UtilityStats {
+            flashbangs_thrown: self.flashbangs_thrown,
+            smokes_thrown: self.smokes_thrown,
+            he_grenades_thrown: self.he_grenades_thrown,
+            molotovs_thrown: self.molotovs_thrown,
+            decoys_thrown: self.decoys_thrown,
+            enemies_flashed: self.enemies_flashed,
+            teammates_flashed: self.teammates_flashed,
+            enemy_blind_time: self.enemy_blind_time,
+            he_damage: self.he_damage,
+            molotov_damage: self.molotov_damage,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::{Arc, Mutex};
+
+use async_std::channel::{self, Receiver, Sender, TrySendError};
+use sqlx::types::Uuid;
+
+use crate::database::models::{MatchCombat, MatchRound, ServerStatus};
+
+/// Number of events that may be waiting for a subscriber, further events are dropped
+const SUBSCRIBER_QUEUE_SIZE: usize = 256;
+
+/// Progress of a match being played, as observed from the log of its server
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchUpdate {
+    pub match_id: Uuid,
+    /// Zero-based index of the map being played
+    pub map_number: i32,
+    pub map_name: Option<String>,
+    /// Number of the round being played, 0 before the first round started
+    pub round: i32,
+    /// Score of the teams on the map, `None` before the first round was won
+    pub team1_score: Option<i32>,
+    pub team2_score: Option<i32>,
+    /// The series is over, or was cancelled
+    pub finished: bool,
+}
+
+/// Kill in the round being played, with the nicknames the players had at the time
+#[derive(Debug, Clone)]
+pub struct Kill {
+    pub combat: MatchCombat,
+    pub attacker_nick: String,
+    pub victim_nick: String,
+}
+
+/// Change to the state of a match or a server, see `EventBus`
+#[derive(Debug, Clone)]
+pub enum Event {
+    MatchUpdated(MatchUpdate),
+    /// The status of a server was polled, see `crate::csgo::rcon::poller`
+    ServerStatus(ServerStatus),
+    Kill(Kill),
+    /// The round was stored, see `crate::csgo::ingest`
+    RoundEnded(MatchRound),
+}
+
+/// Passes the events of the log ingest and the status poller on to the subscribers, like the
+/// GraphQL subscriptions
+///
+/// Events are dropped while the queue of a subscriber is full, such that a slow subscriber doesn't
+/// hold up the publishers. Dropping the receiver ends the subscription.
+#[derive(Clone, Default)]
+pub struct EventBus(Arc<Mutex<Vec<Sender<Event>>>>);
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    /// Receives every event published from now on
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = channel::bounded(SUBSCRIBER_QUEUE_SIZE);
+        self.0.lock().unwrap().push(sender);
+
+        receiver
+    }
+
+    pub fn publish(&self, event: Event) {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|sender| match sender.try_send(event.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Closed(_)) => false,
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Uuid;
+
+    use super::{Event, EventBus, MatchUpdate};
+
+    fn match_updated(match_id: Uuid) -> Event {
+        Event::MatchUpdated(MatchUpdate {
+            match_id,
+            map_number: 0,
+            map_name: Some("de_dust2".to_string()),
+            round: 1,
+            team1_score: None,
+            team2_score: None,
+            finished: false,
+        })
+    }
+
+    #[test]
+    fn subscribers() {
+        let bus = EventBus::new();
+        let match_id = Uuid::new_v4();
+
+        // Nobody is listening yet
+        bus.publish(match_updated(Uuid::new_v4()));
+
+        let subscriber = bus.subscribe();
+        let other_subscriber = bus.subscribe();
+        bus.publish(match_updated(match_id));
+
+        for receiver in [&subscriber, &other_subscriber].iter() {
+            match receiver.try_recv() {
+                Ok(Event::MatchUpdated(update)) => assert_eq!(update.match_id, match_id),
+                event => panic!("unexpected event {:?}", event),
+            }
+            assert!(receiver.try_recv().is_err());
+        }
+
+        // Closed subscriptions are forgotten
+        drop(other_subscriber);
+        bus.publish(match_updated(match_id));
+        assert_eq!(bus.0.lock().unwrap().len(), 1);
+        assert!(subscriber.try_recv().is_ok());
+    }
+}
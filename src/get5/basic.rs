@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::option::Option;
 
 use crate::common::SideType;
@@ -17,7 +18,8 @@ pub struct Match {
     pub min_players_to_ready: Option<i32>,
     pub favored_percentage_team1: Option<i32>,
     pub favored_percentage_text: Option<String>,
-    pub cvars: Option<Vec<String>>,
+    /// Cvar names and their values
+    pub cvars: Option<BTreeMap<String, String>>,
     pub spectators: Option<Spectators>,
     pub team1: Team,
     pub team2: Team,
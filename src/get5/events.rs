@@ -0,0 +1,504 @@
+use std::str::FromStr;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json::json;
+
+// Event logging: https://github.com/splewis/get5/blob/51fe79d0da8131f7104e4a78551f4364f06be950/scripting/get5/eventlogger.sp
+// Events are logged as `get5_event: {"matchid":"...","params":{...},"event":"..."}`
+
+/// Event logged by Get5 on the `get5_event:` log lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Get5Event {
+    /// Id of the loaded match, empty if no match is loaded
+    pub matchid: String,
+    pub kind: Get5EventKind,
+}
+
+/// Get5 event as logged, before the params are decoded
+#[derive(Deserialize)]
+struct RawGet5Event {
+    // the "matchid" field name sometimes has a GRAVE ACCENT character in it, this is a bug in get5
+    #[serde(alias = "matchid`")]
+    matchid: String,
+    event: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+impl<'de> Deserialize<'de> for Get5Event {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawGet5Event::deserialize(deserializer)?;
+
+        // Unknown events are recognized by the name alone, serde only accepts them without params
+        let kind = match serde_json::from_value(json!({ "event": raw.event })) {
+            Ok(Get5EventKind::Unknown) => Get5EventKind::Unknown,
+            _ => serde_json::from_value(json!({ "event": raw.event, "params": raw.params }))
+                .map_err(D::Error::custom)?,
+        };
+
+        Ok(Get5Event {
+            matchid: raw.matchid,
+            kind,
+        })
+    }
+}
+
+impl FromStr for Get5Event {
+    type Err = serde_json::Error;
+
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Team as identified by Get5
+#[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Get5Team {
+    Team1,
+    Team2,
+    Spec,
+    None,
+}
+
+/// Side of the map, as written by Get5
+#[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Get5Side {
+    #[serde(alias = "ct")]
+    CT,
+    #[serde(alias = "t")]
+    T,
+    #[serde(alias = "none")]
+    None,
+}
+
+/// The event and its parameters
+///
+/// Players are logged in the format `nick<entity index><steam id><team>`, or `none` when there is
+/// no player.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", content = "params", rename_all = "snake_case")]
+pub enum Get5EventKind {
+    SeriesStart {
+        team1_name: String,
+        team2_name: String,
+    },
+    MapVeto {
+        team: Get5Team,
+        map_name: String,
+    },
+    MapPick {
+        team: Get5Team,
+        map_name: String,
+        map_number: i32,
+    },
+    SidePicked {
+        team: Get5Team,
+        map_name: String,
+        side: Get5Side,
+        map_number: i32,
+    },
+    KnifeStart {
+        map_name: String,
+        map_number: i32,
+    },
+    KnifeWon {
+        map_name: String,
+        map_number: i32,
+        winner: Get5Team,
+        selected_side: Get5Side,
+    },
+    GoingLive {
+        map_name: String,
+        map_number: i32,
+    },
+    TeamReady {
+        team: Get5Team,
+        stage: String,
+    },
+    PlayerConnect {
+        map_name: String,
+        map_number: i32,
+        client: String,
+    },
+    PlayerDisconnect {
+        map_name: String,
+        map_number: i32,
+        client: String,
+    },
+    ClientSay {
+        map_name: String,
+        map_number: i32,
+        client: String,
+        message: String,
+    },
+    PlayerDeath {
+        map_name: String,
+        map_number: i32,
+        attacker: String,
+        victim: String,
+        headshot: i32,
+        weapon: String,
+        assister: Option<String>,
+        flash_assister: Option<String>,
+    },
+    BombPlanted {
+        map_name: String,
+        map_number: i32,
+        client: String,
+        site: i32,
+    },
+    BombDefused {
+        map_name: String,
+        map_number: i32,
+        client: String,
+        site: i32,
+    },
+    BombExploded {
+        map_name: String,
+        map_number: i32,
+        site: i32,
+    },
+    RoundEnd {
+        map_name: String,
+        map_number: i32,
+        winner_side: Get5Side,
+        winner: Get5Team,
+        /// `CSRoundEndReason` of the round
+        reason: i32,
+    },
+    SideSwap {
+        map_name: String,
+        map_number: i32,
+        team1_side: Get5Side,
+        team2_side: Get5Side,
+        team1_score: i32,
+        team2_score: i32,
+    },
+    MapEnd {
+        map_name: String,
+        map_number: i32,
+        winner: Get5Team,
+        team1_score: i32,
+        team2_score: i32,
+    },
+    SeriesEnd {
+        winner: Get5Team,
+        team1_series_score: i32,
+        team2_series_score: i32,
+    },
+    SeriesCancel {
+        team1_series_score: i32,
+        team2_series_score: i32,
+    },
+    BackupLoaded {
+        file: String,
+    },
+    MatchConfigLoadFail {
+        reason: String,
+    },
+    /// Event not known to minictrl, e.g. added by a newer version of Get5
+    #[serde(other)]
+    Unknown,
+}
+
+impl Get5EventKind {
+    /// Number and name of the map being played when the event occurred
+    ///
+    /// The map of the veto events refers to the picked map instead, and is not included.
+    pub fn map(&self) -> Option<(i32, &str)> {
+        match self {
+            Get5EventKind::KnifeStart {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::KnifeWon {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::GoingLive {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::PlayerConnect {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::PlayerDisconnect {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::ClientSay {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::PlayerDeath {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::BombPlanted {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::BombDefused {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::BombExploded {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::RoundEnd {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::SideSwap {
+                map_number,
+                map_name,
+                ..
+            }
+            | Get5EventKind::MapEnd {
+                map_number,
+                map_name,
+                ..
+            } => Some((*map_number, map_name.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Number of the map being played when the event occurred, see `Get5EventKind::map`
+    pub fn map_number(&self) -> Option<i32> {
+        self.map().map(|(map_number, _)| map_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Get5Event, Get5EventKind, Get5Side, Get5Team};
+
+    fn parse(json: &str) -> Get5Event {
+        json.parse().unwrap()
+    }
+
+    #[test]
+    fn series_start() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"team1_name":"EnvyUs","team2_name":"Fnatic"},"event":"series_start"}"#,
+        );
+        assert_eq!(event.matchid, "example_match");
+        assert_eq!(
+            event.kind,
+            Get5EventKind::SeriesStart {
+                team1_name: "EnvyUs".to_string(),
+                team2_name: "Fnatic".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn grave_accent_matchid() {
+        let event = parse(
+            r#"{"matchid`":"","params":{"client":"none","map_number":0,"map_name":"de_dust2"},"event":"player_disconnect"}"#,
+        );
+        assert_eq!(event.matchid, "");
+        assert_eq!(
+            event.kind,
+            Get5EventKind::PlayerDisconnect {
+                map_name: "de_dust2".to_string(),
+                map_number: 0,
+                client: "none".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn map_veto_and_pick() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"team":"team1","map_name":"de_nuke"},"event":"map_veto"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::MapVeto {
+                team: Get5Team::Team1,
+                map_name: "de_nuke".to_string(),
+            }
+        );
+
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"team":"team2","map_name":"de_mirage","map_number":1},"event":"map_pick"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::MapPick {
+                team: Get5Team::Team2,
+                map_name: "de_mirage".to_string(),
+                map_number: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn side_picked() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"team":"team1","map_name":"de_mirage","side":"ct","map_number":1},"event":"side_picked"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::SidePicked {
+                team: Get5Team::Team1,
+                map_name: "de_mirage".to_string(),
+                side: Get5Side::CT,
+                map_number: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn going_live() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":0},"event":"going_live"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::GoingLive {
+                map_name: "de_inferno".to_string(),
+                map_number: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn player_death() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":0,"attacker":"foobar<2><STEAM_1:1:12345><CT>","victim":"bazgaz<3><STEAM_1:0:54321><TERRORIST>","headshot":1,"weapon":"ak47","assister":"quxquux<4><STEAM_1:1:11111><CT>"},"event":"player_death"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::PlayerDeath {
+                map_name: "de_inferno".to_string(),
+                map_number: 0,
+                attacker: "foobar<2><STEAM_1:1:12345><CT>".to_string(),
+                victim: "bazgaz<3><STEAM_1:0:54321><TERRORIST>".to_string(),
+                headshot: 1,
+                weapon: "ak47".to_string(),
+                assister: Some("quxquux<4><STEAM_1:1:11111><CT>".to_string()),
+                flash_assister: None,
+            }
+        );
+    }
+
+    #[test]
+    fn bomb_planted_and_defused() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":0,"client":"bazgaz<3><STEAM_1:0:54321><TERRORIST>","site":1},"event":"bomb_planted"}"#,
+        );
+        assert!(matches!(
+            event.kind,
+            Get5EventKind::BombPlanted { site: 1, .. }
+        ));
+
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":0,"client":"foobar<2><STEAM_1:1:12345><CT>","site":1},"event":"bomb_defused"}"#,
+        );
+        assert!(matches!(
+            event.kind,
+            Get5EventKind::BombDefused { site: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn round_end() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":0,"winner_side":"CT","winner":"team1","reason":8},"event":"round_end"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::RoundEnd {
+                map_name: "de_inferno".to_string(),
+                map_number: 0,
+                winner_side: Get5Side::CT,
+                winner: Get5Team::Team1,
+                reason: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn map_and_series_end() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":0,"winner":"team2","team1_score":11,"team2_score":16},"event":"map_end"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::MapEnd {
+                map_name: "de_inferno".to_string(),
+                map_number: 0,
+                winner: Get5Team::Team2,
+                team1_score: 11,
+                team2_score: 16,
+            }
+        );
+
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"winner":"team2","team1_series_score":0,"team2_series_score":2},"event":"series_end"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::SeriesEnd {
+                winner: Get5Team::Team2,
+                team1_series_score: 0,
+                team2_series_score: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn backup_loaded() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"file":"get5_backup_match_example_match_map0_round5.cfg"},"event":"backup_loaded"}"#,
+        );
+        assert_eq!(
+            event.kind,
+            Get5EventKind::BackupLoaded {
+                file: "get5_backup_match_example_match_map0_round5.cfg".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn map_number() {
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"map_name":"de_inferno","map_number":1},"event":"going_live"}"#,
+        );
+        assert_eq!(event.kind.map_number(), Some(1));
+        assert_eq!(event.kind.map(), Some((1, "de_inferno")));
+
+        // Refers to the picked map, not the map being played
+        let event = parse(
+            r#"{"matchid":"example_match","params":{"team":"team2","map_name":"de_mirage","map_number":1},"event":"map_pick"}"#,
+        );
+        assert_eq!(event.kind.map_number(), None);
+    }
+
+    #[test]
+    fn unknown_event() {
+        let event =
+            parse(r#"{"matchid":"example_match","params":{"foo":"bar"},"event":"some_new_event"}"#);
+        assert_eq!(event.kind, Get5EventKind::Unknown);
+    }
+}
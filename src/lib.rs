@@ -14,5 +14,7 @@ extern crate tracing;
 pub mod common;
 pub mod csgo;
 pub mod database;
+pub mod events;
 pub mod get5;
+pub mod status;
 pub mod web;
@@ -0,0 +1,302 @@
+use chrono::Utc;
+use sqlx::types::Uuid;
+use sqlx::{Acquire, PgConnection};
+
+use crate::common::{Get5State, MatchStatus, StatusSource};
+use crate::database::models::MatchStatusChange;
+use crate::database::{self, lock_match_status, set_match_status};
+use crate::get5::events::Get5EventKind;
+
+#[derive(Error, Debug)]
+pub enum StatusError {
+    #[error("No such match")]
+    NoSuchMatch,
+    #[error("The status of the match can't change from {from:?} to {to:?}")]
+    IllegalTransition { from: MatchStatus, to: MatchStatus },
+    #[error(transparent)]
+    Database(#[from] database::Error),
+}
+
+/// Statuses a match may change to from `status`
+///
+/// A match that is being played may be cancelled at any time, and goes back to the warmup between
+/// the maps of a series or when a backup is restored. Only a cancelled match may be loaded again,
+/// a finished match can't change anymore.
+pub fn next_statuses(status: MatchStatus) -> &'static [MatchStatus] {
+    use MatchStatus::*;
+
+    match status {
+        Pending => &[Warmup, Cancelled],
+        Warmup => &[Veto, KnifeRound, Live, Cancelled],
+        Veto => &[Warmup, KnifeRound, Live, Cancelled],
+        KnifeRound => &[Warmup, Live, Cancelled],
+        Live => &[Warmup, KnifeRound, Paused, Finished, Cancelled],
+        // The unpause may be missed when the series ends right after it
+        Paused => &[Warmup, Live, Finished, Cancelled],
+        Finished => &[],
+        Cancelled => &[Warmup],
+    }
+}
+
+/// Checks that a match may change from the status `from` to `to`, staying in a status is allowed
+pub fn check_transition(from: MatchStatus, to: MatchStatus) -> Result<(), StatusError> {
+    if from == to || next_statuses(from).contains(&to) {
+        Ok(())
+    } else {
+        Err(StatusError::IllegalTransition { from, to })
+    }
+}
+
+/// Checks that an operator may load a match on a server, which moves it to the warmup
+///
+/// Unlike a warmup observed on the server, e.g. between the maps of a series, a match that is
+/// being played or is over can't be loaded: it would be loaded a second time, maybe on another
+/// server. Only pending and cancelled matches may be loaded.
+pub fn check_load(status: MatchStatus) -> Result<(), StatusError> {
+    match status {
+        MatchStatus::Pending | MatchStatus::Cancelled => Ok(()),
+        _ => Err(StatusError::IllegalTransition {
+            from: status,
+            to: MatchStatus::Warmup,
+        }),
+    }
+}
+
+/// Status of the match loaded on a server, given the state of Get5 polled from the server
+///
+/// The status does not change when Get5 is idle or after a map, the end of the series is observed
+/// in the log of the server.
+pub fn status_of_get5_state(state: Get5State, paused: bool) -> Option<MatchStatus> {
+    match state {
+        Get5State::PreVeto | Get5State::Warmup => Some(MatchStatus::Warmup),
+        Get5State::Veto => Some(MatchStatus::Veto),
+        Get5State::KnifeRound | Get5State::WaitingForKnifeRoundDecision => {
+            Some(MatchStatus::KnifeRound)
+        }
+        Get5State::GoingLive | Get5State::Live if paused => Some(MatchStatus::Paused),
+        Get5State::GoingLive | Get5State::Live => Some(MatchStatus::Live),
+        Get5State::Idle | Get5State::PostGame => None,
+    }
+}
+
+/// Status of the match after a Get5 event logged by its server, along the name Get5 logs the
+/// event with, `None` if the event doesn't change the status
+pub fn status_of_get5_event(event: &Get5EventKind) -> Option<(MatchStatus, &'static str)> {
+    let status = match event {
+        Get5EventKind::SeriesStart { .. } => (MatchStatus::Warmup, "series_start"),
+        Get5EventKind::MapVeto { .. } => (MatchStatus::Veto, "map_veto"),
+        Get5EventKind::MapPick { .. } => (MatchStatus::Veto, "map_pick"),
+        Get5EventKind::SidePicked { .. } => (MatchStatus::Veto, "side_picked"),
+        Get5EventKind::KnifeStart { .. } => (MatchStatus::KnifeRound, "knife_start"),
+        Get5EventKind::GoingLive { .. } => (MatchStatus::Live, "going_live"),
+        Get5EventKind::SeriesEnd { .. } => (MatchStatus::Finished, "series_end"),
+        Get5EventKind::SeriesCancel { .. } => (MatchStatus::Cancelled, "series_cancel"),
+        _ => return None,
+    };
+
+    Some(status)
+}
+
+/// What caused a change of the status of a match, recorded along the change
+#[derive(Debug, Clone)]
+pub struct StatusTrigger {
+    pub source: StatusSource,
+    /// E.g. the mutation, the Get5 event or the polled state
+    pub event: String,
+    pub triggered_by: Option<String>,
+}
+
+impl StatusTrigger {
+    /// Mutation sent by an operator, see `crate::web::graphql::Operator`
+    pub fn operator(mutation: &str, operator: &str) -> Self {
+        StatusTrigger {
+            source: StatusSource::Operator,
+            event: mutation.to_string(),
+            triggered_by: Some(operator.to_string()),
+        }
+    }
+
+    /// Event in the log of the server, e.g. the Get5 event `series_end`
+    pub fn log(event: &str) -> Self {
+        StatusTrigger {
+            source: StatusSource::Log,
+            event: event.to_string(),
+            triggered_by: None,
+        }
+    }
+
+    /// State of Get5 polled from the server
+    pub fn poll(state: Get5State) -> Self {
+        StatusTrigger {
+            source: StatusSource::Poll,
+            event: format!("{:?}", state),
+            triggered_by: None,
+        }
+    }
+}
+
+/// Changes the status of a match, and records the change with the time it happened
+///
+/// Returns the recorded change, `None` if the match already has the status.
+pub async fn change_match_status(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    status: MatchStatus,
+    trigger: StatusTrigger,
+) -> Result<Option<MatchStatusChange>, StatusError> {
+    let mut tx = db.begin().await.map_err(database::Error::from)?;
+
+    let current = lock_match_status(&mut tx, match_id)
+        .await?
+        .ok_or(StatusError::NoSuchMatch)?;
+    if current == status {
+        return Ok(None);
+    }
+    check_transition(current, status)?;
+
+    let change = MatchStatusChange {
+        id: 0,
+        match_id,
+        from_status: current,
+        to_status: status,
+        source: trigger.source,
+        event: trigger.event,
+        triggered_by: trigger.triggered_by,
+        changed_at: Utc::now().naive_utc(),
+    };
+    let change = set_match_status(&mut tx, &change).await?;
+    tx.commit().await.map_err(database::Error::from)?;
+
+    Ok(Some(change))
+}
+
+/// Applies a status observed on the server of a match, see `change_match_status`
+///
+/// An observation that is out of date, e.g. a poll that started before the series ended, would
+/// change the status along an illegal transition, and is ignored.
+pub async fn observe_match_status(
+    db: &mut PgConnection,
+    match_id: Uuid,
+    status: MatchStatus,
+    trigger: StatusTrigger,
+) -> Result<(), database::Error> {
+    match change_match_status(db, match_id, status, trigger).await {
+        Ok(Some(change)) => {
+            debug!(
+                "match (id={}) changed from {:?} to {:?}",
+                match_id, change.from_status, change.to_status
+            );
+            Ok(())
+        }
+        Ok(None) => Ok(()),
+        Err(err @ StatusError::IllegalTransition { .. }) => {
+            debug!("ignoring status of match (id={}): {}", match_id, err);
+            Ok(())
+        }
+        // The match was deleted in the meantime
+        Err(StatusError::NoSuchMatch) => Ok(()),
+        Err(StatusError::Database(err)) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{Get5State, MatchStatus};
+    use crate::get5::events::{Get5EventKind, Get5Team};
+
+    use super::{
+        check_load, check_transition, status_of_get5_event, status_of_get5_state, StatusError,
+    };
+
+    #[test]
+    fn transitions() {
+        use MatchStatus::*;
+
+        // A best of one, with a pause
+        let played = [
+            Pending, Warmup, Veto, KnifeRound, Live, Paused, Live, Finished,
+        ];
+        for pair in played.windows(2) {
+            assert!(check_transition(pair[0], pair[1]).is_ok(), "{:?}", pair);
+        }
+        // The next map of a series
+        assert!(check_transition(Live, Warmup).is_ok());
+        assert!(check_transition(Live, Live).is_ok());
+        // A cancelled match is loaded again
+        assert!(check_transition(Live, Cancelled).is_ok());
+        assert!(check_transition(Cancelled, Warmup).is_ok());
+
+        assert!(matches!(
+            check_transition(Pending, Live),
+            Err(StatusError::IllegalTransition {
+                from: Pending,
+                to: Live
+            })
+        ));
+        assert!(check_transition(Warmup, Paused).is_err());
+        assert!(check_transition(Cancelled, Finished).is_err());
+        for status in [Pending, Warmup, Live, Cancelled].iter() {
+            assert!(check_transition(Finished, *status).is_err());
+        }
+    }
+
+    #[test]
+    fn loads() {
+        use MatchStatus::*;
+
+        assert!(check_load(Pending).is_ok());
+        assert!(check_load(Cancelled).is_ok());
+        // The warmup between maps is allowed when observed, not when loaded by an operator
+        assert!(check_transition(Live, Warmup).is_ok());
+        for status in [Warmup, Veto, KnifeRound, Live, Paused, Finished].iter() {
+            assert!(matches!(
+                check_load(*status),
+                Err(StatusError::IllegalTransition { to: Warmup, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn observed_statuses() {
+        assert_eq!(
+            status_of_get5_state(Get5State::PreVeto, false),
+            Some(MatchStatus::Warmup)
+        );
+        assert_eq!(
+            status_of_get5_state(Get5State::WaitingForKnifeRoundDecision, false),
+            Some(MatchStatus::KnifeRound)
+        );
+        assert_eq!(
+            status_of_get5_state(Get5State::Live, false),
+            Some(MatchStatus::Live)
+        );
+        assert_eq!(
+            status_of_get5_state(Get5State::Live, true),
+            Some(MatchStatus::Paused)
+        );
+        assert_eq!(status_of_get5_state(Get5State::PostGame, false), None);
+        assert_eq!(status_of_get5_state(Get5State::Idle, false), None);
+
+        assert_eq!(
+            status_of_get5_event(&Get5EventKind::SeriesEnd {
+                winner: Get5Team::Team1,
+                team1_series_score: 2,
+                team2_series_score: 1,
+            }),
+            Some((MatchStatus::Finished, "series_end"))
+        );
+        assert_eq!(
+            status_of_get5_event(&Get5EventKind::SeriesCancel {
+                team1_series_score: 0,
+                team2_series_score: 0,
+            }),
+            Some((MatchStatus::Cancelled, "series_cancel"))
+        );
+        assert_eq!(
+            status_of_get5_event(&Get5EventKind::BackupLoaded {
+                file: "get5_backup_match1_map0_round3.cfg".to_string(),
+            }),
+            None
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use async_std::stream::StreamExt;
+use async_std::task;
+use serde_json::Value as JsonValue;
+use sqlx::{Acquire, Postgres};
+use tide::{Endpoint, Response, StatusCode};
+use tide_sqlx::SQLxRequestExt;
+use tide_websockets::{Message, WebSocket, WebSocketConnection};
+use uuid::Uuid;
+
+use crate::csgo::rcon::CommandFilter;
+use crate::database::get_server;
+use crate::web::token::{request_token, token_matches};
+use crate::web::State;
+
+/// Access to the RCON console, see `endpoint_rcon_console`
+#[derive(Clone, Debug)]
+pub struct ConsoleConfig {
+    /// Secret the admins authenticate with
+    pub token: String,
+    /// Commands that may be sent to the servers
+    pub filter: CommandFilter,
+}
+
+/// Message sent to the console
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ConsoleMessage {
+    /// Reply of the server to a command
+    Reply { command: String, reply: String },
+    /// The command was rejected, or could not be sent
+    Error { command: String, error: String },
+    /// Entry of the log of the server, see `crate::csgo::logs::LogEntry`
+    Log { entry: JsonValue },
+}
+
+/// RCON console of a server over a WebSocket
+///
+/// The server is given by its id in the `server_id` parameter of the path, and the console token
+/// in the `token` query parameter or the `Authorization: Bearer` header. Every text message is sent
+/// to the server as a command, unless it is rejected by the command filter. The replies, and the
+/// entries of the log of the server, are sent back as JSON objects tagged by their `type`.
+pub async fn endpoint_rcon_console(req: tide::Request<State>) -> tide::Result<Response> {
+    let config = match &req.state().console {
+        Some(config) => config.clone(),
+        // No token is configured, the console is disabled
+        None => return Ok(Response::new(StatusCode::NotFound)),
+    };
+
+    match request_token(&req)? {
+        Some(token) if token_matches(token.as_str(), config.token.as_str()) => {}
+        _ => return Ok(Response::new(StatusCode::Unauthorized)),
+    }
+
+    let server_id = match Uuid::parse_str(req.param("server_id")?) {
+        Ok(server_id) => server_id,
+        Err(_) => return Ok(Response::new(StatusCode::BadRequest)),
+    };
+
+    {
+        let mut pool = req.sqlx_conn::<Postgres>().await;
+        let db_conn = pool.acquire().await?;
+
+        match get_server(db_conn, server_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Ok(Response::new(StatusCode::NotFound)),
+            Err(err) => {
+                return Err(tide::Error::new(StatusCode::InternalServerError, err));
+            }
+        }
+    }
+
+    WebSocket::new(move |req: tide::Request<State>, stream| {
+        run_console(server_id, config.filter.clone(), req, stream)
+    })
+    .call(req)
+    .await
+}
+
+async fn run_console(
+    server_id: Uuid,
+    filter: CommandFilter,
+    req: tide::Request<State>,
+    mut stream: WebSocketConnection,
+) -> tide::Result<()> {
+    let state = req.state();
+    info!(
+        "RCON console of server (id={}) opened by {}",
+        server_id,
+        req.remote().unwrap_or("unknown")
+    );
+
+    // Forward the log entries until the console is closed
+    let entries = state.log_router.watch(server_id);
+    let log_stream = stream.clone();
+    let forwarder = task::spawn(async move {
+        while let Ok(entry) = entries.recv().await {
+            if log_stream
+                .send_json(&ConsoleMessage::Log { entry })
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    });
+
+    while let Some(message) = stream.next().await {
+        let command = match message? {
+            Message::Text(command) => command,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let reply = match filter.check(command.as_str()) {
+            Err(rejected) => ConsoleMessage::Error {
+                error: format!("The command {} is not allowed", rejected),
+                command,
+            },
+            Ok(()) => {
+                info!("RCON console of server (id={}): {}", server_id, command);
+                match state.rcon.cmd(server_id, command.as_str()).await {
+                    Ok(reply) => ConsoleMessage::Reply { command, reply },
+                    Err(err) => ConsoleMessage::Error {
+                        error: err.to_string(),
+                        command,
+                    },
+                }
+            }
+        };
+        stream.send_json(&reply).await?;
+    }
+
+    forwarder.cancel().await;
+    Ok(())
+}
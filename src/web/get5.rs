@@ -3,7 +3,7 @@ use sqlx::Postgres;
 use tide::{Body, Response, StatusCode};
 use tide_sqlx::SQLxRequestExt;
 
-use crate::common::SideType;
+use crate::csgo::cvars::merge_cvars;
 use crate::database::models::Player;
 use crate::database::*;
 use crate::get5::basic::{
@@ -26,12 +26,12 @@ struct MatchIdArgs {
 
 pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Response> {
     let mut pool = req.sqlx_conn::<Postgres>().await;
-    let mut db_conn = pool.acquire().await?;
+    let db_conn = pool.acquire().await?;
 
     let id = req.query::<MatchIdArgs>()?.id;
 
     // Match
-    let r#match = match get_match(&mut db_conn, id).await {
+    let r#match = match get_match(db_conn, id).await {
         Ok(m) => match m {
             None => {
                 return tide::Result::Ok(Response::new(StatusCode::NotFound));
@@ -44,7 +44,7 @@ pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Res
     };
 
     // Teams
-    let team1 = match get_team(&mut db_conn, r#match.team1_id).await {
+    let team1 = match get_team(db_conn, r#match.team1_id).await {
         Ok(team) => match team {
             None => {
                 error!("match (id={}) referenced team (id={}) in the database, but no such team exists", r#match.id, r#match.team1_id);
@@ -59,7 +59,7 @@ pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Res
             return tide::Result::Err(tide::Error::new(StatusCode::InternalServerError, err))
         }
     };
-    let team2 = match get_team(&mut db_conn, r#match.team2_id).await {
+    let team2 = match get_team(db_conn, r#match.team2_id).await {
         Ok(team) => match team {
             None => {
                 error!("match (id={}) referenced team (id={}) in the database, but no such team exists", r#match.id, r#match.team2_id);
@@ -76,7 +76,7 @@ pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Res
     };
 
     // Players
-    let team1_players = match get_team_players(&mut db_conn, r#match.team1_id).await {
+    let team1_players = match get_team_players(db_conn, r#match.team1_id).await {
         Ok(players) => match players {
             None => {
                 error!("Match (id={}) referenced Team (id={}) in the database, but no such Team exists", r#match.id, r#match.team1_id);
@@ -91,7 +91,7 @@ pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Res
             return tide::Result::Err(tide::Error::new(StatusCode::InternalServerError, err))
         }
     };
-    let team2_players = match get_team_players(&mut db_conn, r#match.team2_id).await {
+    let team2_players = match get_team_players(db_conn, r#match.team2_id).await {
         Ok(players) => match players {
             None => {
                 error!("Match (id={}) referenced Team (id={}) in the database, but no such Team exists", r#match.id, r#match.team2_id);
@@ -108,7 +108,7 @@ pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Res
     };
 
     // Spectators
-    let spectators = match get_spectators(&mut db_conn, r#match.id).await {
+    let spectators = match get_spectators(db_conn, r#match.id).await {
         Ok(spectators) => match spectators {
             None => {
                 error!("no Match with id {} exists", r#match.id);
@@ -141,41 +141,77 @@ pub async fn endpoint_get5_config(req: tide::Request<State>) -> tide::Result<Res
         }
     };
 
+    // Maps
+    let maplist = match get_match_map_list(db_conn, r#match.id).await {
+        Ok(maplist) => match maplist {
+            None => {
+                error!("no Match with id {} exists", r#match.id);
+                return tide::Result::Err(tide::Error::new(
+                    StatusCode::InternalServerError,
+                    anyhow::Error::msg(""),
+                ));
+            }
+            // Get5 breaks on empty lists so omit the field if there is no maps, which makes Get5
+            // fall back to its default map list
+            Some(maplist) if maplist.is_empty() => None,
+            Some(maplist) => Some(maplist),
+        },
+        Err(err) => {
+            return tide::Result::Err(tide::Error::new(StatusCode::InternalServerError, err))
+        }
+    };
+
+    // Cvars, the cvars of the match override the defaults of the server
+    let server_cvars = match get_server_cvars(db_conn, r#match.server_id).await {
+        Ok(cvars) => cvars,
+        Err(err) => {
+            return tide::Result::Err(tide::Error::new(StatusCode::InternalServerError, err))
+        }
+    };
+    let match_cvars = match get_match_cvars(db_conn, r#match.id).await {
+        Ok(cvars) => cvars,
+        Err(err) => {
+            return tide::Result::Err(tide::Error::new(StatusCode::InternalServerError, err))
+        }
+    };
+    let cvars = merge_cvars(
+        server_cvars.into_iter().map(|cvar| (cvar.name, cvar.value)),
+        match_cvars.into_iter().map(|cvar| (cvar.name, cvar.value)),
+    );
+
     let get5_match = Get5Match {
         matchid: Some(r#match.id.to_string()),
         num_maps: Some(r#match.num_maps),
-        maplist: Some(vec![
-            "de_dust2".to_string(),
-            "de_cbble".to_string(),
-            "de_train".to_string(),
-        ]), // TODO
+        maplist,
         skip_veto: Some(r#match.skip_veto),
-        side_type: Some(SideType::Standard), // TODO
+        // The side type is stored in the misnamed `veto_first` column
+        side_type: Some(r#match.veto_first),
         players_per_team: Some(r#match.players_per_team),
         min_players_to_ready: Some(r#match.min_player_to_ready),
-        favored_percentage_team1: None,
-        favored_percentage_text: None,
-        cvars: None,
+        favored_percentage_team1: r#match.favored_percentage_team1,
+        favored_percentage_text: r#match.favored_percentage_text,
+        // Omit the field if there is no cvars, like the other lists
+        cvars: if cvars.is_empty() { None } else { Some(cvars) },
         spectators,
         team1: Get5Team {
             name: team1.name,
-            tag: None,
+            tag: team1.tag,
             flag: team1.country,
             logo: team1.logo,
             players: team1_players,
-            series_score: None,
+            series_score: Some(r#match.team1_series_score),
             match_text: None,
         },
         team2: Get5Team {
             name: team2.name,
-            tag: None,
+            tag: team2.tag,
             flag: team2.country,
             logo: team2.logo,
             players: team2_players,
-            series_score: None,
+            series_score: Some(r#match.team2_series_score),
             match_text: None,
         },
-        match_title: None,
+        match_title: r#match.match_title,
     };
 
     let mut resp = Response::new(StatusCode::Ok);
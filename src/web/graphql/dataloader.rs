@@ -2,28 +2,14 @@ use std::collections::HashMap;
 
 use async_graphql::dataloader::Loader;
 use async_graphql::FieldError;
-use itertools::Itertools;
 use sqlx::types::Uuid;
 use sqlx::{Pool, Postgres};
-use IntoIterator;
-
-use crate::database::models::{Match, Player, Team};
-
-/// Builds a list of UUIDs for use in a SQL query
-fn uuid_list(keys: &[Uuid]) -> String {
-    keys.iter()
-        .map(|key| format!("'{}'", key.to_string()))
-        .enumerate()
-        .fold(String::new(), |mut acc, (i, key)| {
-            if i == 0 {
-                key.to_string()
-            } else {
-                acc.push_str(", ");
-                acc.push_str(key.as_str());
-                acc
-            }
-        })
-}
+
+use crate::common::{BuyType, GameSide, MatchStatus, SideType, StatusSource};
+use crate::database::models::{
+    MapList, Match, MatchRoundEconomy, MatchSpectator, MatchStatusChange, Player, PlayerMapStats,
+    Server, Spectator, Team,
+};
 
 pub struct TeamLoader(Pool<Postgres>);
 
@@ -39,15 +25,14 @@ impl Loader<Uuid> for TeamLoader {
     type Error = FieldError;
 
     async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
-        let key_list = uuid_list(keys);
-        let query = format!("SELECT * FROM teams WHERE id IN ({})", key_list);
-
-        Ok(sqlx::query_as::<_, Team>(query.as_str())
-            .fetch_all(&self.0)
-            .await?
-            .drain(..)
-            .map(|row| (row.id, row))
-            .collect())
+        Ok(
+            sqlx::query_as!(Team, "SELECT * FROM teams WHERE id = ANY($1::uuid[])", keys)
+                .fetch_all(&self.0)
+                .await?
+                .drain(..)
+                .map(|row| (row.id, row))
+                .collect(),
+        )
     }
 }
 
@@ -65,15 +50,16 @@ impl Loader<Uuid> for PlayerLoader {
     type Error = FieldError;
 
     async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
-        let key_list = uuid_list(keys);
-        let query = format!("SELECT * FROM teams WHERE id IN ({})", key_list);
-
-        Ok(sqlx::query_as::<_, Player>(query.as_str())
-            .fetch_all(&self.0)
-            .await?
-            .drain(..)
-            .map(|row| (row.id, row))
-            .collect())
+        Ok(sqlx::query_as!(
+            Player,
+            "SELECT * FROM players WHERE id = ANY($1::uuid[])",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        .drain(..)
+        .map(|row| (row.id, row))
+        .collect())
     }
 }
 
@@ -91,19 +77,17 @@ impl Loader<Uuid> for PlayerTeamLoader {
     type Error = FieldError;
 
     async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
-        let key_list = uuid_list(keys);
-        let query = format!("SELECT * FROM players WHERE team_id IN ({})", key_list);
-
-        let mut players = sqlx::query_as::<_, Player>(query.as_str())
-            .fetch_all(&self.0)
-            .await?;
-        players.sort_by_key(|player| player.team_id);
-        let team_players: HashMap<Uuid, Self::Value> = players
-            .iter()
-            .group_by(|player| player.team_id)
-            .into_iter()
-            .map(|(team_id, group)| (team_id, group.cloned().collect::<Vec<Player>>()))
-            .collect();
+        let mut team_players: HashMap<Uuid, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            Player,
+            "SELECT * FROM players WHERE team_id = ANY($1::uuid[])",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            team_players.entry(row.team_id).or_default().push(row);
+        }
 
         Ok(team_players)
     }
@@ -123,16 +107,722 @@ impl Loader<Uuid> for MatchLoader {
     type Error = FieldError;
 
     async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
-        let key_list = uuid_list(keys);
-        let query = format!("SELECT * FROM teams WHERE id IN ({})", key_list);
+        Ok(sqlx::query_as!(
+            Match,
+            "SELECT id, server_id, team1_id, team2_id, team1_score, team2_score, num_maps, skip_veto, veto_first AS \"veto_first: SideType\", players_per_team, min_player_to_ready, match_title, favored_percentage_team1, favored_percentage_text, created_at, status AS \"status: MatchStatus\", team1_series_score, team2_series_score FROM matches WHERE id = ANY($1::uuid[])",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        .drain(..)
+        .map(|row| (row.id, row))
+        .collect())
+    }
+}
+
+pub struct ServerLoader(Pool<Postgres>);
+
+impl ServerLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for ServerLoader {
+    type Value = Server;
+    type Error = FieldError;
 
-        Ok(sqlx::query_as::<_, Match>(query.as_str())
-            .fetch_all(&self.0)
-            .await?
-            .drain(..)
-            .map(|row| (row.id, row))
-            .collect())
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        Ok(sqlx::query_as!(
+            Server,
+            "SELECT * FROM servers WHERE id = ANY($1::uuid[])",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        .drain(..)
+        .map(|row| (row.id, row))
+        .collect())
     }
 }
 
-// TODO implement loaders for all structs in crate::database::models
+pub struct SpectatorLoader(Pool<Postgres>);
+
+impl SpectatorLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for SpectatorLoader {
+    type Value = Spectator;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        Ok(sqlx::query_as!(
+            Spectator,
+            "SELECT * FROM spectators WHERE id = ANY($1::uuid[])",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        .drain(..)
+        .map(|row| (row.id, row))
+        .collect())
+    }
+}
+
+/// Loads the maps of a match in the order they are played, by match
+pub struct MapListLoader(Pool<Postgres>);
+
+impl MapListLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for MapListLoader {
+    type Value = Vec<MapList>;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        let mut map_lists: HashMap<Uuid, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            MapList,
+            "SELECT id, match_id AS \"match_id!\", \"order\", map FROM maplist WHERE match_id = ANY($1::uuid[]) ORDER BY \"order\"",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            map_lists.entry(row.match_id).or_default().push(row);
+        }
+
+        Ok(map_lists)
+    }
+}
+
+/// Loads the spectators assigned to a match, by match
+pub struct MatchSpectatorLoader(Pool<Postgres>);
+
+impl MatchSpectatorLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for MatchSpectatorLoader {
+    type Value = Vec<MatchSpectator>;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        let mut match_spectators: HashMap<Uuid, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            MatchSpectator,
+            "SELECT * FROM match_spectator WHERE match_id = ANY($1::uuid[])",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            match_spectators.entry(row.match_id).or_default().push(row);
+        }
+
+        Ok(match_spectators)
+    }
+}
+
+/// Loads the statistics of players per map, by Steam ID
+pub struct PlayerStatsLoader(Pool<Postgres>);
+
+impl PlayerStatsLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<String> for PlayerStatsLoader {
+    type Value = Vec<PlayerMapStats>;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let mut player_stats: HashMap<String, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            PlayerMapStats,
+            "SELECT * FROM player_map_stats WHERE steamid = ANY($1) ORDER BY match_id, map_number",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            player_stats
+                .entry(row.steamid.clone())
+                .or_default()
+                .push(row);
+        }
+
+        Ok(player_stats)
+    }
+}
+
+/// Loads the statistics of players per map, by match
+pub struct MatchStatsLoader(Pool<Postgres>);
+
+impl MatchStatsLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for MatchStatsLoader {
+    type Value = Vec<PlayerMapStats>;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        let mut match_stats: HashMap<Uuid, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            PlayerMapStats,
+            "SELECT * FROM player_map_stats WHERE match_id = ANY($1::uuid[]) ORDER BY map_number, steamid",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            match_stats.entry(row.match_id).or_default().push(row);
+        }
+
+        Ok(match_stats)
+    }
+}
+
+/// Loads the economy of the sides per round, by match
+pub struct MatchEconomyLoader(Pool<Postgres>);
+
+impl MatchEconomyLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for MatchEconomyLoader {
+    type Value = Vec<MatchRoundEconomy>;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        let mut match_economy: HashMap<Uuid, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            MatchRoundEconomy,
+            "SELECT match_id, map_number, round, side AS \"side: GameSide\", starting_money, money_spent, equipment_value, buy_type AS \"buy_type: BuyType\" FROM match_round_economy WHERE match_id = ANY($1::uuid[]) ORDER BY map_number, round, side",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            match_economy.entry(row.match_id).or_default().push(row);
+        }
+
+        Ok(match_economy)
+    }
+}
+
+pub struct MatchStatusHistoryLoader(Pool<Postgres>);
+
+impl MatchStatusHistoryLoader {
+    pub(in crate::web::graphql) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<Uuid> for MatchStatusHistoryLoader {
+    type Value = Vec<MatchStatusChange>;
+    type Error = FieldError;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        let mut history: HashMap<Uuid, Self::Value> = HashMap::new();
+        for row in sqlx::query_as!(
+            MatchStatusChange,
+            "SELECT id, match_id, from_status AS \"from_status: MatchStatus\", to_status AS \"to_status: MatchStatus\", source AS \"source: StatusSource\", event, triggered_by, changed_at FROM match_status_history WHERE match_id = ANY($1::uuid[]) ORDER BY id",
+            keys
+        )
+        .fetch_all(&self.0)
+        .await?
+        {
+            history.entry(row.match_id).or_default().push(row);
+        }
+
+        Ok(history)
+    }
+}
+
+/// The loaders are run against a throwaway database, created on the Postgres server given by
+/// `TEST_DATABASE_URL` and dropped after the test, whether it passed or not. The tests only run
+/// with the `test_database` feature.
+#[cfg(all(test, feature = "test_database"))]
+mod tests {
+    use std::env;
+    use std::str::FromStr;
+    use std::thread;
+
+    use async_graphql::dataloader::Loader;
+    use async_std::task;
+
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+    use sqlx::types::Uuid;
+    use sqlx::{Connection, Executor, PgConnection, Pool, Postgres};
+
+    use crate::common::{BuyType, GameSide, MatchStatus, SideType, StatusSource};
+    use crate::csgo::stats::PlayerStats;
+    use crate::csgo::utility::UtilityStats;
+    use crate::database::models::{MatchRoundEconomy, NewMatch, PlayerMapStats};
+    use crate::database::{
+        add_spectator_to_match, create_match, run_migrations, save_round_economy,
+        set_player_map_stats,
+    };
+    use crate::status::{change_match_status, StatusError, StatusTrigger};
+
+    use super::{
+        MapListLoader, MatchEconomyLoader, MatchLoader, MatchSpectatorLoader, MatchStatsLoader,
+        MatchStatusHistoryLoader, PlayerLoader, PlayerStatsLoader, PlayerTeamLoader, ServerLoader,
+        SpectatorLoader, TeamLoader,
+    };
+
+    struct TestDatabase {
+        admin_url: String,
+        name: String,
+        pool: Pool<Postgres>,
+    }
+
+    impl TestDatabase {
+        /// Creates a database with the migrations applied
+        async fn create() -> Self {
+            let admin_url = env::var("TEST_DATABASE_URL")
+                .expect("TEST_DATABASE_URL must be set to run the database tests");
+
+            let name = format!("minictrl_test_{}", Uuid::new_v4().to_simple());
+            let mut admin = PgConnection::connect(admin_url.as_str()).await.unwrap();
+            admin
+                .execute(format!("CREATE DATABASE {}", name).as_str())
+                .await
+                .unwrap();
+
+            let options = PgConnectOptions::from_str(admin_url.as_str())
+                .unwrap()
+                .database(name.as_str());
+            let pool = PgPoolOptions::new()
+                .max_connections(2)
+                .connect_with(options)
+                .await
+                .unwrap();
+            run_migrations(&pool).await.unwrap();
+
+            TestDatabase {
+                admin_url,
+                name,
+                pool,
+            }
+        }
+
+        async fn drop_database(&self) -> sqlx::Result<()> {
+            self.pool.close().await;
+
+            let mut admin = PgConnection::connect(self.admin_url.as_str()).await?;
+            admin
+                .execute(format!("DROP DATABASE {}", self.name).as_str())
+                .await?;
+
+            Ok(())
+        }
+    }
+
+    impl Drop for TestDatabase {
+        /// Drops the database when the test ends, also when it panicked
+        fn drop(&mut self) {
+            if let Err(err) = task::block_on(self.drop_database()) {
+                // Panicking again while unwinding would abort the tests
+                if !thread::panicking() {
+                    panic!("failed to drop database {}: {}", self.name, err);
+                }
+            }
+        }
+    }
+
+    struct Fixture {
+        team1: Uuid,
+        team2: Uuid,
+        /// Players of team1
+        players: Vec<Uuid>,
+        server: Uuid,
+        spectators: Vec<Uuid>,
+        r#match: Uuid,
+    }
+
+    async fn insert_fixture(pool: &Pool<Postgres>) -> Fixture {
+        let mut db = pool.acquire().await.unwrap();
+
+        let mut ids = Vec::new();
+        for query in &[
+            "INSERT INTO teams (name) VALUES ('Team A') RETURNING id",
+            "INSERT INTO teams (name) VALUES ('Team B') RETURNING id",
+            "INSERT INTO servers (host, port, password) VALUES ('10.0.0.2', 27015, 'secret') RETURNING id",
+            "INSERT INTO spectators (steamid) VALUES ('STEAM_1:0:1') RETURNING id",
+            "INSERT INTO spectators (steamid) VALUES ('STEAM_1:0:2') RETURNING id",
+        ] {
+            let id = sqlx::query_scalar::<_, Uuid>(query)
+                .fetch_one(&mut db)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        let (team1, team2, server, spectators) = (ids[0], ids[1], ids[2], vec![ids[3], ids[4]]);
+
+        let mut players = Vec::new();
+        for steamid in &["STEAM_1:0:10", "STEAM_1:0:11"] {
+            let id = sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO players (name, team_id, steamid) VALUES ('Player', $1, $2) RETURNING id",
+            )
+            .bind(team1)
+            .bind(*steamid)
+            .fetch_one(&mut db)
+            .await
+            .unwrap();
+            players.push(id);
+        }
+
+        let r#match = create_match(
+            &mut db,
+            &NewMatch {
+                server_id: server,
+                team1_id: team1,
+                team2_id: team2,
+                num_maps: 3,
+                skip_veto: true,
+                veto_first: SideType::Standard,
+                players_per_team: 5,
+                min_player_to_ready: 5,
+                match_title: None,
+                favored_percentage_team1: None,
+                favored_percentage_text: None,
+            },
+            &[
+                "de_inferno".to_string(),
+                "de_dust2".to_string(),
+                "de_nuke".to_string(),
+            ],
+        )
+        .await
+        .unwrap()
+        .id;
+        for spectator in &spectators {
+            add_spectator_to_match(&mut db, *spectator, r#match)
+                .await
+                .unwrap();
+        }
+
+        Fixture {
+            team1,
+            team2,
+            players,
+            server,
+            spectators,
+            r#match,
+        }
+    }
+
+    #[async_std::test]
+    async fn loaders_by_id() {
+        let db = TestDatabase::create().await;
+        let fixture = insert_fixture(&db.pool).await;
+        let unknown = Uuid::new_v4();
+
+        let teams = TeamLoader::new(db.pool.clone())
+            .load(&[fixture.team1, fixture.team2, unknown])
+            .await
+            .unwrap();
+        assert_eq!(teams.len(), 2);
+        assert_eq!(teams[&fixture.team1].name, "Team A");
+        assert_eq!(teams[&fixture.team2].name, "Team B");
+
+        let players = PlayerLoader::new(db.pool.clone())
+            .load(&[fixture.players[0], unknown])
+            .await
+            .unwrap();
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[&fixture.players[0]].steamid, "STEAM_1:0:10");
+
+        let servers = ServerLoader::new(db.pool.clone())
+            .load(&[fixture.server])
+            .await
+            .unwrap();
+        assert_eq!(servers[&fixture.server].port, 27015);
+
+        let spectators = SpectatorLoader::new(db.pool.clone())
+            .load(&fixture.spectators)
+            .await
+            .unwrap();
+        assert_eq!(spectators[&fixture.spectators[1]].steamid, "STEAM_1:0:2");
+
+        let matches = MatchLoader::new(db.pool.clone())
+            .load(&[fixture.r#match, unknown])
+            .await
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[&fixture.r#match].team2_id, fixture.team2);
+        assert_eq!(matches[&fixture.r#match].veto_first, SideType::Standard);
+
+        assert!(TeamLoader::new(db.pool.clone())
+            .load(&[])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[async_std::test]
+    async fn loaders_by_match_and_team() {
+        let db = TestDatabase::create().await;
+        let fixture = insert_fixture(&db.pool).await;
+
+        let team_players = PlayerTeamLoader::new(db.pool.clone())
+            .load(&[fixture.team1, fixture.team2])
+            .await
+            .unwrap();
+        let mut players = team_players[&fixture.team1]
+            .iter()
+            .map(|player| player.id)
+            .collect::<Vec<Uuid>>();
+        players.sort();
+        let mut expected = fixture.players.clone();
+        expected.sort();
+        assert_eq!(players, expected);
+        // Teams without players are left out
+        assert!(!team_players.contains_key(&fixture.team2));
+
+        let map_lists = MapListLoader::new(db.pool.clone())
+            .load(&[fixture.r#match])
+            .await
+            .unwrap();
+        assert_eq!(
+            map_lists[&fixture.r#match]
+                .iter()
+                .map(|map| map.map.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["de_inferno", "de_dust2", "de_nuke"]
+        );
+
+        let match_spectators = MatchSpectatorLoader::new(db.pool.clone())
+            .load(&[fixture.r#match, Uuid::new_v4()])
+            .await
+            .unwrap();
+        assert_eq!(match_spectators.len(), 1);
+        let mut spectators = match_spectators[&fixture.r#match]
+            .iter()
+            .map(|match_spectator| match_spectator.spectator_id)
+            .collect::<Vec<Uuid>>();
+        spectators.sort();
+        let mut expected = fixture.spectators.clone();
+        expected.sort();
+        assert_eq!(spectators, expected);
+    }
+
+    #[async_std::test]
+    async fn match_status_history() {
+        let db = TestDatabase::create().await;
+        let fixture = insert_fixture(&db.pool).await;
+        let mut conn = db.pool.acquire().await.unwrap();
+
+        let loaded = change_match_status(
+            &mut conn,
+            fixture.r#match,
+            MatchStatus::Warmup,
+            StatusTrigger::operator("loadMatch", "admin"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(loaded.from_status, MatchStatus::Pending);
+        change_match_status(
+            &mut conn,
+            fixture.r#match,
+            MatchStatus::Live,
+            StatusTrigger::log("going_live"),
+        )
+        .await
+        .unwrap();
+
+        // Neither illegal transitions nor repeated statuses are recorded
+        assert!(matches!(
+            change_match_status(
+                &mut conn,
+                fixture.r#match,
+                MatchStatus::Pending,
+                StatusTrigger::operator("loadMatch", "admin"),
+            )
+            .await,
+            Err(StatusError::IllegalTransition { .. })
+        ));
+        assert!(change_match_status(
+            &mut conn,
+            fixture.r#match,
+            MatchStatus::Live,
+            StatusTrigger::log("going_live"),
+        )
+        .await
+        .unwrap()
+        .is_none());
+        assert!(matches!(
+            change_match_status(
+                &mut conn,
+                Uuid::new_v4(),
+                MatchStatus::Warmup,
+                StatusTrigger::log("series_start"),
+            )
+            .await,
+            Err(StatusError::NoSuchMatch)
+        ));
+
+        let matches = MatchLoader::new(db.pool.clone())
+            .load(&[fixture.r#match])
+            .await
+            .unwrap();
+        assert_eq!(matches[&fixture.r#match].status, MatchStatus::Live);
+
+        let history = MatchStatusHistoryLoader::new(db.pool.clone())
+            .load(&[fixture.r#match])
+            .await
+            .unwrap();
+        let history = &history[&fixture.r#match];
+        assert_eq!(
+            history
+                .iter()
+                .map(|change| (change.from_status, change.to_status, change.source))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    MatchStatus::Pending,
+                    MatchStatus::Warmup,
+                    StatusSource::Operator
+                ),
+                (MatchStatus::Warmup, MatchStatus::Live, StatusSource::Log),
+            ]
+        );
+        assert_eq!(history[0].triggered_by.as_deref(), Some("admin"));
+        assert_eq!(history[1].event, "going_live");
+    }
+
+    #[async_std::test]
+    async fn loaders_of_statistics() {
+        let db = TestDatabase::create().await;
+        let fixture = insert_fixture(&db.pool).await;
+        let mut conn = db.pool.acquire().await.unwrap();
+
+        let stats = |map_number: i32, steamid: &str, kills: i32| {
+            PlayerMapStats::new(
+                fixture.r#match,
+                map_number,
+                steamid.to_string(),
+                "Player".to_string(),
+                &PlayerStats {
+                    kills,
+                    ..PlayerStats::default()
+                },
+                &UtilityStats::default(),
+            )
+        };
+        set_player_map_stats(
+            &mut conn,
+            fixture.r#match,
+            1,
+            &[stats(1, "STEAM_1:0:11", 7), stats(1, "STEAM_1:0:10", 12)],
+        )
+        .await
+        .unwrap();
+        set_player_map_stats(
+            &mut conn,
+            fixture.r#match,
+            0,
+            &[stats(0, "STEAM_1:0:10", 20)],
+        )
+        .await
+        .unwrap();
+
+        let player_stats = PlayerStatsLoader::new(db.pool.clone())
+            .load(&["STEAM_1:0:10".to_string(), "STEAM_1:0:99".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(player_stats.len(), 1);
+        assert_eq!(
+            player_stats["STEAM_1:0:10"]
+                .iter()
+                .map(|stats| (stats.map_number, stats.kills))
+                .collect::<Vec<_>>(),
+            vec![(0, 20), (1, 12)]
+        );
+
+        let match_stats = MatchStatsLoader::new(db.pool.clone())
+            .load(&[fixture.r#match, Uuid::new_v4()])
+            .await
+            .unwrap();
+        assert_eq!(match_stats.len(), 1);
+        assert_eq!(
+            match_stats[&fixture.r#match]
+                .iter()
+                .map(|stats| (stats.map_number, stats.steamid.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, "STEAM_1:0:10"),
+                (1, "STEAM_1:0:10"),
+                (1, "STEAM_1:0:11")
+            ]
+        );
+
+        let economy = |round: i32, side: GameSide, buy_type: BuyType| MatchRoundEconomy {
+            match_id: fixture.r#match,
+            map_number: 0,
+            round,
+            side,
+            starting_money: 4000,
+            money_spent: 3000,
+            equipment_value: 3500,
+            buy_type,
+        };
+        for round in 1..=2 {
+            save_round_economy(
+                &mut conn,
+                fixture.r#match,
+                0,
+                round,
+                &[
+                    economy(round, GameSide::T, BuyType::Eco),
+                    economy(round, GameSide::CT, BuyType::Full),
+                ],
+            )
+            .await
+            .unwrap();
+        }
+
+        let match_economy = MatchEconomyLoader::new(db.pool.clone())
+            .load(&[fixture.r#match, Uuid::new_v4()])
+            .await
+            .unwrap();
+        assert_eq!(match_economy.len(), 1);
+        assert_eq!(
+            match_economy[&fixture.r#match]
+                .iter()
+                .map(|side| (side.round, side.side, side.buy_type))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, GameSide::CT, BuyType::Full),
+                (1, GameSide::T, BuyType::Eco),
+                (2, GameSide::CT, BuyType::Full),
+                (2, GameSide::T, BuyType::Eco),
+            ]
+        );
+    }
+}
@@ -1,67 +1,164 @@
+use async_graphql::connection::{query, Connection, CursorType, Edge, EmptyFields};
 use async_graphql::dataloader::DataLoader;
 use async_graphql::extensions::Tracing;
-use async_graphql::{Context, EmptySubscription, Schema};
+use async_graphql::{Context, Schema};
+use async_std::stream::{Stream, StreamExt};
 use sqlx::types::Uuid;
 use sqlx::{Pool, Postgres};
 
 use dataloader::*;
 
-use crate::common::SideType;
+use crate::common::{
+    MatchActionKind, MatchOrder, MatchStatus, PlayerOrder, ServerOrder, SideType, TeamOrder,
+};
+use crate::csgo::cvars::{validate_cvar, CvarError};
+use crate::csgo::rcon::get5status::Get5Status as RconGet5Status;
+use crate::csgo::rcon::{
+    end_match, force_ready, list_backups, load_match, pause_match, restore_backup, skip_veto,
+    unpause_match, RCONError, RconManager,
+};
 use crate::database::models as db_models;
+use crate::database::{
+    add_match_action, count_matches, count_players, count_servers, count_teams, get_match,
+    get_match_actions, get_server, get_server_statuses, list_matches, list_players, list_servers,
+    list_teams, match_cursor, player_cursor, remove_match_cvars, remove_server_cvars,
+    server_cursor, set_match_cvars, set_match_server, set_server_cvars, team_cursor, Cursor,
+    Error as DatabaseError, Keyset,
+};
+use crate::events::{Event, EventBus};
+use crate::status::{
+    change_match_status, check_load, check_transition, StatusError, StatusTrigger,
+};
 use crate::web::graphql::types::*;
+use crate::web::token::{request_token, token_matches};
+use crate::web::{PublicUrl, State};
 use anyhow::anyhow;
+use chrono::Utc;
 use sqlx::types::ipnetwork::IpNetwork;
+use std::collections::HashMap;
 use std::str::FromStr;
 
 mod dataloader;
 mod types;
 
+/// Largest number of nodes in a page of a list query
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Opaque text of a cursor, the hex encoding of its id and key
+impl CursorType for Cursor {
+    type Error = anyhow::Error;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        let text = String::from_utf8(hex::decode(s)?)?;
+        let (id, key) = text
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid cursor"))?;
+
+        Ok(Cursor {
+            key: key.to_string(),
+            id: Uuid::parse_str(id)?,
+        })
+    }
+
+    fn encode_cursor(&self) -> String {
+        hex::encode(format!("{}:{}", self.id, self.key))
+    }
+}
+
+/// Page of a list selected by the pagination arguments of a connection, the cursors hold the
+/// sort key and the id of the nodes, so pages stay in place when rows are added or removed
+///
+/// Pages hold `MAX_PAGE_SIZE` nodes at most, also when `first` or `last` ask for more.
+#[derive(Debug)]
+struct Page {
+    /// Rows to fetch, one more than the page holds to tell whether the list goes on
+    keyset: Keyset,
+    size: usize,
+    /// Nodes kept from the end of the page, when both `first` and `last` are given
+    last: Option<usize>,
+}
+
+impl Page {
+    fn new(
+        after: Option<Cursor>,
+        before: Option<Cursor>,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Self {
+        let (size, from_end) = match (first, last) {
+            (Some(first), _) => (first, false),
+            (None, Some(last)) => (last, true),
+            (None, None) => (MAX_PAGE_SIZE, false),
+        };
+        let size = size.min(MAX_PAGE_SIZE);
+
+        Page {
+            keyset: Keyset {
+                after,
+                before,
+                limit: size as i64 + 1,
+                from_end,
+            },
+            size,
+            last: first.and(last),
+        }
+    }
+
+    /// Connection of the nodes fetched with the keyset, in the order they are listed
+    fn connection<T>(
+        &self,
+        nodes: Vec<(Cursor, T)>,
+        total: i64,
+    ) -> Connection<Cursor, T, TotalCount, EmptyFields> {
+        let (nodes, has_previous, has_next) = self.select(nodes);
+
+        let mut connection = Connection::with_additional_fields(
+            has_previous,
+            has_next,
+            TotalCount { total_count: total },
+        );
+        connection.append(
+            nodes
+                .into_iter()
+                .map(|(cursor, node)| Edge::new(cursor, node)),
+        );
+
+        connection
+    }
+
+    /// Nodes of the page among the fetched ones, and whether there are nodes before and after it
+    ///
+    /// Whether there are nodes before a page taken after a cursor, or after a page taken before
+    /// one, is not looked up: the node of the cursor is taken to still be there.
+    fn select<T>(&self, mut nodes: Vec<(Cursor, T)>) -> (Vec<(Cursor, T)>, bool, bool) {
+        let more = nodes.len() > self.size;
+        let (mut has_previous, has_next) = if self.keyset.from_end {
+            nodes.drain(..nodes.len().saturating_sub(self.size));
+            (more, self.keyset.before.is_some())
+        } else {
+            nodes.truncate(self.size);
+            (self.keyset.after.is_some(), more)
+        };
+        if let Some(last) = self.last {
+            if nodes.len() > last {
+                nodes.drain(..nodes.len() - last);
+                has_previous = true;
+            }
+        }
+
+        (nodes, has_previous, has_next)
+    }
+}
+
 pub(crate) struct Query;
 
 #[async_graphql::Object]
 impl Query {
     async fn teams(&self, ctx: &Context<'_>, ids: Vec<Uuid>) -> async_graphql::Result<Vec<Team>> {
-        let mut teams_raw = ctx
-            .data_unchecked::<DataLoader<TeamLoader>>()
-            .load_many(ids)
-            .await?;
+        let with_players = ctx.look_ahead().field("players").exists();
+        let mut teams = load_teams(ctx, ids.clone(), with_players).await?;
 
-        let team_ids = teams_raw.keys().cloned().collect::<Vec<Uuid>>();
-
-        let mut players_by_team = ctx
-            .data_unchecked::<DataLoader<PlayerTeamLoader>>()
-            .load_many(team_ids)
-            .await?;
-
-        let teams = teams_raw
-            .drain()
-            .map(|(_key, team)| {
-                let players = if ctx.look_ahead().field("players").exists() {
-                    players_by_team
-                        .remove(&team.id)
-                        .expect(format!("missing player for team (id={})", team.id).as_str())
-                        .drain(..)
-                        .map(|player| Player {
-                            steamid: player.steamid,
-                            name: player.name,
-                            tag: player.tag,
-                        })
-                        .collect()
-                } else {
-                    vec![]
-                };
-
-                Team {
-                    id: team.id,
-                    name: team.name,
-                    country: team.country,
-                    logo: team.logo,
-                    players,
-                }
-            })
-            .collect();
-
-        Ok(teams)
+        Ok(ids.iter().filter_map(|id| teams.remove(id)).collect())
     }
 
     async fn players(
@@ -69,7 +166,16 @@ impl Query {
         ctx: &Context<'_>,
         ids: Vec<Uuid>,
     ) -> async_graphql::Result<Vec<Player>> {
-        todo!()
+        let mut players = ctx
+            .data_unchecked::<DataLoader<PlayerLoader>>()
+            .load_many(ids.clone())
+            .await?;
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| players.remove(id))
+            .map(Player::from)
+            .collect())
     }
 
     async fn servers(
@@ -77,7 +183,16 @@ impl Query {
         ctx: &Context<'_>,
         ids: Vec<Uuid>,
     ) -> async_graphql::Result<Vec<Server>> {
-        todo!()
+        let mut servers = ctx
+            .data_unchecked::<DataLoader<ServerLoader>>()
+            .load_many(ids.clone())
+            .await?;
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| servers.remove(id))
+            .map(Server::from)
+            .collect())
     }
 
     async fn spectators(
@@ -85,7 +200,16 @@ impl Query {
         ctx: &Context<'_>,
         ids: Vec<Uuid>,
     ) -> async_graphql::Result<Vec<Spectator>> {
-        todo!()
+        let mut spectators = ctx
+            .data_unchecked::<DataLoader<SpectatorLoader>>()
+            .load_many(ids.clone())
+            .await?;
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| spectators.remove(id))
+            .map(Spectator::from)
+            .collect())
     }
 
     async fn r#match(
@@ -93,7 +217,231 @@ impl Query {
         ctx: &Context<'_>,
         ids: Vec<Uuid>,
     ) -> async_graphql::Result<Vec<r#Match>> {
-        todo!()
+        let mut matches = ctx
+            .data_unchecked::<DataLoader<MatchLoader>>()
+            .load_many(ids.clone())
+            .await?;
+        let matches = ids
+            .iter()
+            .filter_map(|id| matches.remove(id))
+            .collect::<Vec<db_models::Match>>();
+
+        let with_players = ctx.look_ahead().field("team1").field("players").exists()
+            || ctx.look_ahead().field("team2").field("players").exists();
+
+        build_matches(ctx, matches, with_players).await
+    }
+
+    /// Teams whose name contains `search`, a page at a time
+    #[allow(clippy::too_many_arguments)]
+    async fn all_teams(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        #[graphql(default_with = "TeamOrder::Name")] order_by: TeamOrder,
+        #[graphql(default)] descending: bool,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<Cursor, Team, TotalCount, EmptyFields>> {
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+                let search = search.as_deref();
+
+                let total = count_teams(&mut db_conn, search).await?;
+                let page = Page::new(after, before, first, last);
+                let teams =
+                    list_teams(&mut db_conn, search, order_by, descending, &page.keyset).await?;
+
+                let with_players = ctx
+                    .look_ahead()
+                    .field("edges")
+                    .field("node")
+                    .field("players")
+                    .exists();
+                let cursors = teams
+                    .iter()
+                    .map(|team| team_cursor(team, order_by))
+                    .collect::<Vec<Cursor>>();
+                let mut teams = teams_with_players(ctx, teams, with_players).await?;
+                let teams = cursors
+                    .into_iter()
+                    .filter_map(|cursor| Some((cursor.clone(), teams.remove(&cursor.id)?)))
+                    .collect();
+
+                Ok(page.connection(teams, total))
+            },
+        )
+        .await
+    }
+
+    /// Matches passing the filter, a page at a time
+    #[allow(clippy::too_many_arguments)]
+    async fn all_matches(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: MatchFilter,
+        #[graphql(default_with = "MatchOrder::CreatedAt")] order_by: MatchOrder,
+        #[graphql(default)] descending: bool,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<Cursor, Match, TotalCount, EmptyFields>> {
+        let filter = crate::database::MatchFilter::from(filter);
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+
+                let total = count_matches(&mut db_conn, &filter).await?;
+                let page = Page::new(after, before, first, last);
+                let matches =
+                    list_matches(&mut db_conn, &filter, order_by, descending, &page.keyset).await?;
+                let cursors = matches
+                    .iter()
+                    .map(|r#match| match_cursor(r#match, order_by))
+                    .collect::<Vec<Cursor>>();
+
+                let node = ctx.look_ahead().field("edges").field("node");
+                let with_players = node.field("team1").field("players").exists()
+                    || node.field("team2").field("players").exists();
+                let matches = build_matches(ctx, matches, with_players).await?;
+
+                Ok(page.connection(cursors.into_iter().zip(matches).collect(), total))
+            },
+        )
+        .await
+    }
+
+    /// Servers whose address or type contains `search`, a page at a time
+    #[allow(clippy::too_many_arguments)]
+    async fn all_servers(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        #[graphql(default_with = "ServerOrder::Host")] order_by: ServerOrder,
+        #[graphql(default)] descending: bool,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<Cursor, Server, TotalCount, EmptyFields>> {
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+                let search = search.as_deref();
+
+                let total = count_servers(&mut db_conn, search).await?;
+                let page = Page::new(after, before, first, last);
+                let servers =
+                    list_servers(&mut db_conn, search, order_by, descending, &page.keyset)
+                        .await?
+                        .into_iter()
+                        .map(|server| (server_cursor(&server, order_by), Server::from(server)))
+                        .collect();
+
+                Ok(page.connection(servers, total))
+            },
+        )
+        .await
+    }
+
+    /// Players whose name or Steam ID contains `search`, optionally of a single team, a page at
+    /// a time
+    #[allow(clippy::too_many_arguments)]
+    async fn all_players(
+        &self,
+        ctx: &Context<'_>,
+        search: Option<String>,
+        team: Option<Uuid>,
+        #[graphql(default_with = "PlayerOrder::Name")] order_by: PlayerOrder,
+        #[graphql(default)] descending: bool,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<Cursor, Player, TotalCount, EmptyFields>> {
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+                let search = search.as_deref();
+
+                let total = count_players(&mut db_conn, search, team).await?;
+                let page = Page::new(after, before, first, last);
+                let players = list_players(
+                    &mut db_conn,
+                    search,
+                    team,
+                    order_by,
+                    descending,
+                    &page.keyset,
+                )
+                .await?
+                .into_iter()
+                .map(|player| (player_cursor(&player, order_by), Player::from(player)))
+                .collect();
+
+                Ok(page.connection(players, total))
+            },
+        )
+        .await
+    }
+
+    /// Last known status of every server that has been polled
+    async fn server_statuses(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ServerStatus>> {
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let statuses = get_server_statuses(&mut db_conn)
+            .await?
+            .into_iter()
+            .map(ServerStatus::from)
+            .collect();
+
+        Ok(statuses)
+    }
+
+    /// Commands sent to the server of a match to control it, oldest first
+    async fn match_actions(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+    ) -> async_graphql::Result<Vec<MatchAction>> {
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let actions = get_match_actions(&mut db_conn, r#match)
+            .await?
+            .into_iter()
+            .map(MatchAction::from)
+            .collect();
+
+        Ok(actions)
+    }
+
+    /// Health of the RCON connections to the servers
+    async fn rcon_health(&self, ctx: &Context<'_>) -> Vec<RconHealth> {
+        ctx.data_unchecked::<RconManager>()
+            .health()
+            .await
+            .into_iter()
+            .map(RconHealth::from)
+            .collect()
     }
 }
 
@@ -101,6 +449,7 @@ pub(crate) struct Mutation;
 
 #[async_graphql::Object]
 impl Mutation {
+    #[allow(unused_variables)]
     async fn create_team(
         &self,
         name: String,
@@ -110,6 +459,7 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn update_team(
         &self,
         id: Uuid,
@@ -120,10 +470,12 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn delete_teams(&self, id: Vec<Uuid>) -> async_graphql::Result<Team> {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn add_players(
         &self,
         team: Uuid,
@@ -132,6 +484,7 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn update_player(
         &self,
         team: Uuid,
@@ -142,6 +495,7 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn remove_players(
         &self,
         team: Uuid,
@@ -172,14 +526,15 @@ impl Mutation {
             return Err(anyhow!("Port must be between 0 and 65536").into());
         }
 
-        let mut conn = ctx.data_unchecked::<Pool<Postgres>>();
+        let conn = ctx.data_unchecked::<Pool<Postgres>>();
         let new_server: db_models::Server = sqlx::query_as!(
             db_models::Server,
-            "INSERT INTO servers (host, port, type, password) VALUES ($1, $2, $3, $4) RETURNING *",
+            "INSERT INTO servers (host, port, type, password, log_secret) VALUES ($1, $2, $3, $4, $5) RETURNING *",
             host,
             server.port,
             server.r#type,
-            server.rcon_password
+            server.rcon_password,
+            server.log_secret
         )
         .fetch_one(conn)
         .await?;
@@ -187,6 +542,7 @@ impl Mutation {
         Ok(new_server.into())
     }
 
+    #[allow(unused_variables)]
     async fn update_server(
         &self,
         id: Uuid,
@@ -198,10 +554,12 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn delete_servers(&self, id: Vec<Uuid>) -> async_graphql::Result<Server> {
         todo!()
     }
 
+    #[allow(unused_variables, clippy::too_many_arguments)]
     async fn create_match(
         &self,
         server: Option<Uuid>,
@@ -220,6 +578,7 @@ impl Mutation {
 
     // TODO update functions for match
 
+    #[allow(unused_variables)]
     async fn set_map_list_for_match(
         &self,
         r#match: Uuid,
@@ -228,10 +587,333 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn delete_matches(&self, id: Vec<Uuid>) -> async_graphql::Result<Server> {
         todo!()
     }
 
+    /// Loads a match on an idle server, and assigns the match to the server
+    ///
+    /// The match goes to warmup, only pending and cancelled matches can be loaded. The match stays
+    /// assigned to its previous server if it could not be loaded.
+    ///
+    /// @return: the password players need to join the server
+    async fn load_match(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+        server: Uuid,
+    ) -> async_graphql::Result<LoadedMatch> {
+        authenticated_operator(ctx)?;
+
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let previous_server = match get_match(&mut db_conn, r#match).await? {
+            Some(loaded) => {
+                check_load(loaded.status)?;
+                loaded.server_id
+            }
+            None => return Err(anyhow!("No such match").into()),
+        };
+        if get_server(&mut db_conn, server).await?.is_none() {
+            return Err(anyhow!("No such server").into());
+        }
+
+        // Log entries of the server are attributed to the match it is assigned to, and the
+        // configuration Get5 downloads while loading holds the default cvars of the server
+        set_match_server(&mut db_conn, r#match, server).await?;
+
+        let PublicUrl(public_url) = ctx.data_unchecked::<PublicUrl>();
+        let url = format!("{}/api/get5/config?id={}", public_url, r#match);
+        let rcon = ctx.data_unchecked::<RconManager>();
+        let (player_password, _status) =
+            match load_match(url.as_str(), &mut rcon.server(server)).await {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    // The server is busy or did not load the match, it keeps its previous server
+                    set_match_server(&mut db_conn, r#match, previous_server).await?;
+                    return Err(err.into());
+                }
+            };
+        change_status(ctx, r#match, MatchStatus::Warmup, "loadMatch").await?;
+
+        Ok(LoadedMatch {
+            r#match,
+            server,
+            player_password,
+        })
+    }
+
+    /// Pauses a live match at the next freeze time
+    async fn pause_match(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+    ) -> async_graphql::Result<Get5Status> {
+        let server = match_server_for_status(ctx, r#match, MatchStatus::Paused).await?;
+        let result = pause_match(
+            r#match.to_string().as_str(),
+            &mut ctx.data_unchecked::<RconManager>().server(server),
+        )
+        .await;
+        record_match_action(ctx, r#match, server, MatchActionKind::Pause, None, &result).await?;
+        let status = result?;
+        change_status(ctx, r#match, MatchStatus::Paused, "pauseMatch").await?;
+
+        Ok(status.into())
+    }
+
+    /// Resumes a paused match
+    async fn unpause_match(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+    ) -> async_graphql::Result<Get5Status> {
+        let server = match_server_for_status(ctx, r#match, MatchStatus::Live).await?;
+        let result = unpause_match(
+            r#match.to_string().as_str(),
+            &mut ctx.data_unchecked::<RconManager>().server(server),
+        )
+        .await;
+        record_match_action(
+            ctx,
+            r#match,
+            server,
+            MatchActionKind::Unpause,
+            None,
+            &result,
+        )
+        .await?;
+        let status = result?;
+        change_status(ctx, r#match, MatchStatus::Live, "unpauseMatch").await?;
+
+        Ok(status.into())
+    }
+
+    /// Marks both teams ready, such that the veto or the map starts
+    async fn force_ready(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+    ) -> async_graphql::Result<Get5Status> {
+        let server = match_server(ctx, r#match).await?;
+        let result = force_ready(
+            r#match.to_string().as_str(),
+            &mut ctx.data_unchecked::<RconManager>().server(server),
+        )
+        .await;
+        record_match_action(
+            ctx,
+            r#match,
+            server,
+            MatchActionKind::ForceReady,
+            None,
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into())
+    }
+
+    /// Ends a match without a winner
+    async fn end_match(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+    ) -> async_graphql::Result<Get5Status> {
+        let server = match_server_for_status(ctx, r#match, MatchStatus::Cancelled).await?;
+        let result = end_match(
+            r#match.to_string().as_str(),
+            &mut ctx.data_unchecked::<RconManager>().server(server),
+        )
+        .await;
+        record_match_action(
+            ctx,
+            r#match,
+            server,
+            MatchActionKind::EndMatch,
+            None,
+            &result,
+        )
+        .await?;
+        let status = result?;
+        change_status(ctx, r#match, MatchStatus::Cancelled, "endMatch").await?;
+
+        Ok(status.into())
+    }
+
+    /// Restores a match from one of its backups, see `Match.backups`
+    ///
+    /// The match is loaded from the backup if the server is not playing it anymore, e.g. after a
+    /// crash.
+    async fn restore_backup(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+        backup: String,
+    ) -> async_graphql::Result<Get5Status> {
+        let server = match_server(ctx, r#match).await?;
+        let mut rcon = ctx.data_unchecked::<RconManager>().server(server);
+
+        // Only backups of the match can be restored
+        let backups = list_backups(r#match.to_string().as_str(), &mut rcon).await?;
+        if !backups.iter().any(|listed| listed.file == backup) {
+            return Err(anyhow!("No such backup").into());
+        }
+
+        let result = restore_backup(r#match.to_string().as_str(), backup.as_str(), &mut rcon).await;
+        record_match_action(
+            ctx,
+            r#match,
+            server,
+            MatchActionKind::RestoreBackup,
+            Some(backup),
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into())
+    }
+
+    /// Plays the maps in the order of the map list, instead of vetoing them
+    async fn skip_veto(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+    ) -> async_graphql::Result<Get5Status> {
+        let server = match_server(ctx, r#match).await?;
+        let result = skip_veto(
+            r#match.to_string().as_str(),
+            &mut ctx.data_unchecked::<RconManager>().server(server),
+        )
+        .await;
+        record_match_action(
+            ctx,
+            r#match,
+            server,
+            MatchActionKind::SkipVeto,
+            None,
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into())
+    }
+
+    /// Sets cvars of a match, overriding the defaults of its server
+    ///
+    /// @return: every cvar of the match
+    async fn set_match_cvars(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+        cvars: Vec<CvarInput>,
+    ) -> async_graphql::Result<Vec<Cvar>> {
+        let server = match_server(ctx, r#match).await?;
+        let cvars = validate_cvars(cvars)?;
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let result = set_match_cvars(&mut db_conn, r#match, &cvars).await;
+        let argument = cvar_list(&cvars);
+        record_cvars_action(
+            ctx,
+            Some(r#match),
+            server,
+            MatchActionKind::SetCvars,
+            argument,
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into_iter().map(Cvar::from).collect())
+    }
+
+    /// Removes cvars of a match, the defaults of its server apply again
+    ///
+    /// @return: the remaining cvars of the match
+    async fn remove_match_cvars(
+        &self,
+        ctx: &Context<'_>,
+        r#match: Uuid,
+        names: Vec<String>,
+    ) -> async_graphql::Result<Vec<Cvar>> {
+        let server = match_server(ctx, r#match).await?;
+        let names = names
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect::<Vec<_>>();
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let result = remove_match_cvars(&mut db_conn, r#match, &names).await;
+        let argument = names.join(" ");
+        record_cvars_action(
+            ctx,
+            Some(r#match),
+            server,
+            MatchActionKind::RemoveCvars,
+            argument,
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into_iter().map(Cvar::from).collect())
+    }
+
+    /// Sets default cvars for the matches played on a server
+    ///
+    /// @return: every default cvar of the server
+    async fn set_server_cvars(
+        &self,
+        ctx: &Context<'_>,
+        server: Uuid,
+        cvars: Vec<CvarInput>,
+    ) -> async_graphql::Result<Vec<Cvar>> {
+        existing_server(ctx, server).await?;
+        let cvars = validate_cvars(cvars)?;
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let result = set_server_cvars(&mut db_conn, server, &cvars).await;
+        let argument = cvar_list(&cvars);
+        record_cvars_action(
+            ctx,
+            None,
+            server,
+            MatchActionKind::SetCvars,
+            argument,
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into_iter().map(Cvar::from).collect())
+    }
+
+    /// Removes default cvars of a server
+    ///
+    /// @return: the remaining default cvars of the server
+    async fn remove_server_cvars(
+        &self,
+        ctx: &Context<'_>,
+        server: Uuid,
+        names: Vec<String>,
+    ) -> async_graphql::Result<Vec<Cvar>> {
+        existing_server(ctx, server).await?;
+        let names = names
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect::<Vec<_>>();
+        let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+        let result = remove_server_cvars(&mut db_conn, server, &names).await;
+        let argument = names.join(" ");
+        record_cvars_action(
+            ctx,
+            None,
+            server,
+            MatchActionKind::RemoveCvars,
+            argument,
+            &result,
+        )
+        .await?;
+
+        Ok(result?.into_iter().map(Cvar::from).collect())
+    }
+
+    #[allow(unused_variables)]
     async fn create_spectator(
         &self,
         steamid: String,
@@ -240,6 +922,7 @@ impl Mutation {
         todo!()
     }
 
+    #[allow(unused_variables)]
     async fn delete_spectators(&self, steamid: Vec<String>) -> async_graphql::Result<Spectator> {
         todo!()
     }
@@ -247,6 +930,7 @@ impl Mutation {
     /// Add a spectator to a match
     ///
     /// @return: true if the spectator was not already attached to the match
+    #[allow(unused_variables)]
     async fn attach_spectators(
         &self,
         r#match: Uuid,
@@ -258,6 +942,7 @@ impl Mutation {
     /// Removes a spectator from a match
     ///
     /// @return: true if the spectator was not already detached from the match
+    #[allow(unused_variables)]
     async fn detach_spectators(
         &self,
         r#match: Uuid,
@@ -267,13 +952,484 @@ impl Mutation {
     }
 }
 
-pub(crate) fn init_schema(db_pool: Pool<Postgres>) -> Schema<Query, Mutation, EmptySubscription> {
-    Schema::build(Query, Mutation, EmptySubscription)
+/// Live state of the matches and servers, published by the log ingest and the status poller
+pub(crate) struct Subscription;
+
+#[async_graphql::Subscription]
+impl Subscription {
+    /// Progress of a match: the map being played, its score, and the end of the series
+    async fn match_updated(&self, ctx: &Context<'_>, id: Uuid) -> impl Stream<Item = MatchUpdate> {
+        subscribe(ctx, move |event| match event {
+            Event::MatchUpdated(update) if update.match_id == id => Some(update.into()),
+            _ => None,
+        })
+    }
+
+    /// Status of a server, every time it is polled
+    async fn server_status(&self, ctx: &Context<'_>, id: Uuid) -> impl Stream<Item = ServerStatus> {
+        subscribe(ctx, move |event| match event {
+            Event::ServerStatus(status) if status.server_id == id => Some(status.into()),
+            _ => None,
+        })
+    }
+
+    /// Kills in a match, as they happen
+    async fn kill_feed(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "match")] match_id: Uuid,
+    ) -> impl Stream<Item = Kill> {
+        subscribe(ctx, move |event| match event {
+            Event::Kill(kill) if kill.combat.match_id == match_id => Some(kill.into()),
+            _ => None,
+        })
+    }
+
+    /// Rounds of a match, once they are over
+    async fn round_ended(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "match")] match_id: Uuid,
+    ) -> impl Stream<Item = Round> {
+        subscribe(ctx, move |event| match event {
+            Event::RoundEnded(round) if round.match_id == match_id => Some(round.into()),
+            _ => None,
+        })
+    }
+}
+
+/// Subscribes to the event bus, and keeps the events picked by `filter`
+fn subscribe<T, F>(ctx: &Context<'_>, filter: F) -> impl Stream<Item = T>
+where
+    F: FnMut(Event) -> Option<T>,
+{
+    ctx.data_unchecked::<EventBus>()
+        .subscribe()
+        .filter_map(filter)
+}
+
+/// Fills in the server, teams, maps and spectators of the matches, in batches
+async fn build_matches(
+    ctx: &Context<'_>,
+    matches: Vec<db_models::Match>,
+    with_players: bool,
+) -> async_graphql::Result<Vec<Match>> {
+    let match_ids = matches.iter().map(|r#match| r#match.id).collect::<Vec<_>>();
+    let server_ids = matches.iter().map(|r#match| r#match.server_id);
+    let team_ids = matches
+        .iter()
+        .flat_map(|r#match| vec![r#match.team1_id, r#match.team2_id])
+        .collect::<Vec<_>>();
+
+    let mut servers = ctx
+        .data_unchecked::<DataLoader<ServerLoader>>()
+        .load_many(server_ids)
+        .await?;
+    let teams = load_teams(ctx, team_ids, with_players).await?;
+    let mut map_lists = ctx
+        .data_unchecked::<DataLoader<MapListLoader>>()
+        .load_many(match_ids.clone())
+        .await?;
+    let mut match_spectators = ctx
+        .data_unchecked::<DataLoader<MatchSpectatorLoader>>()
+        .load_many(match_ids)
+        .await?;
+    let spectators = ctx
+        .data_unchecked::<DataLoader<SpectatorLoader>>()
+        .load_many(
+            match_spectators
+                .values()
+                .flatten()
+                .map(|match_spectator| match_spectator.spectator_id),
+        )
+        .await?;
+
+    let team = |id: Uuid| {
+        teams
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("missing team (id={})", id))
+    };
+
+    let mut result = Vec::with_capacity(matches.len());
+    for r#match in matches {
+        let spectators = match_spectators
+            .remove(&r#match.id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|match_spectator| spectators.get(&match_spectator.spectator_id))
+            .cloned()
+            .map(Spectator::from)
+            .collect();
+
+        result.push(Match {
+            id: r#match.id,
+            server: servers.remove(&r#match.server_id).map(Server::from),
+            team1: team(r#match.team1_id)?,
+            team2: team(r#match.team2_id)?,
+            team1_score: r#match.team1_score,
+            team2_score: r#match.team2_score,
+            team1_series_score: r#match.team1_series_score,
+            team2_series_score: r#match.team2_series_score,
+            num_maps: r#match.num_maps,
+            skip_veto: r#match.skip_veto,
+            veto_first: r#match.veto_first,
+            players_per_team: r#match.players_per_team,
+            min_player_to_ready: r#match.min_player_to_ready,
+            created_at: r#match.created_at,
+            status: r#match.status,
+            maps: map_lists
+                .remove(&r#match.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|map| map.map)
+                .collect(),
+            spectators,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Loads teams by id, with their players if `with_players` is set
+async fn load_teams(
+    ctx: &Context<'_>,
+    ids: Vec<Uuid>,
+    with_players: bool,
+) -> async_graphql::Result<HashMap<Uuid, Team>> {
+    let teams = ctx
+        .data_unchecked::<DataLoader<TeamLoader>>()
+        .load_many(ids)
+        .await?
+        .into_values()
+        .collect();
+
+    teams_with_players(ctx, teams, with_players).await
+}
+
+/// Converts the teams, and loads their players if `with_players` is set
+async fn teams_with_players(
+    ctx: &Context<'_>,
+    teams: Vec<db_models::Team>,
+    with_players: bool,
+) -> async_graphql::Result<HashMap<Uuid, Team>> {
+    let mut players_by_team = if with_players {
+        ctx.data_unchecked::<DataLoader<PlayerTeamLoader>>()
+            .load_many(teams.iter().map(|team| team.id))
+            .await?
+    } else {
+        HashMap::new()
+    };
+
+    let teams = teams
+        .into_iter()
+        .map(|team| {
+            let players = players_by_team
+                .remove(&team.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(Player::from)
+                .collect();
+
+            let team = Team {
+                id: team.id,
+                name: team.name,
+                country: team.country,
+                logo: team.logo,
+                players,
+            };
+            (team.id, team)
+        })
+        .collect();
+
+    Ok(teams)
+}
+
+/// Token an operator authenticates with, as a bearer token of the GraphQL requests
+#[derive(Clone, Debug)]
+pub struct OperatorToken {
+    /// Name of the operator, recorded along the match actions it triggers
+    pub name: String,
+    pub token: String,
+}
+
+/// Operator who sent a GraphQL request, recorded along the match actions it triggers
+///
+/// The operator is authenticated by one of the `OperatorToken`s, only authenticated operators
+/// can send commands to the servers of the matches.
+pub(crate) struct Operator(pub String);
+
+impl Operator {
+    fn from_request(req: &tide::Request<State>) -> tide::Result<Option<Self>> {
+        let token = match request_token(req)? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        let operator = req
+            .state()
+            .operators
+            .iter()
+            .find(|operator| token_matches(token.as_str(), operator.token.as_str()))
+            .map(|operator| Operator(operator.name.clone()));
+        Ok(operator)
+    }
+}
+
+pub(crate) type MinictrlSchema = Schema<Query, Mutation, Subscription>;
+
+/// Executes a GraphQL request, with the `Operator` who sent it
+pub(crate) async fn endpoint_graphql(
+    schema: MinictrlSchema,
+    req: tide::Request<State>,
+) -> tide::Result {
+    let operator = Operator::from_request(&req)?;
+    let mut request = async_graphql_tide::receive_request(req).await?;
+    if let Some(operator) = operator {
+        request = request.data(operator);
+    }
+
+    async_graphql_tide::respond(schema.execute(request).await)
+}
+
+/// Checks the cvars can be passed on to Get5, and normalizes their names
+fn validate_cvars(cvars: Vec<CvarInput>) -> async_graphql::Result<Vec<db_models::Cvar>> {
+    let cvars = cvars
+        .into_iter()
+        .map(|cvar| {
+            Ok(db_models::Cvar {
+                name: validate_cvar(cvar.name.as_str(), cvar.value.as_str())?,
+                value: cvar.value,
+            })
+        })
+        .collect::<Result<Vec<db_models::Cvar>, CvarError>>()?;
+
+    Ok(cvars)
+}
+
+/// Cvars as the server reads them, e.g. `mp_maxrounds 24; tv_delay 90`
+fn cvar_list(cvars: &[db_models::Cvar]) -> String {
+    cvars
+        .iter()
+        .map(|cvar| format!("{} {}", cvar.name, cvar.value))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+/// Name of the operator who sent the request, fails if the operator is not authenticated
+fn authenticated_operator<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a str> {
+    match ctx.data_opt::<Operator>() {
+        Some(Operator(operator)) => Ok(operator.as_str()),
+        None => Err(anyhow!("Only authenticated operators can control matches and servers").into()),
+    }
+}
+
+/// Server a match is assigned to, for a command sent by an authenticated operator
+async fn match_server(ctx: &Context<'_>, match_id: Uuid) -> async_graphql::Result<Uuid> {
+    authenticated_operator(ctx)?;
+
+    let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+    match get_match(&mut db_conn, match_id).await? {
+        Some(r#match) => Ok(r#match.server_id),
+        None => Err(anyhow!("No such match").into()),
+    }
+}
+
+/// Checks that a server exists, for a change made by an authenticated operator
+async fn existing_server(ctx: &Context<'_>, server_id: Uuid) -> async_graphql::Result<()> {
+    authenticated_operator(ctx)?;
+
+    let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+    match get_server(&mut db_conn, server_id).await? {
+        Some(_) => Ok(()),
+        None => Err(anyhow!("No such server").into()),
+    }
+}
+
+/// Server a match is assigned to, checking that the status of the match may change to `status`
+/// before a command is sent to the server
+async fn match_server_for_status(
+    ctx: &Context<'_>,
+    match_id: Uuid,
+    status: MatchStatus,
+) -> async_graphql::Result<Uuid> {
+    authenticated_operator(ctx)?;
+
+    let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+    match get_match(&mut db_conn, match_id).await? {
+        Some(r#match) => {
+            check_transition(r#match.status, status)?;
+            Ok(r#match.server_id)
+        }
+        None => Err(anyhow!("No such match").into()),
+    }
+}
+
+/// Changes the status of a match on behalf of the operator, once the command was sent
+async fn change_status(
+    ctx: &Context<'_>,
+    match_id: Uuid,
+    status: MatchStatus,
+    mutation: &str,
+) -> async_graphql::Result<()> {
+    let operator = authenticated_operator(ctx)?;
+    let trigger = StatusTrigger::operator(mutation, operator);
+
+    let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+    match change_match_status(&mut db_conn, match_id, status, trigger).await {
+        Ok(_) => Ok(()),
+        // The status was changed while the command was sent, e.g. the series ended
+        Err(err @ StatusError::IllegalTransition { .. }) => {
+            warn!("match (id={}) after {}: {}", match_id, mutation, err);
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Records a command sent to the server of a match in the audit log, whether it succeeded or not
+async fn record_match_action(
+    ctx: &Context<'_>,
+    match_id: Uuid,
+    server_id: Uuid,
+    action: MatchActionKind,
+    argument: Option<String>,
+    result: &Result<RconGet5Status, RCONError>,
+) -> async_graphql::Result<()> {
+    let error = result.as_ref().err().map(RCONError::to_string);
+    record_action(ctx, Some(match_id), server_id, action, argument, error).await
+}
+
+/// Records a change of the cvars of a match, or of the defaults of a server if `match_id` is
+/// `None`, in the audit log, whether it succeeded or not
+async fn record_cvars_action<T>(
+    ctx: &Context<'_>,
+    match_id: Option<Uuid>,
+    server_id: Uuid,
+    action: MatchActionKind,
+    argument: String,
+    result: &Result<T, DatabaseError>,
+) -> async_graphql::Result<()> {
+    let error = result.as_ref().err().map(DatabaseError::to_string);
+    record_action(ctx, match_id, server_id, action, Some(argument), error).await
+}
+
+async fn record_action(
+    ctx: &Context<'_>,
+    match_id: Option<Uuid>,
+    server_id: Uuid,
+    action: MatchActionKind,
+    argument: Option<String>,
+    error: Option<String>,
+) -> async_graphql::Result<()> {
+    let operator = authenticated_operator(ctx)?;
+    let action = db_models::MatchAction {
+        id: 0,
+        match_id,
+        server_id,
+        action,
+        argument,
+        triggered_by: operator.to_string(),
+        triggered_at: Utc::now().naive_utc(),
+        error,
+    };
+
+    let mut db_conn = ctx.data_unchecked::<Pool<Postgres>>().acquire().await?;
+    add_match_action(&mut db_conn, &action).await?;
+
+    Ok(())
+}
+
+pub(crate) fn init_schema(
+    db_pool: Pool<Postgres>,
+    rcon: RconManager,
+    events: EventBus,
+    public_url: PublicUrl,
+) -> MinictrlSchema {
+    Schema::build(Query, Mutation, Subscription)
         .data(DataLoader::new(TeamLoader::new(db_pool.clone())))
         .data(DataLoader::new(MatchLoader::new(db_pool.clone())))
         .data(DataLoader::new(PlayerLoader::new(db_pool.clone())))
         .data(DataLoader::new(PlayerTeamLoader::new(db_pool.clone())))
+        .data(DataLoader::new(PlayerStatsLoader::new(db_pool.clone())))
+        .data(DataLoader::new(MatchStatsLoader::new(db_pool.clone())))
+        .data(DataLoader::new(MatchEconomyLoader::new(db_pool.clone())))
+        .data(DataLoader::new(ServerLoader::new(db_pool.clone())))
+        .data(DataLoader::new(SpectatorLoader::new(db_pool.clone())))
+        .data(DataLoader::new(MapListLoader::new(db_pool.clone())))
+        .data(DataLoader::new(MatchSpectatorLoader::new(db_pool.clone())))
+        .data(DataLoader::new(MatchStatusHistoryLoader::new(
+            db_pool.clone(),
+        )))
         .data(db_pool)
+        .data(rcon)
+        .data(events)
+        .data(public_url)
         .extension(Tracing)
         .finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::CursorType;
+    use sqlx::types::Uuid;
+
+    use super::{Cursor, Page, MAX_PAGE_SIZE};
+
+    fn cursor(key: &str, id: u128) -> Cursor {
+        Cursor {
+            key: key.to_string(),
+            id: Uuid::from_u128(id),
+        }
+    }
+
+    /// Nodes as fetched for the page, with one more than the page holds if the list goes on
+    fn select(page: &Page, fetched: u128) -> (Vec<u128>, bool, bool) {
+        let nodes = (1..=fetched).map(|id| (cursor("team", id), id)).collect();
+        let (nodes, has_previous, has_next) = page.select(nodes);
+
+        (
+            nodes.into_iter().map(|(_, id)| id).collect(),
+            has_previous,
+            has_next,
+        )
+    }
+
+    #[test]
+    fn cursors() {
+        let cursor = cursor("team: one", 7);
+        let encoded = cursor.encode_cursor();
+
+        assert_eq!(Cursor::decode_cursor(&encoded).unwrap(), cursor);
+        assert!(Cursor::decode_cursor("7").is_err());
+        assert!(Cursor::decode_cursor(&hex::encode("7:team")).is_err());
+    }
+
+    #[test]
+    fn pagination() {
+        // Forwards
+        let page = Page::new(None, None, Some(10), None);
+        assert_eq!((page.keyset.limit, page.keyset.from_end), (11, false));
+        assert_eq!(select(&page, 11), ((1..=10).collect(), false, true));
+        assert_eq!(select(&page, 5), ((1..=5).collect(), false, false));
+
+        let page = Page::new(Some(cursor("team", 0)), None, Some(10), None);
+        assert_eq!(select(&page, 5), ((1..=5).collect(), true, false));
+
+        // Backwards, the nodes are fetched from the end but listed in order
+        let page = Page::new(None, Some(cursor("team", 20)), None, Some(10));
+        assert_eq!((page.keyset.limit, page.keyset.from_end), (11, true));
+        assert_eq!(select(&page, 11), ((2..=11).collect(), true, true));
+        assert_eq!(select(&page, 3), ((1..=3).collect(), false, true));
+
+        // Last nodes of the first ones
+        let page = Page::new(None, None, Some(10), Some(3));
+        assert_eq!(select(&page, 11), ((8..=10).collect(), true, true));
+
+        // Pages are capped
+        let page = Page::new(None, None, None, None);
+        assert_eq!(page.keyset.limit, MAX_PAGE_SIZE as i64 + 1);
+        let page = Page::new(None, None, Some(1000), None);
+        assert_eq!(page.keyset.limit, MAX_PAGE_SIZE as i64 + 1);
+    }
+}
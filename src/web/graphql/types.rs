@@ -1,10 +1,21 @@
-use async_graphql::{InputObject, SimpleObject};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{ComplexObject, Context, InputObject, SimpleObject};
+use chrono::NaiveDateTime;
 use sqlx::types::Uuid;
 
-use crate::common::SideType;
+use crate::common::{
+    BuyType, GameSide, Get5State, MatchActionKind, MatchStatus, SideType, StatusSource,
+};
+use crate::csgo::rcon::Get5Backup as RconGet5Backup;
+use crate::csgo::rcon::{get5status, list_backups, RconManager, ServerHealth};
+use crate::csgo::{stats, utility};
 use crate::database;
+use crate::events;
+use crate::web::graphql::dataloader::{
+    MatchEconomyLoader, MatchStatsLoader, MatchStatusHistoryLoader, PlayerStatsLoader,
+};
 
-#[derive(SimpleObject)]
+#[derive(SimpleObject, Clone)]
 pub struct Team {
     pub id: Uuid,
     pub name: String,
@@ -13,13 +24,42 @@ pub struct Team {
     pub players: Vec<Player>,
 }
 
-#[derive(SimpleObject)]
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
 pub struct Player {
     pub steamid: String,
     pub name: Option<String>,
     pub tag: Option<String>,
 }
 
+impl From<database::models::Player> for Player {
+    fn from(player: database::models::Player) -> Self {
+        Player {
+            steamid: player.steamid,
+            name: player.name,
+            tag: player.tag,
+        }
+    }
+}
+
+#[ComplexObject]
+impl Player {
+    /// Statistics of the player in every match played, per series or per map
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] per_map: bool,
+    ) -> async_graphql::Result<Vec<PlayerStats>> {
+        let map_stats = ctx
+            .data_unchecked::<DataLoader<PlayerStatsLoader>>()
+            .load_one(self.steamid.clone())
+            .await?
+            .unwrap_or_default();
+
+        Ok(PlayerStats::from_map_stats(map_stats, per_map))
+    }
+}
+
 #[derive(SimpleObject)]
 pub struct Server {
     pub id: Uuid,
@@ -41,34 +81,685 @@ impl From<crate::database::models::Server> for Server {
     }
 }
 
+/// Last known status of a server
+#[derive(SimpleObject)]
+pub struct ServerStatus {
+    pub server_id: Uuid,
+    pub reachable: bool,
+    /// Why the last poll failed
+    pub error: Option<String>,
+    pub polled_at: NaiveDateTime,
+    /// State of Get5 as of the last successful poll, null if Get5 did not reply
+    pub state: Option<Get5State>,
+    pub matchid: Option<String>,
+    pub map_number: Option<i32>,
+    pub paused: Option<bool>,
+    pub plugin_version: Option<String>,
+    pub team1_connected: Option<i32>,
+    pub team1_ready: Option<bool>,
+    pub team2_connected: Option<i32>,
+    pub team2_ready: Option<bool>,
+}
+
+impl From<database::models::ServerStatus> for ServerStatus {
+    fn from(status: database::models::ServerStatus) -> Self {
+        ServerStatus {
+            server_id: status.server_id,
+            reachable: status.reachable,
+            error: status.error,
+            polled_at: status.polled_at,
+            state: status.state,
+            matchid: status.matchid,
+            map_number: status.map_number,
+            paused: status.paused,
+            plugin_version: status.plugin_version,
+            team1_connected: status.team1_connected,
+            team1_ready: status.team1_ready,
+            team2_connected: status.team2_connected,
+            team2_ready: status.team2_ready,
+        }
+    }
+}
+
+/// State of Get5 on a server, as reported by `get5_status`
+#[derive(SimpleObject)]
+pub struct Get5Status {
+    pub state: Get5State,
+    pub plugin_version: String,
+    pub paused: bool,
+    /// Null if no match is loaded
+    pub matchid: Option<String>,
+    pub map_number: Option<i32>,
+    /// Maps of the series in the order they are played, empty until the veto is over
+    pub maps: Vec<String>,
+    pub team1: Option<Get5Team>,
+    pub team2: Option<Get5Team>,
+}
+
+impl From<get5status::Get5Status> for Get5Status {
+    fn from(status: get5status::Get5Status) -> Self {
+        let (team1, team2) = match status.teams() {
+            Some((team1, team2)) => (Some(team1.into()), Some(team2.into())),
+            None => (None, None),
+        };
+
+        Get5Status {
+            state: status.state(),
+            plugin_version: status.plugin_version().to_string(),
+            paused: status.paused(),
+            matchid: status.matchid().map(str::to_string),
+            map_number: status.map_number(),
+            maps: status.maps().to_vec(),
+            team1,
+            team2,
+        }
+    }
+}
+
+/// Backup Get5 wrote at the start of a round, see `Mutation::restore_backup`
+#[derive(SimpleObject)]
+pub struct Get5Backup {
+    /// Name of the backup on the server
+    pub file: String,
+    /// Zero-based index of the map in the series
+    pub map_number: i32,
+    /// Round the backup restores the map to
+    pub round: i32,
+    /// Local time of the server when the backup was written
+    pub timestamp: Option<NaiveDateTime>,
+    pub map_name: Option<String>,
+    pub team1_score: Option<i32>,
+    pub team2_score: Option<i32>,
+}
+
+impl From<RconGet5Backup> for Get5Backup {
+    fn from(backup: RconGet5Backup) -> Self {
+        Get5Backup {
+            file: backup.file,
+            map_number: backup.map_number,
+            round: backup.round,
+            timestamp: backup.timestamp,
+            map_name: backup.map_name,
+            team1_score: backup.team1_score,
+            team2_score: backup.team2_score,
+        }
+    }
+}
+
+/// Team of the match loaded in Get5
+#[derive(SimpleObject)]
+pub struct Get5Team {
+    pub name: String,
+    pub side: GameSide,
+    pub connected_clients: i32,
+    pub ready: bool,
+    pub series_score: i32,
+    pub current_map_score: i32,
+}
+
+impl From<&get5status::Team> for Get5Team {
+    fn from(team: &get5status::Team) -> Self {
+        Get5Team {
+            name: team.name().to_string(),
+            side: match team.side() {
+                get5status::Side::CT => GameSide::CT,
+                get5status::Side::T => GameSide::T,
+            },
+            connected_clients: team.connected_clients(),
+            ready: team.ready(),
+            series_score: team.series_score(),
+            current_map_score: team.current_map_score(),
+        }
+    }
+}
+
+/// Command sent to a server to control a match, and who triggered it
+#[derive(SimpleObject)]
+pub struct MatchAction {
+    pub action: MatchActionKind,
+    /// Argument of the command, e.g. the restored backup
+    pub argument: Option<String>,
+    pub server_id: Uuid,
+    /// Name of the authenticated operator who sent the command
+    pub triggered_by: String,
+    pub triggered_at: NaiveDateTime,
+    /// Why the command failed, null if it succeeded
+    pub error: Option<String>,
+}
+
+impl From<database::models::MatchAction> for MatchAction {
+    fn from(action: database::models::MatchAction) -> Self {
+        MatchAction {
+            action: action.action,
+            argument: action.argument,
+            server_id: action.server_id,
+            triggered_by: action.triggered_by,
+            triggered_at: action.triggered_at,
+            error: action.error,
+        }
+    }
+}
+
+/// Health of the RCON connection to a server
+#[derive(SimpleObject)]
+pub struct RconHealth {
+    pub server_id: Uuid,
+    /// An authenticated connection is open
+    pub connected: bool,
+    /// Failures since the last successful command
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// Time of the last successful command
+    pub last_success: Option<NaiveDateTime>,
+}
+
+impl From<ServerHealth> for RconHealth {
+    fn from(health: ServerHealth) -> Self {
+        RconHealth {
+            server_id: health.server_id,
+            connected: health.connected,
+            consecutive_failures: health.consecutive_failures,
+            last_error: health.last_error,
+            last_success: health.last_success,
+        }
+    }
+}
+
+/// Cvar set by Get5 when a match is loaded
+#[derive(SimpleObject)]
+pub struct Cvar {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(InputObject)]
+pub struct CvarInput {
+    pub name: String,
+    pub value: String,
+}
+
+impl From<database::models::Cvar> for Cvar {
+    fn from(cvar: database::models::Cvar) -> Self {
+        Cvar {
+            name: cvar.name,
+            value: cvar.value,
+        }
+    }
+}
+
 #[derive(InputObject)]
 pub struct ServerInput {
     pub host: String,
     pub port: i32,
     pub r#type: Option<String>,
     pub rcon_password: String,
+    /// Value of `sv_logsecret` on the server, log lines sent over UDP without it are dropped
+    pub log_secret: Option<String>,
 }
 
 #[derive(SimpleObject)]
+#[graphql(complex)]
 pub struct Match {
     pub id: Uuid,
     pub server: Option<Server>,
     pub team1: Team,
     pub team2: Team,
+    /// Rounds won by team 1 on the current map
     pub team1_score: Option<i32>,
+    /// Rounds won by team 2 on the current map
     pub team2_score: Option<i32>,
+    /// Maps won by team 1 in the series
+    pub team1_series_score: i32,
+    /// Maps won by team 2 in the series
+    pub team2_series_score: i32,
     pub num_maps: i32,
     pub skip_veto: bool,
     pub veto_first: SideType,
     pub players_per_team: i32,
     pub min_player_to_ready: i32,
+    pub created_at: NaiveDateTime,
+    pub status: MatchStatus,
     pub maps: Vec<String>,
     pub spectators: Vec<Spectator>,
 }
 
+#[ComplexObject]
+impl Match {
+    /// Statistics of the players in the match, per series or per map
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] per_map: bool,
+    ) -> async_graphql::Result<Vec<PlayerStats>> {
+        let map_stats = ctx
+            .data_unchecked::<DataLoader<MatchStatsLoader>>()
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default();
+
+        Ok(PlayerStats::from_map_stats(map_stats, per_map))
+    }
+
+    /// Backups Get5 wrote for the match on its server, ordered by map and round
+    async fn backups(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Get5Backup>> {
+        let server = match &self.server {
+            Some(server) => server.id,
+            None => return Ok(vec![]),
+        };
+
+        let backups = list_backups(
+            self.id.to_string().as_str(),
+            &mut ctx.data_unchecked::<RconManager>().server(server),
+        )
+        .await?
+        .into_iter()
+        .map(Get5Backup::from)
+        .collect();
+
+        Ok(backups)
+    }
+
+    /// Economy of both sides in every round played, optionally on a single map
+    async fn economy(
+        &self,
+        ctx: &Context<'_>,
+        map_number: Option<i32>,
+    ) -> async_graphql::Result<Vec<RoundEconomy>> {
+        let economy = ctx
+            .data_unchecked::<DataLoader<MatchEconomyLoader>>()
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|side| map_number.is_none_or(|map_number| side.map_number == map_number))
+            .map(RoundEconomy::from)
+            .collect();
+
+        Ok(economy)
+    }
+
+    /// Every change of the status of the match, oldest first
+    async fn history(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<StatusChange>> {
+        let history = ctx
+            .data_unchecked::<DataLoader<MatchStatusHistoryLoader>>()
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(StatusChange::from)
+            .collect();
+
+        Ok(history)
+    }
+}
+
+/// Change of the status of a match, and what caused it
+#[derive(SimpleObject)]
+pub struct StatusChange {
+    #[graphql(name = "from")]
+    pub from_status: MatchStatus,
+    #[graphql(name = "to")]
+    pub to_status: MatchStatus,
+    pub source: StatusSource,
+    /// What caused the change, e.g. the mutation, the Get5 event or the polled state
+    pub event: String,
+    /// The operator, null for changes observed on the server
+    pub triggered_by: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+impl From<database::models::MatchStatusChange> for StatusChange {
+    fn from(change: database::models::MatchStatusChange) -> Self {
+        StatusChange {
+            from_status: change.from_status,
+            to_status: change.to_status,
+            source: change.source,
+            event: change.event,
+            triggered_by: change.triggered_by,
+            changed_at: change.changed_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct RoundEconomy {
+    pub map_number: i32,
+    pub round: i32,
+    pub side: GameSide,
+    /// Money of the players on the side before buying
+    pub starting_money: i32,
+    /// Money spent on equipment, less the refunds
+    pub money_spent: i32,
+    /// Value of the equipment the players carried when leaving the buyzone
+    pub equipment_value: i32,
+    pub buy_type: BuyType,
+}
+
+impl From<database::models::MatchRoundEconomy> for RoundEconomy {
+    fn from(economy: database::models::MatchRoundEconomy) -> Self {
+        RoundEconomy {
+            map_number: economy.map_number,
+            round: economy.round,
+            side: economy.side,
+            starting_money: economy.starting_money,
+            money_spent: economy.money_spent,
+            equipment_value: economy.equipment_value,
+            buy_type: economy.buy_type,
+        }
+    }
+}
+
+/// Round played on a map of a match
+#[derive(SimpleObject)]
+pub struct Round {
+    pub match_id: Uuid,
+    pub map_number: i32,
+    /// Number of the round on the map, starting from 1
+    pub number: i32,
+    pub half: i32,
+    /// Overtime the round was played in, 0 for regulation time
+    pub overtime: i32,
+    pub winner_side: GameSide,
+    /// Why the round was won, e.g. `SFUI_Notice_Target_Bombed`
+    pub reason: String,
+    pub ct_score: i32,
+    pub t_score: i32,
+    /// Score of team1 after the round, null until the side of the team is known
+    pub team1_score: Option<i32>,
+    pub team2_score: Option<i32>,
+}
+
+impl From<database::models::MatchRound> for Round {
+    fn from(round: database::models::MatchRound) -> Self {
+        Round {
+            match_id: round.match_id,
+            map_number: round.map_number,
+            number: round.number,
+            half: round.half,
+            overtime: round.overtime,
+            winner_side: round.winner_side,
+            reason: round.reason,
+            ct_score: round.ct_score,
+            t_score: round.t_score,
+            team1_score: round.team1_score,
+            team2_score: round.team2_score,
+        }
+    }
+}
+
+/// Kill in a match being played, see `Subscription::kill_feed`
+#[derive(SimpleObject)]
+pub struct Kill {
+    pub match_id: Uuid,
+    pub map_number: i32,
+    pub round: i32,
+    /// Steam ID of the attacker, or `BOT <nickname>` for bots
+    pub attacker: String,
+    pub attacker_nick: String,
+    pub attacker_side: Option<GameSide>,
+    /// Steam ID of the victim, or `BOT <nickname>` for bots
+    pub victim: String,
+    pub victim_nick: String,
+    pub victim_side: Option<GameSide>,
+    pub weapon: String,
+    pub headshot: bool,
+}
+
+impl From<events::Kill> for Kill {
+    fn from(kill: events::Kill) -> Self {
+        Kill {
+            match_id: kill.combat.match_id,
+            map_number: kill.combat.map_number,
+            round: kill.combat.round,
+            attacker: kill.combat.attacker,
+            attacker_nick: kill.attacker_nick,
+            attacker_side: kill.combat.attacker_side,
+            victim: kill.combat.victim,
+            victim_nick: kill.victim_nick,
+            victim_side: kill.combat.victim_side,
+            weapon: kill.combat.weapon,
+            headshot: kill.combat.headshot,
+        }
+    }
+}
+
+/// Progress of a match being played, see `Subscription::match_updated`
+#[derive(SimpleObject)]
+pub struct MatchUpdate {
+    pub match_id: Uuid,
+    /// Zero-based index of the map being played
+    pub map_number: i32,
+    pub map_name: Option<String>,
+    /// Number of the round being played, 0 before the first round started
+    pub round: i32,
+    /// Score of the teams on the map, null before the first round was won
+    pub team1_score: Option<i32>,
+    pub team2_score: Option<i32>,
+    /// The series is over, or was cancelled
+    pub finished: bool,
+}
+
+impl From<events::MatchUpdate> for MatchUpdate {
+    fn from(update: events::MatchUpdate) -> Self {
+        MatchUpdate {
+            match_id: update.match_id,
+            map_number: update.map_number,
+            map_name: update.map_name,
+            round: update.round,
+            team1_score: update.team1_score,
+            team2_score: update.team2_score,
+            finished: update.finished,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PlayerStats {
+    pub match_id: Uuid,
+    /// Map the statistics are for, null for the whole series
+    pub map_number: Option<i32>,
+    pub steamid: String,
+    pub nick: String,
+    pub rounds: i32,
+    pub kills: i32,
+    pub deaths: i32,
+    pub assists: i32,
+    pub flash_assists: i32,
+    /// Average damage per round
+    pub adr: f64,
+    pub headshot_percentage: f64,
+    /// Percentage of rounds with a kill, assist, survival or trade
+    pub kast: f64,
+    pub entry_kills: i32,
+    pub entry_deaths: i32,
+    /// Rounds with two kills or more
+    pub multi_kill_rounds: i32,
+    pub clutches_played: i32,
+    pub clutches_won: i32,
+    /// HLTV 1.0 rating
+    pub rating: f64,
+    pub utility: UtilityStats,
+}
+
+#[derive(SimpleObject)]
+pub struct UtilityStats {
+    pub flashbangs_thrown: i32,
+    pub smokes_thrown: i32,
+    pub he_grenades_thrown: i32,
+    /// Molotovs and incendiary grenades
+    pub molotovs_thrown: i32,
+    pub decoys_thrown: i32,
+    pub grenades_per_round: f64,
+    pub enemies_flashed: i32,
+    pub teammates_flashed: i32,
+    /// Average time an enemy flashed by the player stayed blind, in seconds
+    pub average_blind_time: f64,
+    pub he_damage: i32,
+    pub molotov_damage: i32,
+}
+
+impl UtilityStats {
+    fn new(utility: &utility::UtilityStats, rounds: i32) -> Self {
+        UtilityStats {
+            flashbangs_thrown: utility.flashbangs_thrown,
+            smokes_thrown: utility.smokes_thrown,
+            he_grenades_thrown: utility.he_grenades_thrown,
+            molotovs_thrown: utility.molotovs_thrown,
+            decoys_thrown: utility.decoys_thrown,
+            grenades_per_round: utility.grenades_per_round(rounds),
+            enemies_flashed: utility.enemies_flashed,
+            teammates_flashed: utility.teammates_flashed,
+            average_blind_time: utility.average_blind_time(),
+            he_damage: utility.he_damage,
+            molotov_damage: utility.molotov_damage,
+        }
+    }
+}
+
+impl PlayerStats {
+    fn new(
+        match_id: Uuid,
+        map_number: Option<i32>,
+        steamid: String,
+        nick: String,
+        stats: &stats::PlayerStats,
+        utility: &utility::UtilityStats,
+    ) -> Self {
+        PlayerStats {
+            match_id,
+            map_number,
+            steamid,
+            nick,
+            rounds: stats.rounds,
+            kills: stats.kills,
+            deaths: stats.deaths,
+            assists: stats.assists,
+            flash_assists: stats.flash_assists,
+            adr: stats.adr(),
+            headshot_percentage: stats.headshot_percentage(),
+            kast: stats.kast(),
+            entry_kills: stats.entry_kills,
+            entry_deaths: stats.entry_deaths,
+            multi_kill_rounds: stats.multi_kill_rounds(),
+            clutches_played: stats.clutches_played,
+            clutches_won: stats.clutches_won,
+            rating: stats.rating(),
+            utility: UtilityStats::new(utility, stats.rounds),
+        }
+    }
+
+    /// Statistics per map, or added up per series
+    ///
+    /// `map_stats` must be sorted by match and map, as returned by the loaders.
+    fn from_map_stats(
+        map_stats: Vec<database::models::PlayerMapStats>,
+        per_map: bool,
+    ) -> Vec<Self> {
+        if per_map {
+            return map_stats
+                .into_iter()
+                .map(|row| {
+                    let stats = row.stats();
+                    let utility = row.utility();
+                    PlayerStats::new(
+                        row.match_id,
+                        Some(row.map_number),
+                        row.steamid,
+                        row.nick,
+                        &stats,
+                        &utility,
+                    )
+                })
+                .collect();
+        }
+
+        let mut series: Vec<(
+            Uuid,
+            String,
+            String,
+            stats::PlayerStats,
+            utility::UtilityStats,
+        )> = Vec::new();
+        for row in map_stats {
+            let stats = row.stats();
+            let utility = row.utility();
+            match series.iter_mut().find(|(match_id, steamid, _, _, _)| {
+                *match_id == row.match_id && *steamid == row.steamid
+            }) {
+                Some((_, _, nick, total, utility_total)) => {
+                    // Nickname on the latest map
+                    *nick = row.nick;
+                    *total += &stats;
+                    *utility_total += &utility;
+                }
+                None => series.push((row.match_id, row.steamid, row.nick, stats, utility)),
+            }
+        }
+
+        series
+            .into_iter()
+            .map(|(match_id, steamid, nick, total, utility_total)| {
+                PlayerStats::new(match_id, None, steamid, nick, &total, &utility_total)
+            })
+            .collect()
+    }
+}
+
+/// Number of nodes in a list, on every page of a connection
+#[derive(SimpleObject)]
+pub struct TotalCount {
+    pub total_count: i64,
+}
+
+/// Filters of `Query::all_matches`, a match must pass every filter that is set
+#[derive(InputObject, Default)]
+pub struct MatchFilter {
+    /// Team playing the match, as team1 or team2
+    pub team: Option<Uuid>,
+    pub server: Option<Uuid>,
+    /// Earliest creation time, inclusive
+    pub created_after: Option<NaiveDateTime>,
+    /// Latest creation time, exclusive
+    pub created_before: Option<NaiveDateTime>,
+    /// Part of the title of the match or of the name of a team, case-insensitive
+    pub search: Option<String>,
+    pub status: Option<MatchStatus>,
+}
+
+impl From<MatchFilter> for database::MatchFilter {
+    fn from(filter: MatchFilter) -> Self {
+        database::MatchFilter {
+            team_id: filter.team,
+            server_id: filter.server,
+            created_after: filter.created_after,
+            created_before: filter.created_before,
+            search: filter.search,
+            status: filter.status,
+        }
+    }
+}
+
+/// Match loaded on a server, see `Mutation::load_match`
+#[derive(SimpleObject)]
+pub struct LoadedMatch {
+    pub r#match: Uuid,
+    pub server: Uuid,
+    /// Password players need to join the server
+    pub player_password: String,
+}
+
 #[derive(SimpleObject)]
 pub struct Spectator {
     pub steamid: String,
     // Note: Steam profile might be hidden which prevents us from fetching the name of the steamid
     pub name: Option<String>,
 }
+
+impl From<database::models::Spectator> for Spectator {
+    fn from(spectator: database::models::Spectator) -> Self {
+        Spectator {
+            steamid: spectator.steamid,
+            name: None,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use serde_json::{json, Value as JsonValue};
+use sqlx::Acquire;
+use sqlx::Postgres;
+use tide::{Body, Response, StatusCode};
+use tide_sqlx::SQLxRequestExt;
+use uuid::Uuid;
+
+use crate::common::{CombatKind, GameSide};
+use crate::database::get_map_combat;
+use crate::database::models::MatchCombat;
+use crate::web::State;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    GeoJson,
+    Csv,
+}
+
+#[derive(Deserialize, Debug)]
+struct HeatmapArgs {
+    #[serde(default)]
+    format: ExportFormat,
+    /// Match the kills and damage are exported of, a map of every match would be unbounded
+    r#match: Uuid,
+}
+
+/// Exports the kills and damage on a map, with the position of the attacker and the victim
+///
+/// The map is given by its name (e.g. `de_inferno`) in the `map_name` parameter of the path, and
+/// the match by its id in the required `match` query parameter. The events are exported as a
+/// GeoJSON feature collection with a point for each player involved, or as CSV with a row for each
+/// event.
+pub async fn endpoint_heatmap_export(req: tide::Request<State>) -> tide::Result<Response> {
+    let mut pool = req.sqlx_conn::<Postgres>().await;
+    let db_conn = pool.acquire().await?;
+
+    let map_name = req.param("map_name")?.to_string();
+    let args = req.query::<HeatmapArgs>()?;
+
+    let combat = match get_map_combat(db_conn, map_name.as_str(), args.r#match).await {
+        Ok(combat) => combat,
+        Err(err) => {
+            return tide::Result::Err(tide::Error::new(StatusCode::InternalServerError, err));
+        }
+    };
+
+    let mut resp = Response::new(StatusCode::Ok);
+    match args.format {
+        ExportFormat::GeoJson => {
+            resp.set_body(Body::from_json(&geojson(&combat))?);
+            resp.set_content_type("application/geo+json");
+        }
+        ExportFormat::Csv => {
+            resp.set_body(csv(&combat));
+            resp.set_content_type("text/csv");
+        }
+    }
+    Ok(resp)
+}
+
+fn kind_name(kind: CombatKind) -> &'static str {
+    match kind {
+        CombatKind::Kill => "kill",
+        CombatKind::Damage => "damage",
+    }
+}
+
+fn side_name(side: Option<GameSide>) -> &'static str {
+    match side {
+        Some(GameSide::CT) => "ct",
+        Some(GameSide::T) => "t",
+        None => "",
+    }
+}
+
+/// Feature collection with a point at the position of the attacker, and one at the position of
+/// the victim, for every event
+fn geojson(combat: &[MatchCombat]) -> JsonValue {
+    let features = combat
+        .iter()
+        .flat_map(|event| {
+            let point = |role: &str, player: &str, side, x, y, z| {
+                json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Point",
+                        "coordinates": [x, y, z],
+                    },
+                    "properties": {
+                        "role": role,
+                        "player": player,
+                        "side": side_name(side),
+                        "kind": kind_name(event.kind),
+                        "match_id": event.match_id.to_string(),
+                        "map_number": event.map_number,
+                        "map_name": event.map_name,
+                        "round": event.round,
+                        "weapon": event.weapon,
+                        "damage": event.damage,
+                        "headshot": event.headshot,
+                    },
+                })
+            };
+
+            vec![
+                point(
+                    "attacker",
+                    event.attacker.as_str(),
+                    event.attacker_side,
+                    event.attacker_x,
+                    event.attacker_y,
+                    event.attacker_z,
+                ),
+                point(
+                    "victim",
+                    event.victim.as_str(),
+                    event.victim_side,
+                    event.victim_x,
+                    event.victim_y,
+                    event.victim_z,
+                ),
+            ]
+        })
+        .collect::<Vec<JsonValue>>();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(combat: &[MatchCombat]) -> String {
+    let mut csv = String::from(
+        "match_id,map_number,map_name,round,kind,attacker,attacker_side,attacker_x,attacker_y,attacker_z,victim,victim_side,victim_x,victim_y,victim_z,weapon,damage,headshot\n",
+    );
+
+    for event in combat {
+        let row = [
+            event.match_id.to_string(),
+            event.map_number.to_string(),
+            csv_field(event.map_name.as_str()),
+            event.round.to_string(),
+            kind_name(event.kind).to_string(),
+            csv_field(event.attacker.as_str()),
+            side_name(event.attacker_side).to_string(),
+            event.attacker_x.to_string(),
+            event.attacker_y.to_string(),
+            event.attacker_z.to_string(),
+            csv_field(event.victim.as_str()),
+            side_name(event.victim_side).to_string(),
+            event.victim_x.to_string(),
+            event.victim_y.to_string(),
+            event.victim_z.to_string(),
+            csv_field(event.weapon.as_str()),
+            event.damage.to_string(),
+            event.headshot.to_string(),
+        ];
+        csv.push_str(row.join(",").as_str());
+        csv.push('\n');
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::common::{CombatKind, GameSide};
+    use crate::database::models::MatchCombat;
+
+    use super::{csv, csv_field, geojson};
+
+    fn kill() -> MatchCombat {
+        MatchCombat {
+            id: 1,
+            match_id: Uuid::nil(),
+            map_number: 0,
+            map_name: "de_inferno".to_string(),
+            round: 3,
+            kind: CombatKind::Kill,
+            attacker: "STEAM_1:0:1".to_string(),
+            attacker_side: Some(GameSide::CT),
+            attacker_x: 10,
+            attacker_y: 20,
+            attacker_z: 30,
+            victim: "BOT Albert".to_string(),
+            victim_side: Some(GameSide::T),
+            victim_x: -10,
+            victim_y: -20,
+            victim_z: -30,
+            weapon: "ak47".to_string(),
+            damage: 0,
+            headshot: true,
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("ak47"), "ak47");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn csv_export() {
+        let csv = csv(&[kill()]);
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("match_id,map_number,map_name,round,"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "00000000-0000-0000-0000-000000000000,0,de_inferno,3,kill,STEAM_1:0:1,ct,10,20,30,BOT Albert,t,-10,-20,-30,ak47,0,true"
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn geojson_export() {
+        let geojson = geojson(&[kill()]);
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["geometry"]["coordinates"], json!([10, 20, 30]));
+        assert_eq!(features[0]["properties"]["role"], "attacker");
+        assert_eq!(
+            features[1]["geometry"]["coordinates"],
+            json!([-10, -20, -30])
+        );
+        assert_eq!(features[1]["properties"]["player"], "BOT Albert");
+        assert_eq!(features[1]["properties"]["side"], "t");
+    }
+}
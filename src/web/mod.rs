@@ -2,33 +2,82 @@ use sqlx::{Pool, Postgres};
 use tide_sqlx::SQLxMiddleware;
 use tide_tracing::TraceMiddleware;
 
-//pub use crate::csgo::receiver::http::handler_log_receiver;
+use crate::csgo::rcon::RconManager;
+pub use crate::csgo::receiver::http::handler_log_receiver;
+use crate::csgo::receiver::LogRouter;
+use crate::events::EventBus;
 //pub use crate::get5::handler_get5_config;
+use crate::web::console::endpoint_rcon_console;
 use crate::web::get5::endpoint_get5_config;
-use crate::web::graphql::init_schema;
+use crate::web::graphql::{endpoint_graphql, init_schema};
+use crate::web::heatmap::endpoint_heatmap_export;
 
-pub type State = ();
+#[derive(Clone)]
+pub struct State {
+    /// Passes the logs received from the game servers on to the log processors
+    pub log_router: LogRouter,
+    pub rcon: RconManager,
+    /// Access to the RCON console, `None` if it is disabled
+    pub console: Option<ConsoleConfig>,
+    /// Tokens of the operators allowed to control the matches, see `graphql::Operator`
+    pub operators: Vec<OperatorToken>,
+}
+
+/// Address at which the game servers reach the web server, e.g. `http://10.0.0.2:8080`
+#[derive(Clone, Debug)]
+pub struct PublicUrl(pub String);
+
+pub use console::ConsoleConfig;
+pub use graphql::OperatorToken;
 
+mod console;
 mod get5;
 mod graphql;
+mod heatmap;
+pub(crate) mod token;
 
-pub async fn webserver_start(db_pool: Pool<Postgres>) -> anyhow::Result<()> {
+pub async fn webserver_start(
+    db_pool: Pool<Postgres>,
+    log_router: LogRouter,
+    rcon: RconManager,
+    events: EventBus,
+    public_url: PublicUrl,
+    console: Option<ConsoleConfig>,
+    operators: Vec<OperatorToken>,
+) -> anyhow::Result<()> {
     // Setup http server
-    let mut app = tide::new();
+    let mut app = tide::with_state(State {
+        log_router,
+        rcon: rcon.clone(),
+        console,
+        operators,
+    });
     app.with(SQLxMiddleware::from(db_pool.clone()));
     app.with(TraceMiddleware::new());
 
     // TODO setup routes
     app.at("/")
-        .get(|_req: tide::Request<()>| async move { Ok("hello world") });
+        .get(|_req: tide::Request<State>| async move { Ok("hello world") });
 
     // Get5 configuration files
     app.at("/api/get5/config").get(endpoint_get5_config);
 
-    // GraphQL endpoint
-    let schema = init_schema(db_pool.clone());
+    // Kills and damage with positions, see `endpoint_heatmap_export`
+    app.at("/api/heatmaps/:map_name")
+        .get(endpoint_heatmap_export);
+
+    // RCON console over a WebSocket, see `endpoint_rcon_console`
+    app.at("/api/servers/:server_id/console")
+        .get(endpoint_rcon_console);
+
+    // Game server logs, see `logaddress_add_http`
+    app.at("/api/logs/:server_id").post(handler_log_receiver);
+
+    // GraphQL endpoint, the subscriptions are served over a WebSocket (graphql-ws)
+    let schema = init_schema(db_pool.clone(), rcon, events, public_url);
     app.at("/api/graphql")
-        .post(async_graphql_tide::endpoint(schema));
+        .get(async_graphql_tide::Subscription::new(schema.clone()))
+        .post(move |req: tide::Request<State>| endpoint_graphql(schema.clone(), req));
 
     // Start http server
     app.listen("127.0.0.1:8080").await?;
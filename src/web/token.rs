@@ -0,0 +1,46 @@
+use crate::web::State;
+
+#[derive(Deserialize, Debug)]
+struct TokenArgs {
+    token: Option<String>,
+}
+
+/// Compares the secrets in constant time, such that the token can't be guessed from the time it
+/// takes to reject it
+pub(crate) fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Token given with the request, in the `token` query parameter or as a bearer token
+pub(crate) fn request_token(req: &tide::Request<State>) -> tide::Result<Option<String>> {
+    if let Some(token) = req.query::<TokenArgs>()?.token {
+        return Ok(Some(token));
+    }
+
+    let token = req.header("Authorization").and_then(|values| {
+        values
+            .last()
+            .as_str()
+            .strip_prefix("Bearer ")
+            .map(str::to_string)
+    });
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::token_matches;
+
+    #[test]
+    fn token_comparison() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret1", "secret"));
+        assert!(!token_matches("", "secret"));
+    }
+}